@@ -1,14 +1,21 @@
 use cosmwasm_std::{
-    attr, to_json_binary, Addr, Binary, BlockInfo, CosmosMsg, Deps, DepsMut, Env, MessageInfo,
-    Response, StdError, StdResult, Storage, Uint128, WasmMsg,
+    attr, Addr, Binary, BlockInfo, Deps, DepsMut, Env, Event, MessageInfo, Response, StdError,
+    StdResult, Storage, Uint128,
 };
-use cw20::{AllowanceResponse, Cw20ReceiveMsg, Expiration};
-
-use crate::msg::Cw20TaxedExecuteMsg as ExecuteMsg;
+use cw20::{AllowanceResponse, Expiration};
 
 use crate::error::ContractError;
+use crate::events::{Cw20TaxEvent, Cw20TransferEvent};
+use crate::receiver::{receive_msg, TaxedCw20ReceiveMsg};
+use crate::referral::pay_referral;
+use crate::settlement::settle_tax_msgs;
 use crate::state::{
-    ALLOWANCES, ALLOWANCES_SPENDER, ANTI_WHALE_INFO, BALANCES, TAX_INFO, TOKEN_INFO,
+    ALLOWANCES, ALLOWANCES_SPENDER, ANTI_WHALE_INFO, BALANCES, TAX_INFO, TAX_MAP_VERSION,
+    TOKEN_INFO,
+};
+use crate::tax::{
+    debit_payer, pay_native_tax, record_acquisition, record_outgoing_volume, TaxAction, TaxContext,
+    TaxDeduction,
 };
 
 pub fn execute_increase_allowance(
@@ -136,26 +143,39 @@ pub fn execute_transfer_from(
     let rcpt_addr = deps.api.addr_validate(&recipient)?;
     let owner_addr = deps.api.addr_validate(&owner)?;
     let map = TAX_INFO.load(deps.storage)?;
-    let rcpt_proceeds = map.on_transfer_from.proceeds.clone().into_string();
-    let (net, tax) = map.on_transfer_from.deduct_tax(
-        &deps.querier,
+    let tax_map_version = TAX_MAP_VERSION.may_load(deps.storage)?.unwrap_or_default();
+    let mut deduction = map.on_transfer_from.deduct(
+        &TaxContext::new(deps.as_ref(), &env),
         owner_addr.clone(),
         rcpt_addr.clone(),
         amount,
     )?;
+    let native_tax = pay_native_tax(
+        &TaxContext::new(deps.as_ref(), &env),
+        &map.on_transfer_from,
+        &mut deduction,
+        amount,
+        &info.sender,
+        &info.funds,
+    )?;
+    let TaxDeduction {
+        net,
+        gross,
+        tax,
+        proceeds,
+        ..
+    } = deduction;
+    let rcpt_proceeds = proceeds.to_string();
+    record_outgoing_volume(deps.storage, &env.block, &map, &owner_addr, amount)?;
+    let referral = pay_referral(deps.storage, &map.on_transfer_from, &owner_addr, tax)?;
+    let proceeds_tax = tax - referral.as_ref().map(|x| x.amount).unwrap_or_default();
     let whale_info = ANTI_WHALE_INFO.load(deps.storage)?;
 
     // deduct allowance before doing anything else have enough allowance
-    deduct_allowance(deps.storage, &owner_addr, &info.sender, &env.block, amount)?;
+    deduct_allowance(deps.storage, &owner_addr, &info.sender, &env.block, gross)?;
 
     // reduce owners balance
-    BALANCES.update(
-        deps.storage,
-        &owner_addr,
-        |balance: Option<Uint128>| -> StdResult<_> {
-            Ok(balance.unwrap_or_default().checked_sub(amount)?)
-        },
-    )?;
+    debit_payer(deps.storage, &owner_addr, amount, gross)?;
 
     // move tax to token contract
     BALANCES.update(
@@ -170,36 +190,75 @@ pub fn execute_transfer_from(
         &rcpt_addr,
         |balance: Option<Uint128>| -> StdResult<_> { Ok(balance.unwrap_or_default() + net) },
     )?;
+    record_acquisition(deps.storage, &env.block, &rcpt_addr, net)?;
 
     // assert whale policy
     let new_balance = BALANCES.load(deps.storage, &rcpt_addr)?;
     whale_info.assert_no_whale(deps.as_ref().storage, &rcpt_addr, new_balance)?;
 
-    // construct msg to send tax to proceeds wallet
-    let tax_msg = CosmosMsg::Wasm(WasmMsg::Execute {
-        contract_addr: env.contract.address.into(),
-        msg: to_json_binary(&ExecuteMsg::Transfer {
-            recipient: rcpt_proceeds.clone(),
-            amount: tax,
-        })?,
-        funds: vec![],
-    });
+    // construct msgs to settle the tax shares as transfers of the payer
+    let tax_msgs = settle_tax_msgs(
+        deps.storage,
+        &env.contract.address,
+        &owner_addr,
+        &proceeds,
+        proceeds_tax,
+        referral.as_ref(),
+    )?;
 
-    let res = Response::new().add_attributes(vec![
+    let mut events = vec![Event::from(Cw20TransferEvent {
+        action: "transfer_from",
+        from: &owner_addr,
+        to: &rcpt_addr,
+        by: Some(&info.sender),
+        amount,
+        net,
+        gross,
+    })];
+    if !tax.is_zero() || native_tax.is_some() {
+        events.push(
+            Cw20TaxEvent {
+                tax_action: &TaxAction::OnTransferFrom,
+                payer: &owner_addr,
+                proceeds: &rcpt_proceeds,
+                tax,
+                tax_mode: &map.on_transfer_from.mode,
+                tax_map_version,
+                referral: referral.as_ref(),
+                native_tax: native_tax.as_ref().map(|x| &x.tax),
+            }
+            .into(),
+        );
+    }
+
+    let mut res = Response::new().add_events(events).add_attributes(vec![
         attr("action", "transfer_from"),
         attr("from", owner),
         attr("to", recipient),
         attr("by", info.sender),
-        attr("amount", amount),
+        attr("amount", net),
     ]);
+    if let Some(native_tax) = native_tax {
+        res = res
+            .add_attribute("native_tax", native_tax.tax.to_string())
+            .add_messages(native_tax.messages);
+    }
 
     if tax.gt(&Uint128::zero()) {
-        let tax_res = res
+        let mut tax_res = res
             .clone()
             .add_attribute("net", net)
             .add_attribute("tax", tax)
+            .add_attribute("gross", gross)
+            .add_attribute("tax_mode", map.on_transfer_from.mode.to_string())
             .add_attribute("proceeds", &rcpt_proceeds)
-            .add_message(tax_msg);
+            .add_attribute("tax_map_version", tax_map_version.to_string())
+            .add_submessages(tax_msgs);
+        if let Some(referral) = referral {
+            tax_res = tax_res
+                .add_attribute("referrer", referral.referrer)
+                .add_attribute("referral", referral.amount);
+        }
         return Ok(tax_res);
     }
 
@@ -254,31 +313,47 @@ pub fn execute_send_from(
     let rcpt_addr = deps.api.addr_validate(&contract)?;
     let owner_addr = deps.api.addr_validate(&owner)?;
     let map = TAX_INFO.load(deps.storage)?;
-    let rcpt_proceeds = map.on_send_from.proceeds.clone().into_string();
-    let (net, tax) = map.on_send_from.deduct_tax(
-        &deps.querier,
+    let tax_map_version = TAX_MAP_VERSION.may_load(deps.storage)?.unwrap_or_default();
+    let mut deduction = map.on_send_from.deduct(
+        &TaxContext::new(deps.as_ref(), &env)
+            .with_hook(&msg)
+            .with_payer(&owner_addr),
         info.sender.clone(),
         rcpt_addr.clone(),
         amount,
     )?;
+    let native_tax = pay_native_tax(
+        &TaxContext::new(deps.as_ref(), &env),
+        &map.on_send_from,
+        &mut deduction,
+        amount,
+        &info.sender,
+        &info.funds,
+    )?;
+    let TaxDeduction {
+        net,
+        gross,
+        tax,
+        proceeds,
+        ..
+    } = deduction;
+    let rcpt_proceeds = proceeds.to_string();
+    record_outgoing_volume(deps.storage, &env.block, &map, &owner_addr, amount)?;
+    let referral = pay_referral(deps.storage, &map.on_send_from, &owner_addr, tax)?;
+    let proceeds_tax = tax - referral.as_ref().map(|x| x.amount).unwrap_or_default();
     let whale_info = ANTI_WHALE_INFO.load(deps.storage)?;
 
     // deduct allowance before doing anything else have enough allowance
-    deduct_allowance(deps.storage, &owner_addr, &info.sender, &env.block, amount)?;
+    deduct_allowance(deps.storage, &owner_addr, &info.sender, &env.block, gross)?;
 
     // move net tokens to the contract
-    BALANCES.update(
-        deps.storage,
-        &owner_addr.clone(),
-        |balance: Option<Uint128>| -> StdResult<_> {
-            Ok(balance.unwrap_or_default().checked_sub(amount)?)
-        },
-    )?;
+    debit_payer(deps.storage, &owner_addr, amount, gross)?;
     BALANCES.update(
         deps.storage,
         &rcpt_addr,
         |balance: Option<Uint128>| -> StdResult<_> { Ok(balance.unwrap_or_default() + net) },
     )?;
+    record_acquisition(deps.storage, &env.block, &rcpt_addr, net)?;
 
     // assert whale policy
     let new_balance = BALANCES.load(deps.storage, &rcpt_addr)?;
@@ -292,39 +367,84 @@ pub fn execute_send_from(
     )?;
 
     // construct msg for net amount
-    let net_msg = Cw20ReceiveMsg {
-        sender: info.sender.clone().into(),
-        amount: net,
-        msg,
+    let net_msg = receive_msg(
+        deps.storage,
+        &rcpt_addr,
+        TaxedCw20ReceiveMsg {
+            sender: info.sender.to_string(),
+            amount: net,
+            gross,
+            tax,
+            tax_action: TaxAction::OnSendFrom,
+            msg,
+        },
+    )?;
+
+    // construct msgs to settle the tax shares as transfers of the payer
+    let tax_msgs = settle_tax_msgs(
+        deps.storage,
+        &env.contract.address,
+        &owner_addr,
+        &proceeds,
+        proceeds_tax,
+        referral.as_ref(),
+    )?;
+
+    let mut events = vec![Event::from(Cw20TransferEvent {
+        action: "send_from",
+        from: &owner_addr,
+        to: &rcpt_addr,
+        by: Some(&info.sender),
+        amount,
+        net,
+        gross,
+    })];
+    if !tax.is_zero() || native_tax.is_some() {
+        events.push(
+            Cw20TaxEvent {
+                tax_action: &TaxAction::OnSendFrom,
+                payer: &owner_addr,
+                proceeds: &rcpt_proceeds,
+                tax,
+                tax_mode: &map.on_send_from.mode,
+                tax_map_version,
+                referral: referral.as_ref(),
+                native_tax: native_tax.as_ref().map(|x| &x.tax),
+            }
+            .into(),
+        );
     }
-    .into_cosmos_msg(contract)?;
-
-    // construct msg to send tax to proceeds wallet
-    let tax_msg = CosmosMsg::Wasm(WasmMsg::Execute {
-        contract_addr: env.contract.address.into(),
-        msg: to_json_binary(&ExecuteMsg::Transfer {
-            recipient: rcpt_proceeds.clone(),
-            amount: tax,
-        })?,
-        funds: vec![],
-    });
 
     // emit
-    let res = Response::new()
+    let mut res = Response::new()
+        .add_events(events)
         .add_attribute("action", "send_from")
-        .add_attribute("from", &info.sender.clone().into_string())
+        .add_attribute("from", &owner_addr)
         .add_attribute("to", &rcpt_addr)
         .add_attribute("by", &info.sender)
-        .add_attribute("amount", amount)
+        .add_attribute("amount", net)
         .add_message(net_msg);
+    if let Some(native_tax) = native_tax {
+        res = res
+            .add_attribute("native_tax", native_tax.tax.to_string())
+            .add_messages(native_tax.messages);
+    }
 
     if tax.gt(&Uint128::zero()) {
-        let tax_res = res
+        let mut tax_res = res
             .clone()
             .add_attribute("net", net)
             .add_attribute("tax", tax)
+            .add_attribute("gross", gross)
+            .add_attribute("tax_mode", map.on_send_from.mode.to_string())
             .add_attribute("proceeds", &rcpt_proceeds)
-            .add_message(tax_msg);
+            .add_attribute("tax_map_version", tax_map_version.to_string())
+            .add_submessages(tax_msgs);
+        if let Some(referral) = referral {
+            tax_res = tax_res
+                .add_attribute("referrer", referral.referrer)
+                .add_attribute("referral", referral.amount);
+        }
         return Ok(tax_res);
     }
 
@@ -345,12 +465,19 @@ mod tests {
     use super::*;
 
     use cosmwasm_std::testing::{mock_dependencies_with_balance, mock_env, mock_info};
-    use cosmwasm_std::{coins, CosmosMsg, Decimal, Empty, SubMsg, Timestamp, WasmMsg};
-    use cw20::{Cw20Coin, TokenInfoResponse};
+    use cosmwasm_std::{
+        coins, to_json_binary, CosmosMsg, Decimal, Empty, SubMsg, Timestamp, WasmMsg,
+    };
+    use cw20::{Cw20Coin, Cw20ReceiveMsg, TokenInfoResponse};
 
-    use crate::contract::{execute, instantiate, query_balance, query_token_info};
+    use crate::contract::{
+        execute, instantiate, query_balance, query_tax_free_volume, query_token_info,
+    };
     use crate::msg::{Cw20TaxedExecuteMsg as ExecuteMsg, InstantiateMsg};
-    use crate::tax::{TaxAlwaysCondition, TaxCondition, TaxInfo, TaxMap, TaxNeverCondition};
+    use crate::tax::{
+        TaxAddressCondition, TaxAlwaysCondition, TaxCondition, TaxDiscountTier, TaxInfo, TaxMap,
+        TaxMode, TaxNeverCondition, TaxVolumeCondition,
+    };
 
     fn get_balance<T: Into<String>>(deps: Deps, address: T) -> Uint128 {
         query_balance(deps, address.into()).unwrap().balance
@@ -374,6 +501,9 @@ mod tests {
             marketing: None,
             tax_map: None,
             whale_info: None,
+            max_tax_rate: None,
+            tax_rate_limit: None,
+            approval: None,
         };
         let info = mock_info("creator", &[]);
         let env = mock_env();
@@ -392,16 +522,34 @@ mod tests {
                 src_cond: TaxCondition::Never(TaxNeverCondition {}),
                 dst_cond: TaxCondition::Never(TaxNeverCondition {}),
                 proceeds: Addr::unchecked(""),
+                admin: None,
+                discounts: vec![],
+                referral_share: Decimal::zero(),
+                mode: TaxMode::Deduct,
+                native_payment: None,
+                rules: None,
             },
             on_send: TaxInfo {
                 src_cond: TaxCondition::Never(TaxNeverCondition {}),
                 dst_cond: TaxCondition::Never(TaxNeverCondition {}),
                 proceeds: Addr::unchecked(""),
+                admin: None,
+                discounts: vec![],
+                referral_share: Decimal::zero(),
+                mode: TaxMode::Deduct,
+                native_payment: None,
+                rules: None,
             },
             on_send_from: TaxInfo {
                 src_cond: TaxCondition::Never(TaxNeverCondition {}),
                 dst_cond: TaxCondition::Never(TaxNeverCondition {}),
                 proceeds: Addr::unchecked(""),
+                admin: None,
+                discounts: vec![],
+                referral_share: Decimal::zero(),
+                mode: TaxMode::Deduct,
+                native_payment: None,
+                rules: None,
             },
             on_transfer_from: TaxInfo {
                 src_cond: TaxCondition::Always(TaxAlwaysCondition {
@@ -411,6 +559,12 @@ mod tests {
                     tax_rate: Decimal::percent(10),
                 }),
                 proceeds: Addr::unchecked(String::from("proceeds")),
+                admin: None,
+                discounts: vec![],
+                referral_share: Decimal::zero(),
+                mode: TaxMode::Deduct,
+                native_payment: None,
+                rules: None,
             },
             admin: Addr::unchecked(""),
         });
@@ -427,6 +581,9 @@ mod tests {
             marketing: None,
             tax_map: tax_map_in,
             whale_info: None,
+            max_tax_rate: None,
+            tax_rate_limit: None,
+            approval: None,
         };
         let info = mock_info("creator", &[]);
         let env = mock_env();
@@ -458,11 +615,23 @@ mod tests {
                 src_cond: TaxCondition::Never(TaxNeverCondition {}),
                 dst_cond: TaxCondition::Never(TaxNeverCondition {}),
                 proceeds: Addr::unchecked(""),
+                admin: None,
+                discounts: vec![],
+                referral_share: Decimal::zero(),
+                mode: TaxMode::Deduct,
+                native_payment: None,
+                rules: None,
             },
             on_send: TaxInfo {
                 src_cond: TaxCondition::Never(TaxNeverCondition {}),
                 dst_cond: TaxCondition::Never(TaxNeverCondition {}),
                 proceeds: Addr::unchecked(""),
+                admin: None,
+                discounts: vec![],
+                referral_share: Decimal::zero(),
+                mode: TaxMode::Deduct,
+                native_payment: None,
+                rules: None,
             },
             on_send_from: TaxInfo {
                 src_cond: TaxCondition::Always(TaxAlwaysCondition {
@@ -472,11 +641,23 @@ mod tests {
                     tax_rate: Decimal::percent(10),
                 }),
                 proceeds: Addr::unchecked(String::from("proceeds")),
+                admin: None,
+                discounts: vec![],
+                referral_share: Decimal::zero(),
+                mode: TaxMode::Deduct,
+                native_payment: None,
+                rules: None,
             },
             on_transfer_from: TaxInfo {
                 src_cond: TaxCondition::Never(TaxNeverCondition {}),
                 dst_cond: TaxCondition::Never(TaxNeverCondition {}),
                 proceeds: Addr::unchecked(""),
+                admin: None,
+                discounts: vec![],
+                referral_share: Decimal::zero(),
+                mode: TaxMode::Deduct,
+                native_payment: None,
+                rules: None,
             },
             admin: Addr::unchecked(""),
         });
@@ -493,6 +674,9 @@ mod tests {
             marketing: None,
             tax_map: tax_map_in,
             whale_info: None,
+            max_tax_rate: None,
+            tax_rate_limit: None,
+            approval: None,
         };
         let info = mock_info("creator", &[]);
         let env = mock_env();
@@ -513,6 +697,54 @@ mod tests {
         meta
     }
 
+    // "owner" holds 10000 tokens and allows "spender" to send all of them
+    // under the given send_from tax
+    fn do_instantiate_with_send_from_info(mut deps: DepsMut, on_send_from: TaxInfo) {
+        let instantiate_msg = InstantiateMsg {
+            name: "Auto Gen".to_string(),
+            symbol: "AUTO".to_string(),
+            decimals: 3,
+            initial_balances: vec![Cw20Coin {
+                address: String::from("owner"),
+                amount: Uint128::new(10000),
+            }],
+            mint: None,
+            marketing: None,
+            tax_map: Some(TaxMap {
+                on_send_from,
+                admin: Addr::unchecked("admin"),
+                ..TaxMap::default()
+            }),
+            whale_info: None,
+            max_tax_rate: None,
+            tax_rate_limit: None,
+            approval: None,
+        };
+        instantiate(
+            deps.branch(),
+            mock_env(),
+            mock_info("creator", &[]),
+            instantiate_msg,
+        )
+        .unwrap();
+        let msg = ExecuteMsg::IncreaseAllowance {
+            spender: String::from("spender"),
+            amount: Uint128::new(10000),
+            expires: None,
+        };
+        execute(deps, mock_env(), mock_info("owner", &[]), msg).unwrap();
+    }
+
+    fn send_from(deps: DepsMut, amount: u128) -> Response {
+        let msg = ExecuteMsg::SendFrom {
+            owner: String::from("owner"),
+            contract: String::from("pool"),
+            amount: Uint128::new(amount),
+            msg: Binary::default(),
+        };
+        execute(deps, mock_env(), mock_info("spender", &[]), msg).unwrap()
+    }
+
     #[test]
     fn transfer_from_with_tax() {
         let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
@@ -524,7 +756,8 @@ mod tests {
         let expected_remainder = amount1.checked_sub(transfer).unwrap();
         let expected_tax = Uint128::from(7654u128);
         let expected_net = Uint128::from(68889u128);
-        let expected_tfer_msg = ExecuteMsg::Transfer {
+        let expected_tfer_msg = ExecuteMsg::SettleTax {
+            payer: addr1.clone(),
             recipient: String::from("proceeds"),
             amount: expected_tax.clone(),
         };
@@ -589,6 +822,95 @@ mod tests {
         );
     }
 
+    #[test]
+    fn transfer_from_with_tax_on_top() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        let owner = String::from("addr0001");
+        let spender = String::from("addr0000");
+        let tax_map = TaxMap {
+            on_transfer_from: TaxInfo {
+                src_cond: TaxCondition::Always(TaxAlwaysCondition {
+                    tax_rate: Decimal::percent(10),
+                }),
+                dst_cond: TaxCondition::Always(TaxAlwaysCondition {
+                    tax_rate: Decimal::zero(),
+                }),
+                proceeds: Addr::unchecked("proceeds"),
+                mode: TaxMode::OnTop,
+                ..TaxInfo::default()
+            },
+            admin: Addr::unchecked("admin"),
+            ..TaxMap::default()
+        };
+        let instantiate_msg = InstantiateMsg {
+            name: "Auto Gen".to_string(),
+            symbol: "AUTO".to_string(),
+            decimals: 3,
+            initial_balances: vec![Cw20Coin {
+                address: owner.clone(),
+                amount: Uint128::new(1000),
+            }],
+            mint: None,
+            marketing: None,
+            tax_map: Some(tax_map),
+            whale_info: None,
+            max_tax_rate: None,
+            tax_rate_limit: None,
+            approval: None,
+        };
+        let env = mock_env();
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            instantiate_msg,
+        )
+        .unwrap();
+
+        let increase_allowance = |deps: DepsMut, amount: u128| {
+            let msg = ExecuteMsg::IncreaseAllowance {
+                spender: spender.clone(),
+                amount: Uint128::new(amount),
+                expires: None,
+            };
+            execute(deps, env.clone(), mock_info(owner.as_ref(), &[]), msg).unwrap();
+        };
+        let transfer_from = |deps: DepsMut, amount: u128| {
+            let msg = ExecuteMsg::TransferFrom {
+                owner: owner.clone(),
+                recipient: String::from("recipient"),
+                amount: Uint128::new(amount),
+            };
+            execute(deps, env.clone(), mock_info(spender.as_ref(), &[]), msg)
+        };
+
+        // the allowance has to cover the tax on top
+        increase_allowance(deps.as_mut(), 100);
+        let err = transfer_from(deps.as_mut(), 100).unwrap_err();
+        assert!(matches!(err, ContractError::Std(StdError::Overflow { .. })));
+        increase_allowance(deps.as_mut(), 10);
+        let res = transfer_from(deps.as_mut(), 100).unwrap();
+        assert!(res.attributes.contains(&attr("gross", "110")));
+        assert!(res.attributes.contains(&attr("tax_mode", "on_top")));
+        assert_eq!(get_balance(deps.as_ref(), "recipient"), Uint128::new(100));
+        assert_eq!(get_balance(deps.as_ref(), owner.clone()), Uint128::new(890));
+        assert_eq!(
+            get_balance(deps.as_ref(), "cosmos2contract"),
+            Uint128::new(10)
+        );
+
+        // a short balance names the gross amount required
+        increase_allowance(deps.as_mut(), 10000);
+        let err = transfer_from(deps.as_mut(), 850).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InsufficientBalance {
+                required: Uint128::new(935),
+                balance: Uint128::new(890),
+            }
+        );
+    }
+
     #[test]
     fn send_from_with_tax() {
         let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
@@ -600,7 +922,8 @@ mod tests {
         let expected_remainder = amount1.checked_sub(transfer).unwrap();
         let expected_tax = Uint128::from(7654u128);
         let expected_net = Uint128::from(68889u128);
-        let expected_tfer_msg = ExecuteMsg::Transfer {
+        let expected_tfer_msg = ExecuteMsg::SettleTax {
+            payer: addr1.clone(),
             recipient: String::from("proceeds"),
             amount: expected_tax.clone(),
         };
@@ -688,6 +1011,94 @@ mod tests {
         );
     }
 
+    #[test]
+    fn send_from_counts_volume_of_the_owner() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        do_instantiate_with_send_from_info(
+            deps.as_mut(),
+            TaxInfo {
+                src_cond: TaxCondition::Volume(TaxVolumeCondition {
+                    allowance: Uint128::new(1000),
+                    window: 24 * 60 * 60,
+                    tax_rate: Decimal::percent(10),
+                }),
+                dst_cond: TaxCondition::Always(TaxAlwaysCondition {
+                    tax_rate: Decimal::zero(),
+                }),
+                proceeds: Addr::unchecked("proceeds"),
+                ..TaxInfo::default()
+            },
+        );
+        send_from(deps.as_mut(), 600);
+
+        let remaining = |address: &str| {
+            query_tax_free_volume(deps.as_ref(), mock_env(), address.to_string())
+                .unwrap()
+                .headroom[0]
+                .remaining
+        };
+        assert_eq!(remaining("owner"), Uint128::new(400));
+        assert_eq!(remaining("spender"), Uint128::new(1000));
+    }
+
+    #[test]
+    fn send_from_pays_the_referrer_of_the_owner() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        do_instantiate_with_send_from_info(
+            deps.as_mut(),
+            TaxInfo {
+                src_cond: TaxCondition::Always(TaxAlwaysCondition {
+                    tax_rate: Decimal::percent(10),
+                }),
+                dst_cond: TaxCondition::Always(TaxAlwaysCondition {
+                    tax_rate: Decimal::zero(),
+                }),
+                proceeds: Addr::unchecked("proceeds"),
+                referral_share: Decimal::percent(50),
+                ..TaxInfo::default()
+            },
+        );
+        for (referee, referrer) in [("owner", "friend"), ("spender", "stranger")] {
+            let msg = ExecuteMsg::SetReferrer {
+                referrer: referrer.to_string(),
+            };
+            execute(deps.as_mut(), mock_env(), mock_info(referee, &[]), msg).unwrap();
+        }
+
+        let res = send_from(deps.as_mut(), 1000);
+        assert!(res.attributes.contains(&attr("referrer", "friend")));
+        assert!(res.attributes.contains(&attr("referral", "50")));
+    }
+
+    #[test]
+    fn send_from_applies_the_discount_of_the_owner() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        do_instantiate_with_send_from_info(
+            deps.as_mut(),
+            TaxInfo {
+                src_cond: TaxCondition::Addresses(TaxAddressCondition {
+                    addresses: vec![Addr::unchecked("spender")],
+                    tax_rate: Decimal::percent(10),
+                }),
+                dst_cond: TaxCondition::Always(TaxAlwaysCondition {
+                    tax_rate: Decimal::zero(),
+                }),
+                proceeds: Addr::unchecked("proceeds"),
+                discounts: vec![TaxDiscountTier {
+                    min_balance: Uint128::new(5000),
+                    discount: Decimal::percent(50),
+                }],
+                ..TaxInfo::default()
+            },
+        );
+
+        // the spender is the source, but the owner qualifies for the discount
+        let res = send_from(deps.as_mut(), 1000);
+        assert!(res.attributes.contains(&attr("tax", "50")));
+        assert_eq!(get_balance(deps.as_ref(), "pool"), Uint128::new(950));
+        assert_eq!(get_balance(deps.as_ref(), "owner"), Uint128::new(9000));
+    }
+
     #[test]
     fn increase_decrease_allowances() {
         let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
@@ -0,0 +1,686 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{Addr, Api, DepsMut, Env, MessageInfo, Response, StdError, StdResult, Storage};
+use cw20::Expiration;
+
+use crate::contract::{
+    execute_cancel_tax_admin, execute_lower_max_tax_rate, execute_patch_tax_map,
+    execute_renounce_tax_admin, execute_set_tax_admin, execute_set_tax_map,
+};
+use crate::msg::{Cw20TaxedExecuteMsg as ExecuteMsg, GovernedMsg};
+use crate::settlement::execute_set_tax_settlement_policy;
+use crate::state::{ANTI_WHALE_INFO, APPROVAL_CONFIG, PROPOSALS, PROPOSAL_COUNT, TAX_INFO};
+use crate::whale::{
+    execute_cancel_whale_admin, execute_renounce_whale_admin, execute_set_whale_admin,
+    execute_set_whale_info,
+};
+use crate::ContractError;
+
+/// Signers that have to approve changes to the tax and whale configuration
+#[cw_serde]
+pub struct ApprovalConfig {
+    pub signers: Vec<Addr>,
+    /// number of signer approvals needed to execute a proposal
+    pub threshold: u64,
+}
+
+impl ApprovalConfig {
+    pub fn validate(&self) -> StdResult<()> {
+        if self.threshold == 0 || self.threshold > self.signers.len() as u64 {
+            return Err(StdError::generic_err(
+                "Approval threshold must be between 1 and the number of signers",
+            ));
+        }
+        for (i, signer) in self.signers.iter().enumerate() {
+            if self.signers[..i].contains(signer) {
+                return Err(StdError::generic_err(format!(
+                    "Duplicate signer {}",
+                    signer
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Like `validate`, but additionally checks the addresses of the signers.
+    /// Approval configs supplied by users have to pass this check.
+    pub fn validate_with_api(&self, api: &dyn Api) -> StdResult<()> {
+        self.validate()?;
+        for signer in &self.signers {
+            api.addr_validate(signer.as_str())?;
+        }
+        Ok(())
+    }
+
+    pub fn is_signer(&self, addr: &Addr) -> bool {
+        self.signers.contains(addr)
+    }
+}
+
+/// A governed change waiting for the approval of the signers
+#[cw_serde]
+pub struct Proposal {
+    pub id: u64,
+    pub proposer: Addr,
+    pub msg: GovernedMsg,
+    pub approvals: Vec<Addr>,
+    pub expires: Expiration,
+    pub executed: bool,
+}
+
+/// Rejects governed messages from single keys once signers are configured.
+///
+/// Every message the tax or whale admin can send is governed, except for
+/// `AcceptTaxAdmin` and `AcceptWhaleAdmin`: they are sent by the proposed
+/// admin and can only complete a handover the signers already approved.
+pub fn assert_not_governed(storage: &dyn Storage, msg: &ExecuteMsg) -> Result<(), ContractError> {
+    let governed = matches!(
+        msg,
+        ExecuteMsg::SetTaxMap { .. }
+            | ExecuteMsg::PatchTaxMap { .. }
+            | ExecuteMsg::SetTaxAdmin { .. }
+            | ExecuteMsg::CancelTaxAdmin {}
+            | ExecuteMsg::RenounceTaxAdmin {}
+            | ExecuteMsg::LowerMaxTaxRate { .. }
+            | ExecuteMsg::SetTaxSettlementPolicy { .. }
+            | ExecuteMsg::SetWhaleInfo { .. }
+            | ExecuteMsg::SetWhaleAdmin { .. }
+            | ExecuteMsg::CancelWhaleAdmin {}
+            | ExecuteMsg::RenounceWhaleAdmin {}
+    );
+    if governed && APPROVAL_CONFIG.may_load(storage)?.is_some() {
+        return Err(ContractError::ApprovalRequired {});
+    }
+    Ok(())
+}
+
+pub fn execute_propose(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: GovernedMsg,
+    expires: Option<Expiration>,
+) -> Result<Response, ContractError> {
+    let config = load_signer_config(deps.storage, &info.sender)?;
+    let expires = expires.unwrap_or_default();
+    if expires.is_expired(&env.block) {
+        return Err(ContractError::InvalidExpiration {});
+    }
+
+    let id = PROPOSAL_COUNT.may_load(deps.storage)?.unwrap_or_default() + 1;
+    PROPOSAL_COUNT.save(deps.storage, &id)?;
+
+    // proposing counts as the first approval
+    let proposal = Proposal {
+        id,
+        proposer: info.sender.clone(),
+        msg,
+        approvals: vec![info.sender],
+        expires,
+        executed: false,
+    };
+    let res = Response::new()
+        .add_attribute("action", "propose")
+        .add_attribute("proposal_id", id.to_string());
+    execute_if_approved(deps, env, &config, proposal, res)
+}
+
+pub fn execute_approve(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    proposal_id: u64,
+) -> Result<Response, ContractError> {
+    let config = load_signer_config(deps.storage, &info.sender)?;
+    let mut proposal = PROPOSALS.load(deps.storage, proposal_id)?;
+    if proposal.executed {
+        return Err(ContractError::ProposalExecuted {});
+    }
+    if proposal.expires.is_expired(&env.block) {
+        return Err(ContractError::ProposalExpired {});
+    }
+    if proposal.approvals.contains(&info.sender) {
+        return Err(ContractError::AlreadyApproved {});
+    }
+    proposal.approvals.push(info.sender);
+
+    let res = Response::new()
+        .add_attribute("action", "approve")
+        .add_attribute("proposal_id", proposal_id.to_string());
+    execute_if_approved(deps, env, &config, proposal, res)
+}
+
+fn load_signer_config(
+    storage: &dyn Storage,
+    sender: &Addr,
+) -> Result<ApprovalConfig, ContractError> {
+    let config = APPROVAL_CONFIG
+        .may_load(storage)?
+        .ok_or(ContractError::NoApprovalConfig {})?;
+    if !config.is_signer(sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    Ok(config)
+}
+
+// stores the proposal and executes it once enough of the current signers approved
+fn execute_if_approved(
+    deps: DepsMut,
+    env: Env,
+    config: &ApprovalConfig,
+    mut proposal: Proposal,
+    res: Response,
+) -> Result<Response, ContractError> {
+    let approvals = proposal
+        .approvals
+        .iter()
+        .filter(|x| config.is_signer(x))
+        .count() as u64;
+    let res = res.add_attribute("approvals", approvals.to_string());
+
+    if approvals < config.threshold {
+        PROPOSALS.save(deps.storage, proposal.id, &proposal)?;
+        return Ok(res);
+    }
+
+    proposal.executed = true;
+    PROPOSALS.save(deps.storage, proposal.id, &proposal)?;
+    let executed = execute_governed(deps, env, proposal.msg)?;
+    Ok(res
+        .add_attribute("executed", "true")
+        .add_attributes(executed.attributes)
+        .add_events(executed.events)
+        .add_submessages(executed.messages))
+}
+
+// approved messages act on behalf of the admin of the respective configuration
+fn execute_governed(deps: DepsMut, env: Env, msg: GovernedMsg) -> Result<Response, ContractError> {
+    // a renounced admin is gone for good, signers cannot act in its place
+    let as_admin = |admin: Addr| -> Result<MessageInfo, ContractError> {
+        if admin.as_str().is_empty() {
+            return Err(ContractError::Unauthorized {});
+        }
+        Ok(MessageInfo {
+            sender: admin,
+            funds: vec![],
+        })
+    };
+    match msg {
+        GovernedMsg::SetTaxMap { tax_map } => {
+            let admin = TAX_INFO.load(deps.storage)?.admin;
+            execute_set_tax_map(deps, env, as_admin(admin)?, tax_map)
+        }
+        GovernedMsg::PatchTaxMap {
+            action,
+            tax_info,
+            proceeds,
+        } => {
+            let admin = TAX_INFO.load(deps.storage)?.admin;
+            execute_patch_tax_map(deps, env, as_admin(admin)?, action, tax_info, proceeds)
+        }
+        GovernedMsg::SetTaxAdmin { tax_admin, expires } => {
+            let admin = TAX_INFO.load(deps.storage)?.admin;
+            execute_set_tax_admin(deps, env, as_admin(admin)?, tax_admin, expires)
+        }
+        GovernedMsg::CancelTaxAdmin {} => {
+            let admin = TAX_INFO.load(deps.storage)?.admin;
+            execute_cancel_tax_admin(deps, env, as_admin(admin)?)
+        }
+        GovernedMsg::RenounceTaxAdmin {} => {
+            let admin = TAX_INFO.load(deps.storage)?.admin;
+            execute_renounce_tax_admin(deps, env, as_admin(admin)?)
+        }
+        GovernedMsg::LowerMaxTaxRate { max_tax_rate } => {
+            let admin = TAX_INFO.load(deps.storage)?.admin;
+            execute_lower_max_tax_rate(deps, env, as_admin(admin)?, max_tax_rate)
+        }
+        GovernedMsg::SetTaxSettlementPolicy { policy } => {
+            let admin = TAX_INFO.load(deps.storage)?.admin;
+            execute_set_tax_settlement_policy(deps, as_admin(admin)?, policy)
+        }
+        GovernedMsg::SetWhaleInfo { whale_info } => match whale_info {
+            Some(x) => {
+                let admin = ANTI_WHALE_INFO.load(deps.storage)?.admin;
+                execute_set_whale_info(deps, env, as_admin(admin)?, x)
+            }
+            None => Ok(Response::new()),
+        },
+        GovernedMsg::SetWhaleAdmin {
+            whale_admin,
+            expires,
+        } => match whale_admin {
+            Some(x) if !x.trim().is_empty() => {
+                let new_addr = deps.api.addr_validate(x.as_str())?;
+                let admin = ANTI_WHALE_INFO.load(deps.storage)?.admin;
+                execute_set_whale_admin(deps, env, as_admin(admin)?, new_addr, expires)
+            }
+            _ => Err(ContractError::EmptyAdmin {}),
+        },
+        GovernedMsg::CancelWhaleAdmin {} => {
+            let admin = ANTI_WHALE_INFO.load(deps.storage)?.admin;
+            execute_cancel_whale_admin(deps, env, as_admin(admin)?)
+        }
+        GovernedMsg::RenounceWhaleAdmin {} => {
+            let admin = ANTI_WHALE_INFO.load(deps.storage)?.admin;
+            execute_renounce_whale_admin(deps, env, as_admin(admin)?)
+        }
+        GovernedMsg::SetApprovalConfig { approval } => {
+            match approval {
+                Some(x) => {
+                    x.validate_with_api(deps.api)?;
+                    APPROVAL_CONFIG.save(deps.storage, &x)?;
+                }
+                None => APPROVAL_CONFIG.remove(deps.storage),
+            }
+            Ok(Response::new().add_attribute("approval_config", "updated"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cosmwasm_std::testing::MockApi;
+    use cosmwasm_std::Decimal;
+    use cw_multi_test::{App, AppResponse, Executor};
+
+    use crate::msg::{ApprovalConfigResponse, PendingAdminResponse, ProposalsResponse, QueryMsg};
+    use crate::settlement::TaxSettlementPolicy;
+    use crate::state::TAX_SETTLEMENT_POLICY;
+    use crate::tax::{TaxAlwaysCondition, TaxCondition, TaxMap};
+    use crate::testing::{instantiate_cw20, instantiate_msg};
+    use crate::whale::WhaleInfo;
+
+    fn whale_info(threshold: Decimal) -> WhaleInfo {
+        WhaleInfo {
+            threshold,
+            whitelist: vec![],
+            admin: Addr::unchecked("admin"),
+        }
+    }
+
+    fn taxed_map(tax_rate: Decimal) -> TaxMap {
+        let mut tax_map = TaxMap {
+            admin: Addr::unchecked("admin"),
+            ..TaxMap::default()
+        };
+        tax_map.on_transfer.src_cond = TaxCondition::Always(TaxAlwaysCondition { tax_rate });
+        tax_map.on_transfer.proceeds = Addr::unchecked("proceeds");
+        tax_map
+    }
+
+    // token governed by signer1, signer2 and signer3 with a threshold of 2
+    fn setup() -> (App, Addr) {
+        let mut app = App::default();
+        let msg = instantiate_msg()
+            .with_tax_map(taxed_map(Decimal::percent(1)))
+            .with_whale_info(whale_info(Decimal::percent(100)))
+            .with_approval(ApprovalConfig {
+                signers: vec![
+                    Addr::unchecked("signer1"),
+                    Addr::unchecked("signer2"),
+                    Addr::unchecked("signer3"),
+                ],
+                threshold: 2,
+            });
+        let cw20_addr = instantiate_cw20(&mut app, &msg);
+        (app, cw20_addr)
+    }
+
+    fn exec(
+        app: &mut App,
+        contract: &Addr,
+        sender: &str,
+        msg: ExecuteMsg,
+    ) -> Result<AppResponse, ContractError> {
+        app.execute_contract(Addr::unchecked(sender), contract.clone(), &msg, &[])
+            .map_err(|err| err.downcast().unwrap())
+    }
+
+    fn propose(app: &mut App, contract: &Addr, sender: &str, msg: GovernedMsg) -> AppResponse {
+        let msg = ExecuteMsg::Propose { msg, expires: None };
+        exec(app, contract, sender, msg).unwrap()
+    }
+
+    fn approve(
+        app: &mut App,
+        contract: &Addr,
+        sender: &str,
+        proposal_id: u64,
+    ) -> Result<AppResponse, ContractError> {
+        exec(app, contract, sender, ExecuteMsg::Approve { proposal_id })
+    }
+
+    fn query_tax_map(app: &App, contract: &Addr) -> TaxMap {
+        app.wrap()
+            .query_wasm_smart(contract, &QueryMsg::TaxMap {})
+            .unwrap()
+    }
+
+    #[test]
+    fn single_keys_cannot_change_governed_configuration() {
+        let (mut app, cw20_addr) = setup();
+
+        let msgs = [
+            ExecuteMsg::SetTaxMap {
+                tax_map: Some(taxed_map(Decimal::percent(5))),
+            },
+            ExecuteMsg::PatchTaxMap {
+                action: crate::tax::TaxAction::OnTransfer,
+                tax_info: None,
+                proceeds: Some("thief".to_string()),
+            },
+            ExecuteMsg::SetTaxAdmin {
+                tax_admin: Some("thief".to_string()),
+                expires: None,
+            },
+            ExecuteMsg::CancelTaxAdmin {},
+            ExecuteMsg::RenounceTaxAdmin {},
+            ExecuteMsg::LowerMaxTaxRate {
+                max_tax_rate: Decimal::percent(1),
+            },
+            ExecuteMsg::SetTaxSettlementPolicy {
+                policy: TaxSettlementPolicy::Escrow,
+            },
+            ExecuteMsg::SetWhaleInfo {
+                whale_info: Some(whale_info(Decimal::percent(1))),
+            },
+            ExecuteMsg::SetWhaleAdmin {
+                whale_admin: Some("thief".to_string()),
+                expires: None,
+            },
+            ExecuteMsg::CancelWhaleAdmin {},
+            ExecuteMsg::RenounceWhaleAdmin {},
+        ];
+        for msg in msgs {
+            for sender in ["admin", "signer1"] {
+                let err = exec(&mut app, &cw20_addr, sender, msg.clone()).unwrap_err();
+                assert_eq!(err, ContractError::ApprovalRequired {});
+            }
+        }
+
+        // only signers may propose
+        let msg = ExecuteMsg::Propose {
+            msg: GovernedMsg::SetTaxMap { tax_map: None },
+            expires: None,
+        };
+        let err = exec(&mut app, &cw20_addr, "admin", msg).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+    }
+
+    #[test]
+    fn proposal_executes_once_threshold_is_reached() {
+        let (mut app, cw20_addr) = setup();
+
+        let new_tax_map = taxed_map(Decimal::percent(5));
+        let msg = GovernedMsg::SetTaxMap {
+            tax_map: Some(new_tax_map.clone()),
+        };
+        propose(&mut app, &cw20_addr, "signer1", msg.clone());
+
+        // one approval is not enough
+        assert_eq!(
+            query_tax_map(&app, &cw20_addr),
+            taxed_map(Decimal::percent(1))
+        );
+        let proposal: Proposal = app
+            .wrap()
+            .query_wasm_smart(&cw20_addr, &QueryMsg::Proposal { proposal_id: 1 })
+            .unwrap();
+        assert_eq!(
+            proposal,
+            Proposal {
+                id: 1,
+                proposer: Addr::unchecked("signer1"),
+                msg,
+                approvals: vec![Addr::unchecked("signer1")],
+                expires: Expiration::Never {},
+                executed: false,
+            }
+        );
+
+        let err = approve(&mut app, &cw20_addr, "signer1", 1).unwrap_err();
+        assert_eq!(err, ContractError::AlreadyApproved {});
+        let err = approve(&mut app, &cw20_addr, "admin", 1).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        // the second signer executes it
+        let res = approve(&mut app, &cw20_addr, "signer2", 1).unwrap();
+        assert!(res.has_event(&cosmwasm_std::Event::new("wasm").add_attribute("executed", "true")));
+        assert_eq!(query_tax_map(&app, &cw20_addr), new_tax_map);
+
+        // late approvals do not execute it twice
+        let err = approve(&mut app, &cw20_addr, "signer3", 1).unwrap_err();
+        assert_eq!(err, ContractError::ProposalExecuted {});
+    }
+
+    #[test]
+    fn expired_proposals_cannot_be_approved() {
+        let (mut app, cw20_addr) = setup();
+
+        let height = app.block_info().height;
+        let msg = ExecuteMsg::Propose {
+            msg: GovernedMsg::SetTaxMap { tax_map: None },
+            expires: Some(Expiration::AtHeight(height)),
+        };
+        let err = exec(&mut app, &cw20_addr, "signer1", msg).unwrap_err();
+        assert_eq!(err, ContractError::InvalidExpiration {});
+
+        let msg = ExecuteMsg::Propose {
+            msg: GovernedMsg::SetTaxMap { tax_map: None },
+            expires: Some(Expiration::AtHeight(height + 10)),
+        };
+        exec(&mut app, &cw20_addr, "signer1", msg).unwrap();
+
+        app.update_block(|block| block.height += 10);
+        let err = approve(&mut app, &cw20_addr, "signer2", 1).unwrap_err();
+        assert_eq!(err, ContractError::ProposalExpired {});
+        assert_eq!(
+            query_tax_map(&app, &cw20_addr),
+            taxed_map(Decimal::percent(1))
+        );
+    }
+
+    #[test]
+    fn signers_govern_whale_and_admin_changes() {
+        let (mut app, cw20_addr) = setup();
+
+        let msgs = [
+            GovernedMsg::SetWhaleInfo {
+                whale_info: Some(whale_info(Decimal::percent(2))),
+            },
+            GovernedMsg::SetWhaleAdmin {
+                whale_admin: Some("dao".to_string()),
+                expires: None,
+            },
+            GovernedMsg::SetTaxAdmin {
+                tax_admin: Some("dao".to_string()),
+                expires: None,
+            },
+        ];
+        for (i, msg) in msgs.into_iter().enumerate() {
+            propose(&mut app, &cw20_addr, "signer3", msg);
+            approve(&mut app, &cw20_addr, "signer2", i as u64 + 1).unwrap();
+        }
+
+        let res: PendingAdminResponse = app
+            .wrap()
+            .query_wasm_smart(&cw20_addr, &QueryMsg::PendingWhaleAdmin {})
+            .unwrap();
+        assert_eq!(res.pending_admin, Some("dao".to_string()));
+        let res: PendingAdminResponse = app
+            .wrap()
+            .query_wasm_smart(&cw20_addr, &QueryMsg::PendingTaxAdmin {})
+            .unwrap();
+        assert_eq!(res.pending_admin, Some("dao".to_string()));
+
+        // a whale info that the whale admin could not have set is rejected as a whole
+        let mut invalid = whale_info(Decimal::percent(2));
+        invalid.threshold = Decimal::percent(200);
+        propose(
+            &mut app,
+            &cw20_addr,
+            "signer1",
+            GovernedMsg::SetWhaleInfo {
+                whale_info: Some(invalid),
+            },
+        );
+        assert!(approve(&mut app, &cw20_addr, "signer2", 4).is_err());
+
+        let res: ProposalsResponse = app
+            .wrap()
+            .query_wasm_smart(
+                &cw20_addr,
+                &QueryMsg::Proposals {
+                    start_after: None,
+                    limit: None,
+                },
+            )
+            .unwrap();
+        assert_eq!(
+            res.proposals.iter().map(|x| x.executed).collect::<Vec<_>>(),
+            vec![true, true, true, false]
+        );
+    }
+
+    #[test]
+    fn signers_govern_renouncing_the_admins_and_their_settings() {
+        let (mut app, cw20_addr) = setup();
+
+        let msgs = [
+            GovernedMsg::LowerMaxTaxRate {
+                max_tax_rate: Decimal::percent(2),
+            },
+            GovernedMsg::SetTaxSettlementPolicy {
+                policy: TaxSettlementPolicy::Escrow,
+            },
+            GovernedMsg::RenounceTaxAdmin {},
+            GovernedMsg::RenounceWhaleAdmin {},
+        ];
+        for (i, msg) in msgs.into_iter().enumerate() {
+            propose(&mut app, &cw20_addr, "signer1", msg);
+            approve(&mut app, &cw20_addr, "signer3", i as u64 + 1).unwrap();
+        }
+
+        assert_eq!(query_tax_map(&app, &cw20_addr).admin, Addr::unchecked(""));
+        let res = TAX_SETTLEMENT_POLICY.query(&app.wrap(), cw20_addr.clone());
+        assert_eq!(res.unwrap(), TaxSettlementPolicy::Escrow);
+        let res = ANTI_WHALE_INFO.query(&app.wrap(), cw20_addr).unwrap();
+        assert_eq!(res.admin, Addr::unchecked(""));
+    }
+
+    #[test]
+    fn signers_cannot_act_for_a_renounced_admin() {
+        let (mut app, cw20_addr) = setup();
+        propose(
+            &mut app,
+            &cw20_addr,
+            "signer1",
+            GovernedMsg::RenounceTaxAdmin {},
+        );
+        approve(&mut app, &cw20_addr, "signer2", 1).unwrap();
+        propose(
+            &mut app,
+            &cw20_addr,
+            "signer1",
+            GovernedMsg::RenounceWhaleAdmin {},
+        );
+        approve(&mut app, &cw20_addr, "signer2", 2).unwrap();
+
+        let msgs = [
+            GovernedMsg::SetTaxMap {
+                tax_map: Some(taxed_map(Decimal::percent(5))),
+            },
+            GovernedMsg::SetTaxAdmin {
+                tax_admin: Some("signer1".to_string()),
+                expires: None,
+            },
+            GovernedMsg::SetWhaleAdmin {
+                whale_admin: Some("signer1".to_string()),
+                expires: None,
+            },
+        ];
+        for (i, msg) in msgs.into_iter().enumerate() {
+            propose(&mut app, &cw20_addr, "signer1", msg);
+            let err = approve(&mut app, &cw20_addr, "signer2", i as u64 + 3).unwrap_err();
+            assert_eq!(err, ContractError::Unauthorized {});
+        }
+        assert_eq!(query_tax_map(&app, &cw20_addr).admin, Addr::unchecked(""));
+        let res = ANTI_WHALE_INFO.query(&app.wrap(), cw20_addr).unwrap();
+        assert_eq!(res.admin, Addr::unchecked(""));
+    }
+
+    #[test]
+    fn signers_can_change_and_remove_themselves() {
+        let (mut app, cw20_addr) = setup();
+
+        // raise the threshold to all three signers
+        let msg = GovernedMsg::SetApprovalConfig {
+            approval: Some(ApprovalConfig {
+                signers: vec![
+                    Addr::unchecked("signer1"),
+                    Addr::unchecked("signer2"),
+                    Addr::unchecked("signer3"),
+                ],
+                threshold: 3,
+            }),
+        };
+        propose(&mut app, &cw20_addr, "signer1", msg);
+        approve(&mut app, &cw20_addr, "signer2", 1).unwrap();
+
+        // now two approvals are not enough anymore
+        propose(
+            &mut app,
+            &cw20_addr,
+            "signer1",
+            GovernedMsg::SetApprovalConfig { approval: None },
+        );
+        let res = approve(&mut app, &cw20_addr, "signer2", 2).unwrap();
+        assert!(!res.has_event(&cosmwasm_std::Event::new("wasm").add_attribute("executed", "true")));
+        approve(&mut app, &cw20_addr, "signer3", 2).unwrap();
+
+        let res: ApprovalConfigResponse = app
+            .wrap()
+            .query_wasm_smart(&cw20_addr, &QueryMsg::ApprovalConfig {})
+            .unwrap();
+        assert_eq!(res.approval, None);
+
+        // the admin is in charge again
+        let msg = ExecuteMsg::SetTaxMap {
+            tax_map: Some(taxed_map(Decimal::percent(5))),
+        };
+        exec(&mut app, &cw20_addr, "admin", msg).unwrap();
+        assert_eq!(
+            query_tax_map(&app, &cw20_addr),
+            taxed_map(Decimal::percent(5))
+        );
+    }
+
+    #[test]
+    fn validate_approval_config() {
+        let signers = vec![Addr::unchecked("signer1"), Addr::unchecked("signer2")];
+        for threshold in [0, 3] {
+            let config = ApprovalConfig {
+                signers: signers.clone(),
+                threshold,
+            };
+            assert!(config.validate().is_err());
+        }
+        let config = ApprovalConfig {
+            signers: vec![Addr::unchecked("signer1"), Addr::unchecked("signer1")],
+            threshold: 1,
+        };
+        assert!(config.validate().is_err());
+        let config = ApprovalConfig {
+            signers: vec![Addr::unchecked("signer1"), Addr::unchecked("Signer2")],
+            threshold: 2,
+        };
+        assert!(config.validate().is_ok());
+        assert!(config.validate_with_api(&MockApi::default()).is_err());
+        let config = ApprovalConfig {
+            signers,
+            threshold: 2,
+        };
+        assert!(config.validate_with_api(&MockApi::default()).is_ok());
+    }
+}
@@ -2,32 +2,64 @@ use std::f32::consts::E;
 
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
-use cosmwasm_std::Order::Ascending;
+use cosmwasm_std::Order::{self, Ascending};
 use cosmwasm_std::{
-    to_json_binary, Addr, Binary, CosmosMsg, Decimal, Deps, DepsMut, Empty, Env, MessageInfo,
-    Response, StdError, StdResult, Uint128, WasmMsg,
+    to_json_binary, to_json_string, Addr, Api, Binary, BlockInfo, Decimal, Deps, DepsMut, Empty,
+    Env, Event, MessageInfo, Reply, Response, StdError, StdResult, Storage, Uint128,
 };
 
 use cw2::{ensure_from_older_version, set_contract_version};
 use cw20::{
-    BalanceResponse, Cw20Coin, Cw20ReceiveMsg, DownloadLogoResponse, EmbeddedLogo, Logo, LogoInfo,
+    BalanceResponse, Cw20Coin, DownloadLogoResponse, EmbeddedLogo, Expiration, Logo, LogoInfo,
     MarketingInfoResponse, MinterResponse, TokenInfoResponse,
 };
+use cw_storage_plus::Bound;
 
 use crate::allowances::{
     execute_burn_from, execute_decrease_allowance, execute_increase_allowance, execute_send_from,
     execute_transfer_from, query_allowance,
 };
-use crate::enumerable::{query_all_accounts, query_owner_allowances, query_spender_allowances};
+use crate::approval::{assert_not_governed, execute_approve, execute_propose};
+use crate::enumerable::{
+    query_all_accounts, query_all_escrowed_tax, query_owner_allowances, query_proposals,
+    query_spender_allowances, query_tax_map_history,
+};
 use crate::error::ContractError;
-use crate::msg::{Cw20TaxedExecuteMsg as ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg};
+use crate::events::{
+    AdminChangedEvent, Cw20TaxEvent, Cw20TransferEvent, TaxMapUpdatedEvent, WhaleInfoUpdatedEvent,
+};
+use crate::msg::{
+    ApprovalConfigResponse, Cw20TaxedExecuteMsg as ExecuteMsg, InstantiateMsg, MaxTaxRateResponse,
+    MigrateMsg, PendingAdminResponse, QueryMsg, SimulateTaxResponse, TaxActionAdmin,
+    TaxAdminsResponse, TaxDiscount, TaxDiscountsResponse, TaxFreeHeadroom, TaxFreeVolumeResponse,
+    TaxRateLimitResponse, TaxRuleMatch, ValidateTaxMapResponse,
+};
+use crate::receiver::{
+    execute_set_extended_receive, query_extended_receive, receive_msg, TaxedCw20ReceiveMsg,
+};
+use crate::referral::{
+    execute_set_referrer, pay_referral, query_referral_earnings, query_referrer,
+};
+use crate::settlement::{
+    execute_flush_escrowed_tax, execute_set_tax_settlement_policy, execute_settle_tax,
+    query_escrowed_tax, query_tax_settlement_policy, reply_settle_tax, settle_tax_msgs,
+};
 use crate::state::{
-    self, MinterData, TokenInfo, ALLOWANCES, ALLOWANCES_SPENDER, ANTI_WHALE_INFO, BALANCES, LOGO,
-    MARKETING_INFO, TAX_INFO, TOKEN_INFO,
+    self, MinterData, PendingAdmin, TokenInfo, ALLOWANCES, ALLOWANCES_SPENDER, ANTI_WHALE_INFO,
+    APPROVAL_CONFIG, BALANCES, LOGO, MARKETING_INFO, MAX_TAX_RATE, PENDING_TAX_ADMIN,
+    PENDING_WHALE_ADMIN, PROPOSALS, TAX_INFO, TAX_MAP_HEIGHTS, TAX_MAP_HISTORY, TAX_MAP_VERSION,
+    TAX_RATE_LIMIT, TAX_RATE_WINDOW, TOKEN_INFO,
 };
 
-use crate::tax::TaxMap;
-use crate::whale::{self, execute_set_whale_admin, execute_set_whale_info, WhaleInfo};
+use crate::tax::{
+    assert_tax_rate_change, check_tax_rate_change, debit_payer, pay_native_tax, record_acquisition,
+    record_outgoing_volume, save_tax_map, TaxAction, TaxCondition, TaxContext, TaxDeduction,
+    TaxInfo, TaxMap, TaxMapVersion,
+};
+use crate::whale::{
+    self, execute_accept_whale_admin, execute_cancel_whale_admin, execute_renounce_whale_admin,
+    execute_set_whale_admin, execute_set_whale_info, WhaleInfo,
+};
 
 // version info for migration info
 pub const CONTRACT_NAME: &str = "crates.io:cw20-base";
@@ -101,7 +133,7 @@ fn verify_logo(logo: &Logo) -> Result<(), ContractError> {
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     mut deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
@@ -111,7 +143,7 @@ pub fn instantiate(
     msg.validate()?;
 
     // create initial accounts
-    let total_supply = create_accounts(&mut deps, &msg.initial_balances)?;
+    let total_supply = create_accounts(&mut deps, &env.block, &msg.initial_balances)?;
 
     if let Some(limit) = msg.get_cap() {
         if total_supply > limit {
@@ -162,12 +194,25 @@ pub fn instantiate(
     };
     TOKEN_INFO.save(deps.storage, &data)?;
 
+    if let Some(max_tax_rate) = msg.max_tax_rate {
+        MAX_TAX_RATE.save(deps.storage, &max_tax_rate)?;
+    }
+
     let tax_info = match msg.tax_map {
         Some(x) => x,
         None => TaxMap::default(),
     };
-    tax_info.validate()?;
-    TAX_INFO.save(deps.storage, &tax_info)?;
+    tax_info.validate_with_api(deps.api, msg.max_tax_rate)?;
+    save_tax_map(deps.storage, &env.block, &tax_info)?;
+
+    if let Some(tax_rate_limit) = msg.tax_rate_limit {
+        TAX_RATE_LIMIT.save(deps.storage, &tax_rate_limit)?;
+    }
+
+    if let Some(approval) = msg.approval {
+        approval.validate_with_api(deps.api)?;
+        APPROVAL_CONFIG.save(deps.storage, &approval)?;
+    }
 
     let whale_info = match msg.whale_info {
         Some(x) => x,
@@ -177,6 +222,7 @@ pub fn instantiate(
             admin: Addr::unchecked(info.sender.clone()),
         },
     };
+    whale_info.validate_with_api(deps.api)?;
 
     ANTI_WHALE_INFO.save(deps.storage, &whale_info)?;
 
@@ -185,6 +231,7 @@ pub fn instantiate(
 
 pub fn create_accounts(
     deps: &mut DepsMut,
+    block: &BlockInfo,
     accounts: &[Cw20Coin],
 ) -> Result<Uint128, ContractError> {
     validate_accounts(accounts)?;
@@ -193,6 +240,7 @@ pub fn create_accounts(
     for row in accounts {
         let address = deps.api.addr_validate(&row.address)?;
         BALANCES.save(deps.storage, &address, &row.amount)?;
+        record_acquisition(deps.storage, block, &address, row.amount)?;
         total_supply += row.amount;
     }
 
@@ -218,6 +266,8 @@ pub fn execute(
     info: MessageInfo,
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
+    assert_not_governed(deps.storage, &msg)?;
+
     match msg {
         ExecuteMsg::Transfer { recipient, amount } => {
             execute_transfer(deps, env, info, recipient, amount)
@@ -263,7 +313,20 @@ pub fn execute(
 
         // Tax related extension
         ExecuteMsg::SetTaxMap { tax_map } => execute_set_tax_map(deps, env, info, tax_map),
-        ExecuteMsg::SetTaxAdmin { tax_admin } => execute_set_tax_admin(deps, env, info, tax_admin),
+        ExecuteMsg::PatchTaxMap {
+            action,
+            tax_info,
+            proceeds,
+        } => execute_patch_tax_map(deps, env, info, action, tax_info, proceeds),
+        ExecuteMsg::SetTaxAdmin { tax_admin, expires } => {
+            execute_set_tax_admin(deps, env, info, tax_admin, expires)
+        }
+        ExecuteMsg::AcceptTaxAdmin {} => execute_accept_tax_admin(deps, env, info),
+        ExecuteMsg::CancelTaxAdmin {} => execute_cancel_tax_admin(deps, env, info),
+        ExecuteMsg::RenounceTaxAdmin {} => execute_renounce_tax_admin(deps, env, info),
+        ExecuteMsg::LowerMaxTaxRate { max_tax_rate } => {
+            execute_lower_max_tax_rate(deps, env, info, max_tax_rate)
+        }
 
         // WhaleInfo related extension
         ExecuteMsg::SetWhaleInfo { whale_info } => {
@@ -272,61 +335,351 @@ pub fn execute(
                 None => { Ok(Response::new()) }
             }
         },
-        ExecuteMsg::SetWhaleAdmin { whale_admin } => {
+        ExecuteMsg::SetWhaleAdmin { whale_admin, expires } => {
             match whale_admin {
-                Some(x) => {
+                Some(x) if !x.trim().is_empty() => {
                     let new_addr = deps.api.addr_validate(x.as_str())?;
-                    execute_set_whale_admin(deps, env, info, new_addr)
+                    execute_set_whale_admin(deps, env, info, new_addr, expires)
                 }
-                None => { Ok(Response::new()) }
+                _ => Err(ContractError::EmptyAdmin {}),
             }
         },
+        ExecuteMsg::AcceptWhaleAdmin {} => execute_accept_whale_admin(deps, env, info),
+        ExecuteMsg::CancelWhaleAdmin {} => execute_cancel_whale_admin(deps, env, info),
+        ExecuteMsg::RenounceWhaleAdmin {} => execute_renounce_whale_admin(deps, env, info),
+
+        // Approval related extension
+        ExecuteMsg::Propose { msg, expires } => execute_propose(deps, env, info, msg, expires),
+        ExecuteMsg::Approve { proposal_id } => execute_approve(deps, env, info, proposal_id),
+
+        // Referral related extension
+        ExecuteMsg::SetReferrer { referrer } => execute_set_referrer(deps, info, referrer),
+
+        // Receiver related extension
+        ExecuteMsg::SetExtendedReceive { contract, enabled } => {
+            execute_set_extended_receive(deps, info, contract, enabled)
+        }
+
+        // Tax settlement related extension
+        ExecuteMsg::SetTaxSettlementPolicy { policy } => {
+            execute_set_tax_settlement_policy(deps, info, policy)
+        }
+        ExecuteMsg::FlushEscrowedTax { recipient } => {
+            execute_flush_escrowed_tax(deps, env, recipient)
+        }
+
+        // Tax settlement of taxed transfers
+        ExecuteMsg::SettleTax {
+            payer,
+            recipient,
+            amount,
+        } => execute_settle_tax(deps, env, info, payer, recipient, amount),
     }
 }
 
 pub fn execute_set_tax_map(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     tax_map: Option<TaxMap>,
 ) -> Result<Response, ContractError> {
     let curr_tax_map = TAX_INFO.load(deps.storage)?;
-    if curr_tax_map.admin != info.sender {
-        return Err(ContractError::Unauthorized {});
-    }
     let new_tax_map = match tax_map {
         Some(x) => x,
         None => {
             // reset default but preserve admin
             let mut def = TaxMap::default();
-            def.admin = curr_tax_map.admin;
+            def.admin = curr_tax_map.admin.clone();
             def
         }
     };
+    curr_tax_map.assert_can_change(&new_tax_map, &info.sender)?;
+
+    let version = store_tax_map(deps.storage, deps.api, &env, &curr_tax_map, &new_tax_map)?;
+
+    Ok(Response::new()
+        .add_event(
+            TaxMapUpdatedEvent {
+                action: "set_tax_map",
+                admin: &new_tax_map.admin,
+                tax_map_version: version,
+            }
+            .into(),
+        )
+        .add_attribute("admin", new_tax_map.admin)
+        .add_attribute("tax_map_version", version.to_string()))
+}
+
+pub fn execute_patch_tax_map(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    action: TaxAction,
+    tax_info: Option<TaxInfo>,
+    proceeds: Option<String>,
+) -> Result<Response, ContractError> {
+    let curr_tax_map = TAX_INFO.load(deps.storage)?;
+    if curr_tax_map.admin != info.sender && *curr_tax_map.action_admin(&action) != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut new_tax_map = curr_tax_map.clone();
+    let patched = new_tax_map.tax_info_mut(&action);
+    if let Some(tax_info) = tax_info {
+        *patched = tax_info;
+    }
+    if let Some(proceeds) = proceeds {
+        if patched.rules.is_some() {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Tax info with rules has proceeds per rule",
+            )));
+        }
+        patched.proceeds = deps.api.addr_validate(&proceeds)?;
+    }
+
+    // state exactly which fields of the tax info changed
+    let old = curr_tax_map.tax_info(&action);
+    let new = new_tax_map.tax_info(&action);
+    let mut changes = vec![];
+    if old.src_cond != new.src_cond {
+        changes.push((
+            "src_cond",
+            to_json_string(&old.src_cond)?,
+            to_json_string(&new.src_cond)?,
+        ));
+    }
+    if old.dst_cond != new.dst_cond {
+        changes.push((
+            "dst_cond",
+            to_json_string(&old.dst_cond)?,
+            to_json_string(&new.dst_cond)?,
+        ));
+    }
+    if old.proceeds != new.proceeds {
+        changes.push((
+            "proceeds",
+            old.proceeds.to_string(),
+            new.proceeds.to_string(),
+        ));
+    }
+    if old.rules != new.rules {
+        changes.push((
+            "rules",
+            to_json_string(&old.rules)?,
+            to_json_string(&new.rules)?,
+        ));
+    }
+    if old.admin != new.admin {
+        changes.push((
+            "admin",
+            to_json_string(&old.admin)?,
+            to_json_string(&new.admin)?,
+        ));
+    }
+    if old.discounts != new.discounts {
+        changes.push((
+            "discounts",
+            to_json_string(&old.discounts)?,
+            to_json_string(&new.discounts)?,
+        ));
+    }
+    if old.referral_share != new.referral_share {
+        changes.push((
+            "referral_share",
+            old.referral_share.to_string(),
+            new.referral_share.to_string(),
+        ));
+    }
+    if old.mode != new.mode {
+        changes.push(("mode", old.mode.to_string(), new.mode.to_string()));
+    }
+    if old.native_payment != new.native_payment {
+        changes.push((
+            "native_payment",
+            to_json_string(&old.native_payment)?,
+            to_json_string(&new.native_payment)?,
+        ));
+    }
+    if changes.is_empty() {
+        return Err(ContractError::EmptyTaxMapPatch {});
+    }
+    curr_tax_map.assert_can_change(&new_tax_map, &info.sender)?;
+
+    let version = store_tax_map(deps.storage, deps.api, &env, &curr_tax_map, &new_tax_map)?;
+
+    let mut event = Event::new("tax_map_patched")
+        .add_attribute("tax_action", action.as_str())
+        .add_attribute(
+            "changed",
+            changes
+                .iter()
+                .map(|(field, _, _)| *field)
+                .collect::<Vec<_>>()
+                .join(","),
+        );
+    for (field, old, new) in changes {
+        event = event
+            .add_attribute(format!("old_{}", field), old)
+            .add_attribute(format!("new_{}", field), new);
+    }
 
-    new_tax_map.validate()?;
-    TAX_INFO.save(deps.storage, &new_tax_map)?;
+    Ok(Response::new()
+        .add_attribute("action", "patch_tax_map")
+        .add_attribute("tax_map_version", version.to_string())
+        .add_event(event)
+        .add_event(
+            TaxMapUpdatedEvent {
+                action: "patch_tax_map",
+                admin: &new_tax_map.admin,
+                tax_map_version: version,
+            }
+            .into(),
+        ))
+}
 
-    Ok(Response::new().add_attribute("admin", new_tax_map.admin))
+/// Validates a changed tax map against the tax rate ceiling and the rate
+/// limit and stores it as a new version
+fn store_tax_map(
+    storage: &mut dyn Storage,
+    api: &dyn Api,
+    env: &Env,
+    curr_tax_map: &TaxMap,
+    new_tax_map: &TaxMap,
+) -> Result<u64, ContractError> {
+    new_tax_map.validate_with_api(api, MAX_TAX_RATE.may_load(storage)?)?;
+    assert_tax_rate_change(storage, &env.block, curr_tax_map, new_tax_map)?;
+    Ok(save_tax_map(storage, &env.block, new_tax_map)?)
 }
 
+/// Proposes a new tax admin. The role is only handed over once the
+/// proposed admin accepts it.
 pub fn execute_set_tax_admin(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     tax_admin: Option<String>,
+    expires: Option<Expiration>,
 ) -> Result<Response, ContractError> {
-    let mut tax_map = TAX_INFO.load(deps.storage)?;
+    let tax_map = TAX_INFO.load(deps.storage)?;
     if tax_map.admin != info.sender {
         return Err(ContractError::Unauthorized {});
     }
-    tax_map.admin = match tax_admin {
-        Some(x) => deps.api.addr_validate(&x)?,
-        None => Addr::unchecked(""),
+    let tax_admin = match tax_admin {
+        Some(x) if !x.trim().is_empty() => deps.api.addr_validate(&x)?,
+        _ => return Err(ContractError::EmptyAdmin {}),
     };
-    TAX_INFO.save(deps.storage, &tax_map)?;
+    let pending = PendingAdmin::new(tax_admin, expires, &env.block)?;
+    PENDING_TAX_ADMIN.save(deps.storage, &pending)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "propose_tax_admin")
+        .add_attribute("admin", tax_map.admin)
+        .add_attribute("pending_admin", pending.admin)
+        .add_attribute("expires", pending.expires.to_string()))
+}
+
+pub fn execute_accept_tax_admin(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let pending = PENDING_TAX_ADMIN
+        .may_load(deps.storage)?
+        .ok_or(ContractError::NoPendingAdmin {})?;
+    pending.assert_can_accept(&info.sender, &env.block)?;
+
+    let mut tax_map = TAX_INFO.load(deps.storage)?;
+    let previous = std::mem::replace(&mut tax_map.admin, pending.admin);
+    save_tax_map(deps.storage, &env.block, &tax_map)?;
+    PENDING_TAX_ADMIN.remove(deps.storage);
+
+    Ok(Response::new()
+        .add_event(
+            AdminChangedEvent {
+                role: "tax",
+                previous: &previous,
+                new: &tax_map.admin,
+            }
+            .into(),
+        )
+        .add_attribute("action", "accept_tax_admin")
+        .add_attribute("admin", tax_map.admin))
+}
+
+pub fn execute_cancel_tax_admin(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let tax_map = TAX_INFO.load(deps.storage)?;
+    if tax_map.admin != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    if PENDING_TAX_ADMIN.may_load(deps.storage)?.is_none() {
+        return Err(ContractError::NoPendingAdmin {});
+    }
+    PENDING_TAX_ADMIN.remove(deps.storage);
+
+    Ok(Response::new().add_attribute("action", "cancel_tax_admin"))
+}
 
-    Ok(Response::new().add_attribute("admin", tax_map.admin))
+/// Gives up the tax admin role for good. Nobody can change the tax map afterwards.
+pub fn execute_renounce_tax_admin(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let mut tax_map = TAX_INFO.load(deps.storage)?;
+    if tax_map.admin != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    let previous = std::mem::replace(&mut tax_map.admin, Addr::unchecked(""));
+    save_tax_map(deps.storage, &env.block, &tax_map)?;
+    PENDING_TAX_ADMIN.remove(deps.storage);
+
+    Ok(Response::new()
+        .add_event(
+            AdminChangedEvent {
+                role: "tax",
+                previous: &previous,
+                new: &tax_map.admin,
+            }
+            .into(),
+        )
+        .add_attribute("action", "renounce_tax_admin"))
+}
+
+pub fn execute_lower_max_tax_rate(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    max_tax_rate: Decimal,
+) -> Result<Response, ContractError> {
+    let tax_map = TAX_INFO.load(deps.storage)?;
+    if tax_map.admin != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    set_max_tax_rate(deps.storage, &tax_map, max_tax_rate)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "lower_max_tax_rate")
+        .add_attribute("max_tax_rate", max_tax_rate.to_string()))
+}
+
+/// Stores a new tax rate ceiling. The ceiling can only ever be lowered and the
+/// active tax map must already comply with it.
+fn set_max_tax_rate(
+    storage: &mut dyn Storage,
+    tax_map: &TaxMap,
+    max_tax_rate: Decimal,
+) -> Result<(), ContractError> {
+    let curr_max = MAX_TAX_RATE.may_load(storage)?.unwrap_or(Decimal::one());
+    if max_tax_rate > curr_max {
+        return Err(ContractError::CannotRaiseMaxTaxRate {});
+    }
+    tax_map.validate(Some(max_tax_rate))?;
+    MAX_TAX_RATE.save(storage, &max_tax_rate)?;
+    Ok(())
 }
 
 pub fn execute_transfer(
@@ -338,23 +691,36 @@ pub fn execute_transfer(
 ) -> Result<Response, ContractError> {
     let rcpt_addr = deps.api.addr_validate(&recipient)?;
     let map = TAX_INFO.load(deps.storage)?;
-    let rcpt_proceeds = map.on_transfer.proceeds.clone().into_string();
-    let (net, tax) = map.on_transfer.deduct_tax(
-        &deps.querier,
+    let tax_map_version = TAX_MAP_VERSION.may_load(deps.storage)?.unwrap_or_default();
+    let mut deduction = map.on_transfer.deduct(
+        &TaxContext::new(deps.as_ref(), &env),
         info.sender.clone(),
         rcpt_addr.clone(),
         amount,
     )?;
+    let native_tax = pay_native_tax(
+        &TaxContext::new(deps.as_ref(), &env),
+        &map.on_transfer,
+        &mut deduction,
+        amount,
+        &info.sender,
+        &info.funds,
+    )?;
+    let TaxDeduction {
+        net,
+        gross,
+        tax,
+        proceeds,
+        ..
+    } = deduction;
+    let rcpt_proceeds = proceeds.to_string();
+    record_outgoing_volume(deps.storage, &env.block, &map, &info.sender, amount)?;
+    let referral = pay_referral(deps.storage, &map.on_transfer, &info.sender, tax)?;
+    let proceeds_tax = tax - referral.as_ref().map(|x| x.amount).unwrap_or_default();
     let whale_info = ANTI_WHALE_INFO.load(deps.storage)?;
 
     // remove tokens from sender balance
-    BALANCES.update(
-        deps.storage,
-        &info.sender,
-        |balance: Option<Uint128>| -> StdResult<_> {
-            Ok(balance.unwrap_or_default().checked_sub(amount)?)
-        },
-    )?;
+    debit_payer(deps.storage, &info.sender, amount, gross)?;
 
     // move tax to contract
     BALANCES.update(
@@ -369,34 +735,74 @@ pub fn execute_transfer(
         &rcpt_addr,
         |balance: Option<Uint128>| -> StdResult<_> { Ok(balance.unwrap_or_default() + net) },
     )?;
+    record_acquisition(deps.storage, &env.block, &rcpt_addr, net)?;
 
     // assert whale policy
     let new_balance = BALANCES.load(deps.storage, &rcpt_addr)?;
     whale_info.assert_no_whale(deps.as_ref().storage, &rcpt_addr, new_balance)?;
 
-    // construct msg to send tax to proceeds wallet
-    let tax_msg = CosmosMsg::Wasm(WasmMsg::Execute {
-        contract_addr: env.contract.address.into(),
-        msg: to_json_binary(&ExecuteMsg::Transfer {
-            recipient: rcpt_proceeds.clone(),
-            amount: tax,
-        })?,
-        funds: vec![],
-    });
+    // construct msgs to settle the tax shares as transfers of the payer
+    let tax_msgs = settle_tax_msgs(
+        deps.storage,
+        &env.contract.address,
+        &info.sender,
+        &proceeds,
+        proceeds_tax,
+        referral.as_ref(),
+    )?;
 
-    let res = Response::new()
+    let mut events = vec![Event::from(Cw20TransferEvent {
+        action: "transfer",
+        from: &info.sender,
+        to: &rcpt_addr,
+        by: None,
+        amount,
+        net,
+        gross,
+    })];
+    if !tax.is_zero() || native_tax.is_some() {
+        events.push(
+            Cw20TaxEvent {
+                tax_action: &TaxAction::OnTransfer,
+                payer: &info.sender,
+                proceeds: &rcpt_proceeds,
+                tax,
+                tax_mode: &map.on_transfer.mode,
+                tax_map_version,
+                referral: referral.as_ref(),
+                native_tax: native_tax.as_ref().map(|x| &x.tax),
+            }
+            .into(),
+        );
+    }
+
+    let mut res = Response::new()
+        .add_events(events)
         .add_attribute("action", "transfer")
         .add_attribute("from", info.sender)
         .add_attribute("to", recipient)
-        .add_attribute("amount", amount);
+        .add_attribute("amount", net);
+    if let Some(native_tax) = native_tax {
+        res = res
+            .add_attribute("native_tax", native_tax.tax.to_string())
+            .add_messages(native_tax.messages);
+    }
 
     if tax.gt(&Uint128::zero()) {
-        let tax_res = res
+        let mut tax_res = res
             .clone()
             .add_attribute("net", net)
             .add_attribute("tax", tax)
+            .add_attribute("gross", gross)
+            .add_attribute("tax_mode", map.on_transfer.mode.to_string())
             .add_attribute("proceeds", &rcpt_proceeds)
-            .add_message(tax_msg);
+            .add_attribute("tax_map_version", tax_map_version.to_string())
+            .add_submessages(tax_msgs);
+        if let Some(referral) = referral {
+            tax_res = tax_res
+                .add_attribute("referrer", referral.referrer)
+                .add_attribute("referral", referral.amount);
+        }
         return Ok(tax_res);
     }
 
@@ -432,7 +838,7 @@ pub fn execute_burn(
 
 pub fn execute_mint(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     recipient: String,
     amount: Uint128,
@@ -467,6 +873,7 @@ pub fn execute_mint(
         &rcpt_addr,
         |balance: Option<Uint128>| -> StdResult<_> { Ok(balance.unwrap_or_default() + amount) },
     )?;
+    record_acquisition(deps.storage, &env.block, &rcpt_addr, amount)?;
 
     let res = Response::new()
         .add_attribute("action", "mint")
@@ -485,26 +892,43 @@ pub fn execute_send(
 ) -> Result<Response, ContractError> {
     let rcpt_addr = deps.api.addr_validate(&contract.clone())?;
     let map = TAX_INFO.load(deps.storage)?;
-    let rcpt_proceeds = map.on_send.proceeds.clone().into_string();
+    let tax_map_version = TAX_MAP_VERSION.may_load(deps.storage)?.unwrap_or_default();
     let rcpt = deps.api.addr_validate(contract.clone().as_str())?;
-    let (net, tax) = map
-        .on_send
-        .deduct_tax(&deps.querier, info.sender.clone(), rcpt, amount)?;
+    let mut deduction = map.on_send.deduct(
+        &TaxContext::new(deps.as_ref(), &env).with_hook(&msg),
+        info.sender.clone(),
+        rcpt,
+        amount,
+    )?;
+    let native_tax = pay_native_tax(
+        &TaxContext::new(deps.as_ref(), &env),
+        &map.on_send,
+        &mut deduction,
+        amount,
+        &info.sender,
+        &info.funds,
+    )?;
+    let TaxDeduction {
+        net,
+        gross,
+        tax,
+        proceeds,
+        ..
+    } = deduction;
+    let rcpt_proceeds = proceeds.to_string();
+    record_outgoing_volume(deps.storage, &env.block, &map, &info.sender, amount)?;
+    let referral = pay_referral(deps.storage, &map.on_send, &info.sender, tax)?;
+    let proceeds_tax = tax - referral.as_ref().map(|x| x.amount).unwrap_or_default();
     let whale_info = ANTI_WHALE_INFO.load(deps.storage)?;
 
     // move tokens to the contract
-    BALANCES.update(
-        deps.storage,
-        &info.sender,
-        |balance: Option<Uint128>| -> StdResult<_> {
-            Ok(balance.unwrap_or_default().checked_sub(amount)?)
-        },
-    )?;
+    debit_payer(deps.storage, &info.sender, amount, gross)?;
     BALANCES.update(
         deps.storage,
         &rcpt_addr,
         |balance: Option<Uint128>| -> StdResult<_> { Ok(balance.unwrap_or_default() + net) },
     )?;
+    record_acquisition(deps.storage, &env.block, &rcpt_addr, net)?;
 
     //assert whale policy
     let new_balance = BALANCES.load(deps.storage, &rcpt_addr)?;
@@ -518,38 +942,83 @@ pub fn execute_send(
     )?;
 
     // construct msg for net amount
-    let net_msg = Cw20ReceiveMsg {
-        sender: info.sender.clone().into(),
-        amount: net,
-        msg,
-    }
-    .into_cosmos_msg(contract)?;
-
-    // construct msg to send tax to proceeds wallet
-    let tax_msg = CosmosMsg::Wasm(WasmMsg::Execute {
-        contract_addr: env.contract.address.into(),
-        msg: to_json_binary(&ExecuteMsg::Transfer {
-            recipient: rcpt_proceeds.clone(),
-            amount: tax,
-        })?,
-        funds: vec![],
-    });
+    let net_msg = receive_msg(
+        deps.storage,
+        &rcpt_addr,
+        TaxedCw20ReceiveMsg {
+            sender: info.sender.to_string(),
+            amount: net,
+            gross,
+            tax,
+            tax_action: TaxAction::OnSend,
+            msg,
+        },
+    )?;
+
+    // construct msgs to settle the tax shares as transfers of the payer
+    let tax_msgs = settle_tax_msgs(
+        deps.storage,
+        &env.contract.address,
+        &info.sender,
+        &proceeds,
+        proceeds_tax,
+        referral.as_ref(),
+    )?;
+
+    let mut events = vec![Event::from(Cw20TransferEvent {
+        action: "send",
+        from: &info.sender,
+        to: &rcpt_addr,
+        by: None,
+        amount,
+        net,
+        gross,
+    })];
+    if !tax.is_zero() || native_tax.is_some() {
+        events.push(
+            Cw20TaxEvent {
+                tax_action: &TaxAction::OnSend,
+                payer: &info.sender,
+                proceeds: &rcpt_proceeds,
+                tax,
+                tax_mode: &map.on_send.mode,
+                tax_map_version,
+                referral: referral.as_ref(),
+                native_tax: native_tax.as_ref().map(|x| &x.tax),
+            }
+            .into(),
+        );
+    }
 
     // emit
-    let res = Response::new()
+    let mut res = Response::new()
+        .add_events(events)
         .add_attribute("action", "send")
         .add_attribute("from", &info.sender.clone().into_string())
         .add_attribute("to", &rcpt_addr)
-        .add_attribute("amount", amount)
+        .add_attribute("amount", net)
         .add_message(net_msg);
+    if let Some(native_tax) = native_tax {
+        res = res
+            .add_attribute("native_tax", native_tax.tax.to_string())
+            .add_messages(native_tax.messages);
+    }
 
     if tax.gt(&Uint128::zero()) {
-        let tax_res = res
+        let mut tax_res = res
             .clone()
             .add_attribute("net", net)
             .add_attribute("tax", tax)
+            .add_attribute("gross", gross)
+            .add_attribute("tax_mode", map.on_send.mode.to_string())
             .add_attribute("proceeds", &rcpt_proceeds)
-            .add_message(tax_msg);
+            .add_attribute("tax_map_version", tax_map_version.to_string())
+            .add_submessages(tax_msgs);
+        if let Some(referral) = referral {
+            tax_res = tax_res
+                .add_attribute("referrer", referral.referrer)
+                .add_attribute("referral", referral.amount);
+        }
         return Ok(tax_res);
     }
 
@@ -683,7 +1152,13 @@ pub fn execute_upload_logo(
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> Result<Response, ContractError> {
+    // tax settlements are the only submessages replying
+    reply_settle_tax(deps, env, msg)
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
         QueryMsg::Balance { address } => to_json_binary(&query_balance(deps, address)?),
         QueryMsg::TokenInfo {} => to_json_binary(&query_token_info(deps)?),
@@ -712,9 +1187,221 @@ pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
         QueryMsg::MarketingInfo {} => to_json_binary(&query_marketing_info(deps)?),
         QueryMsg::DownloadLogo {} => to_json_binary(&query_download_logo(deps)?),
         QueryMsg::TaxMap {} => to_json_binary(&TAX_INFO.load(deps.storage)?),
+        QueryMsg::TaxFreeVolume { address } => {
+            to_json_binary(&query_tax_free_volume(deps, env, address)?)
+        }
+        QueryMsg::TaxDiscounts { address } => to_json_binary(&query_tax_discounts(deps, address)?),
+        QueryMsg::SimulateTax {
+            action,
+            sender,
+            recipient,
+            amount,
+            msg,
+        } => to_json_binary(&query_simulate_tax(
+            deps, env, action, sender, recipient, amount, msg,
+        )?),
+        QueryMsg::ValidateTaxMap { tax_map } => {
+            to_json_binary(&query_validate_tax_map(deps, env, *tax_map)?)
+        }
+        QueryMsg::TaxAdmins {} => to_json_binary(&query_tax_admins(deps)?),
+        QueryMsg::MaxTaxRate {} => to_json_binary(&query_max_tax_rate(deps)?),
+        QueryMsg::TaxRateLimit {} => to_json_binary(&query_tax_rate_limit(deps)?),
+        QueryMsg::TaxMapAt { height } => to_json_binary(&query_tax_map_at(deps, height)?),
+        QueryMsg::TaxMapHistory { start_after, limit } => {
+            to_json_binary(&query_tax_map_history(deps, start_after, limit)?)
+        }
+        QueryMsg::PendingTaxAdmin {} => to_json_binary(&query_pending_admin(
+            PENDING_TAX_ADMIN.may_load(deps.storage)?,
+        )),
+        QueryMsg::PendingWhaleAdmin {} => to_json_binary(&query_pending_admin(
+            PENDING_WHALE_ADMIN.may_load(deps.storage)?,
+        )),
+        QueryMsg::ApprovalConfig {} => to_json_binary(&ApprovalConfigResponse {
+            approval: APPROVAL_CONFIG.may_load(deps.storage)?,
+        }),
+        QueryMsg::Proposal { proposal_id } => {
+            to_json_binary(&PROPOSALS.load(deps.storage, proposal_id)?)
+        }
+        QueryMsg::Proposals { start_after, limit } => {
+            to_json_binary(&query_proposals(deps, start_after, limit)?)
+        }
+        QueryMsg::Referrer { address } => to_json_binary(&query_referrer(deps, address)?),
+        QueryMsg::ReferralEarnings { address } => {
+            to_json_binary(&query_referral_earnings(deps, address)?)
+        }
+        QueryMsg::ExtendedReceive { address } => {
+            to_json_binary(&query_extended_receive(deps, address)?)
+        }
+        QueryMsg::TaxSettlementPolicy {} => to_json_binary(&query_tax_settlement_policy(deps)?),
+        QueryMsg::EscrowedTax { address } => to_json_binary(&query_escrowed_tax(deps, address)?),
+        QueryMsg::AllEscrowedTax { start_after, limit } => {
+            to_json_binary(&query_all_escrowed_tax(deps, start_after, limit)?)
+        }
+    }
+}
+
+pub fn query_tax_free_volume(
+    deps: Deps,
+    env: Env,
+    address: String,
+) -> StdResult<TaxFreeVolumeResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let tax_map = TAX_INFO.load(deps.storage)?;
+    let mut headroom = vec![];
+    for (action, tax_info) in tax_map.tax_infos() {
+        let volume = tax_info
+            .effective_rules()
+            .iter()
+            .find_map(|x| match &x.src_cond {
+                TaxCondition::Volume(x) => Some(x.clone()),
+                _ => None,
+            });
+        if let Some(x) = volume {
+            headroom.push(TaxFreeHeadroom {
+                action,
+                allowance: x.allowance,
+                remaining: x.headroom(deps.storage, &env.block, &address)?,
+            });
+        }
+    }
+    Ok(TaxFreeVolumeResponse { headroom })
+}
+
+pub fn query_tax_discounts(deps: Deps, address: String) -> StdResult<TaxDiscountsResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let balance = BALANCES
+        .may_load(deps.storage, &address)?
+        .unwrap_or_default();
+    let tax_map = TAX_INFO.load(deps.storage)?;
+    let discounts = tax_map
+        .tax_infos()
+        .iter()
+        .filter(|(_, x)| !x.discounts.is_empty())
+        .map(|(action, x)| TaxDiscount {
+            action: action.clone(),
+            discount: x.discount(balance),
+        })
+        .collect();
+    Ok(TaxDiscountsResponse { balance, discounts })
+}
+
+pub fn query_simulate_tax(
+    deps: Deps,
+    env: Env,
+    action: TaxAction,
+    sender: String,
+    recipient: String,
+    amount: Uint128,
+    msg: Option<Binary>,
+) -> StdResult<SimulateTaxResponse> {
+    let sender = deps.api.addr_validate(&sender)?;
+    let recipient = deps.api.addr_validate(&recipient)?;
+    let tax_map = TAX_INFO.load(deps.storage)?;
+    let tax_info = tax_map.tax_info(&action);
+    let mut ctx = TaxContext::new(deps, &env);
+    if let Some(msg) = &msg {
+        ctx = ctx.with_hook(msg);
+    }
+    let deduction = tax_info
+        .deduct(&ctx, sender, recipient, amount)
+        .map_err(|err| StdError::generic_err(err.to_string()))?;
+    let rule = deduction.rule.map(|index| TaxRuleMatch {
+        index: index as u32,
+        name: tax_info.effective_rules()[index].name.clone(),
+    });
+    Ok(SimulateTaxResponse {
+        net: deduction.net,
+        gross: deduction.gross,
+        tax: deduction.tax,
+        proceeds: (!deduction.tax.is_zero()).then(|| deduction.proceeds.into_string()),
+        rule,
+    })
+}
+
+/// Dry-runs `SetTaxMap` with the given tax map against the validation, the tax
+/// rate ceiling and the rate limit, without checking who may set it
+pub fn query_validate_tax_map(
+    deps: Deps,
+    env: Env,
+    tax_map: TaxMap,
+) -> StdResult<ValidateTaxMapResponse> {
+    let curr_tax_map = TAX_INFO.load(deps.storage)?;
+    let res = tax_map
+        .validate_with_api(deps.api, MAX_TAX_RATE.may_load(deps.storage)?)
+        .and_then(|_| check_tax_rate_change(deps.storage, &env.block, &curr_tax_map, &tax_map));
+    let errors = match res {
+        Ok(_) => vec![],
+        Err(ContractError::InvalidTaxMap { errors }) => {
+            errors.iter().map(|err| err.to_string()).collect()
+        }
+        Err(err) => vec![err.to_string()],
+    };
+    Ok(ValidateTaxMapResponse {
+        valid: errors.is_empty(),
+        errors,
+    })
+}
+
+pub fn query_tax_admins(deps: Deps) -> StdResult<TaxAdminsResponse> {
+    let tax_map = TAX_INFO.load(deps.storage)?;
+    let actions = tax_map
+        .tax_infos()
+        .iter()
+        .map(|(action, _)| TaxActionAdmin {
+            action: action.clone(),
+            admin: tax_map.action_admin(action).to_string(),
+        })
+        .collect();
+    Ok(TaxAdminsResponse {
+        admin: tax_map.admin.to_string(),
+        actions,
+    })
+}
+
+pub fn query_max_tax_rate(deps: Deps) -> StdResult<MaxTaxRateResponse> {
+    Ok(MaxTaxRateResponse {
+        max_tax_rate: MAX_TAX_RATE.may_load(deps.storage)?,
+    })
+}
+
+pub fn query_pending_admin(pending: Option<PendingAdmin>) -> PendingAdminResponse {
+    match pending {
+        Some(pending) => PendingAdminResponse {
+            pending_admin: Some(pending.admin.into_string()),
+            expires: Some(pending.expires),
+        },
+        None => PendingAdminResponse {
+            pending_admin: None,
+            expires: None,
+        },
     }
 }
 
+/// Returns the tax map that was accepted last at or before the given height
+pub fn query_tax_map_at(deps: Deps, height: u64) -> StdResult<TaxMapVersion> {
+    let version = TAX_MAP_HEIGHTS
+        .range(
+            deps.storage,
+            None,
+            Some(Bound::inclusive(height)),
+            Order::Descending,
+        )
+        .next()
+        .transpose()?
+        .map(|(_, version)| version)
+        .ok_or_else(|| StdError::not_found("tax map at height"))?;
+    TAX_MAP_HISTORY.load(deps.storage, version)
+}
+
+pub fn query_tax_rate_limit(deps: Deps) -> StdResult<TaxRateLimitResponse> {
+    let window = TAX_RATE_WINDOW.may_load(deps.storage)?;
+    Ok(TaxRateLimitResponse {
+        tax_rate_limit: TAX_RATE_LIMIT.may_load(deps.storage)?,
+        epoch_start_height: window.as_ref().map(|w| w.height),
+        epoch_start_time: window.map(|w| w.time),
+    })
+}
+
 pub fn query_balance(deps: Deps, address: String) -> StdResult<BalanceResponse> {
     let address = deps.api.addr_validate(&address)?;
     let balance = BALANCES
@@ -766,12 +1453,13 @@ pub fn query_download_logo(deps: Deps) -> StdResult<DownloadLogoResponse> {
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn migrate(deps: DepsMut, _env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
+pub fn migrate(deps: DepsMut, env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
     // we support upgrading from
     // terraport tokens
     // terraswap tokens
     // cw20-base 1.0.1 (FRG token)
     state::migrate_v1::ensure_known_upgrade_path(deps.storage)?;
+    msg.validate()?;
 
     // after merging upgrade paths and normalize to cw-base 1.1.0
     // check normal upgrade flow
@@ -788,53 +1476,102 @@ pub fn migrate(deps: DepsMut, _env: Env, msg: MigrateMsg) -> Result<Response, Co
         }
     }
 
-    if original_version < "1.1.0+taxed002".parse::<semver::Version>().unwrap() {
-        match TAX_INFO.load(deps.storage) {
-            // there seems to be an existing tax map, so we don't need to do anything
-            Ok(_) => {}
-
-            // no tax map, so we need to add one
-            Err(_) => {
-                // Add tax map
-                let tax_map = match msg.tax_map {
-                    Some(x) => x,
-                    None => TaxMap::default(),
-                };
-                TAX_INFO.save(deps.storage, &tax_map)?;
-            }
+    let mut events: Vec<Event> = vec![];
+    match TAX_INFO.may_load(deps.storage)? {
+        // tokens from before 1.1.0+taxed002 have no tax map, so we need to add one
+        None => {
+            let tax_map = msg.tax_map.unwrap_or_default();
+            tax_map.validate_with_api(deps.api, MAX_TAX_RATE.may_load(deps.storage)?)?;
+            let version = save_tax_map(deps.storage, &env.block, &tax_map)?;
+            events.push(
+                TaxMapUpdatedEvent {
+                    action: "migrate",
+                    admin: &tax_map.admin,
+                    tax_map_version: version,
+                }
+                .into(),
+            );
         }
-        match ANTI_WHALE_INFO.load(deps.storage) {
-            // there seems to be an existing whale info, so we don't need to do anything
-            Ok(_) => {}
-
-            // no whale info, so we need to add one
-            Err(_) => {
-                // Add whale info
-                let whale_info = match msg.whale_info {
-                    Some(x) => x,
-                    None => WhaleInfo {
-                        threshold: Decimal::percent(100),
-                        whitelist: vec![],
-                        admin: Addr::unchecked(""),
-                    },
-                };
-                ANTI_WHALE_INFO.save(deps.storage, &whale_info)?;
+        Some(curr_tax_map) => {
+            // tokens from before the tax map history start it with the active tax map
+            if TAX_MAP_VERSION.may_load(deps.storage)?.is_none() {
+                save_tax_map(deps.storage, &env.block, &curr_tax_map)?;
+            }
+            // a supplied tax map replaces the existing one like `SetTaxMap` would
+            if let Some(tax_map) = msg.tax_map {
+                let version = store_tax_map(deps.storage, deps.api, &env, &curr_tax_map, &tax_map)?;
+                events.push(
+                    TaxMapUpdatedEvent {
+                        action: "migrate",
+                        admin: &tax_map.admin,
+                        tax_map_version: version,
+                    }
+                    .into(),
+                );
             }
         }
     }
 
-    match msg.new_info {
-        Some(new_info) => {
-            let mut old_info = TOKEN_INFO.load(deps.storage)?;
-            old_info.name = new_info.name;
-            old_info.symbol = new_info.symbol;
-            old_info.mint = new_info.mint;
-            TOKEN_INFO.save(deps.storage, &old_info)?;
-        }
+    match (msg.whale_info, ANTI_WHALE_INFO.may_load(deps.storage)?) {
+        (Some(whale_info), _) => {
+            whale_info.validate_with_api(deps.api)?;
+            ANTI_WHALE_INFO.save(deps.storage, &whale_info)?;
+            events.push(
+                WhaleInfoUpdatedEvent {
+                    whale_info: &whale_info,
+                }
+                .into(),
+            );
+        }
+        // no whale info, so we need to add one
+        (None, None) => ANTI_WHALE_INFO.save(
+            deps.storage,
+            &WhaleInfo {
+                threshold: Decimal::percent(100),
+                whitelist: vec![],
+                admin: Addr::unchecked(""),
+            },
+        )?,
+        (None, Some(_)) => {}
+    }
+
+    if let Some(max_tax_rate) = msg.max_tax_rate {
+        let tax_map = TAX_INFO.load(deps.storage)?;
+        set_max_tax_rate(deps.storage, &tax_map, max_tax_rate)?;
+    }
+
+    if let Some(tax_rate_limit) = msg.tax_rate_limit {
+        tax_rate_limit.validate()?;
+        TAX_RATE_LIMIT.save(deps.storage, &tax_rate_limit)?;
+    }
+
+    if let Some(approval) = msg.approval {
+        // once set, the signers can only be changed with their own approval
+        if APPROVAL_CONFIG.may_load(deps.storage)?.is_some() {
+            return Err(ContractError::ApprovalRequired {});
+        }
+        approval.validate_with_api(deps.api)?;
+        APPROVAL_CONFIG.save(deps.storage, &approval)?;
+    }
+
+    match msg.new_info {
+        Some(new_info) => {
+            let mut old_info = TOKEN_INFO.load(deps.storage)?;
+            old_info.name = new_info.name;
+            old_info.symbol = new_info.symbol;
+            old_info.mint = match new_info.mint {
+                Some(m) => Some(MinterData {
+                    minter: deps.api.addr_validate(m.minter.as_str())?,
+                    cap: m.cap,
+                }),
+                None => None,
+            };
+            TOKEN_INFO.save(deps.storage, &old_info)?;
+        }
         None => (),
     }
 
-    Ok(Response::default())
+    Ok(Response::new().add_events(events))
 }
 
 #[cfg(test)]
@@ -845,10 +1582,17 @@ mod tests {
     use cosmwasm_std::{
         coins, from_json, to_binary, Addr, CosmosMsg, Decimal, StdError, SubMsg, WasmMsg,
     };
+    use cw20::Cw20ReceiveMsg;
 
     use super::*;
-    use crate::msg::InstantiateMarketingInfo;
-    use crate::tax::{TaxAlwaysCondition, TaxCondition, TaxInfo, TaxNeverCondition};
+    use crate::msg::{InstantiateMarketingInfo, TaxMapHistoryResponse};
+    use crate::state::ACQUIRED_AT;
+    use crate::tax::{
+        TaxAddressCondition, TaxAlwaysCondition, TaxCondition, TaxDiscountTier, TaxEpoch,
+        TaxHoldingPeriodCondition, TaxHoldingTier, TaxInfo, TaxMode, TaxNativePayment,
+        TaxNativePrice, TaxNeverCondition, TaxRateLimit, TaxRule, TaxSendHookCondition,
+        TaxVolumeCondition,
+    };
     use crate::whale::WhaleInfo;
 
     fn get_balance<T: Into<String>>(deps: Deps, address: T) -> Uint128 {
@@ -922,6 +1666,9 @@ mod tests {
             marketing: None,
             tax_map: None,
             whale_info: None,
+            max_tax_rate: None,
+            tax_rate_limit: None,
+            approval: None,
         };
         let info = mock_info("creator", &[]);
         let env = mock_env();
@@ -960,21 +1707,45 @@ mod tests {
                     tax_rate: Decimal::percent(10),
                 }),
                 proceeds: Addr::unchecked(String::from("proceeds")),
+                admin: None,
+                discounts: vec![],
+                referral_share: Decimal::zero(),
+                mode: TaxMode::Deduct,
+                native_payment: None,
+                rules: None,
             },
             on_send: TaxInfo {
                 src_cond: TaxCondition::Never(TaxNeverCondition {}),
                 dst_cond: TaxCondition::Never(TaxNeverCondition {}),
                 proceeds: Addr::unchecked(""),
+                admin: None,
+                discounts: vec![],
+                referral_share: Decimal::zero(),
+                mode: TaxMode::Deduct,
+                native_payment: None,
+                rules: None,
             },
             on_send_from: TaxInfo {
                 src_cond: TaxCondition::Never(TaxNeverCondition {}),
                 dst_cond: TaxCondition::Never(TaxNeverCondition {}),
                 proceeds: Addr::unchecked(""),
+                admin: None,
+                discounts: vec![],
+                referral_share: Decimal::zero(),
+                mode: TaxMode::Deduct,
+                native_payment: None,
+                rules: None,
             },
             on_transfer_from: TaxInfo {
                 src_cond: TaxCondition::Never(TaxNeverCondition {}),
                 dst_cond: TaxCondition::Never(TaxNeverCondition {}),
                 proceeds: Addr::unchecked(""),
+                admin: None,
+                discounts: vec![],
+                referral_share: Decimal::zero(),
+                mode: TaxMode::Deduct,
+                native_payment: None,
+                rules: None,
             },
             admin: Addr::unchecked(""),
         });
@@ -991,6 +1762,9 @@ mod tests {
             marketing: None,
             tax_map: tax_map_in,
             whale_info: None,
+            max_tax_rate: None,
+            tax_rate_limit: None,
+            approval: None,
         };
         let info = mock_info("creator", &[]);
         let env = mock_env();
@@ -1022,6 +1796,12 @@ mod tests {
                 src_cond: TaxCondition::Never(TaxNeverCondition {}),
                 dst_cond: TaxCondition::Never(TaxNeverCondition {}),
                 proceeds: Addr::unchecked(""),
+                admin: None,
+                discounts: vec![],
+                referral_share: Decimal::zero(),
+                mode: TaxMode::Deduct,
+                native_payment: None,
+                rules: None,
             },
             on_send: TaxInfo {
                 src_cond: TaxCondition::Always(TaxAlwaysCondition {
@@ -1031,16 +1811,34 @@ mod tests {
                     tax_rate: Decimal::percent(10),
                 }),
                 proceeds: Addr::unchecked(String::from("proceeds")),
+                admin: None,
+                discounts: vec![],
+                referral_share: Decimal::zero(),
+                mode: TaxMode::Deduct,
+                native_payment: None,
+                rules: None,
             },
             on_send_from: TaxInfo {
                 src_cond: TaxCondition::Never(TaxNeverCondition {}),
                 dst_cond: TaxCondition::Never(TaxNeverCondition {}),
                 proceeds: Addr::unchecked(""),
+                admin: None,
+                discounts: vec![],
+                referral_share: Decimal::zero(),
+                mode: TaxMode::Deduct,
+                native_payment: None,
+                rules: None,
             },
             on_transfer_from: TaxInfo {
                 src_cond: TaxCondition::Never(TaxNeverCondition {}),
                 dst_cond: TaxCondition::Never(TaxNeverCondition {}),
                 proceeds: Addr::unchecked(""),
+                admin: None,
+                discounts: vec![],
+                referral_share: Decimal::zero(),
+                mode: TaxMode::Deduct,
+                native_payment: None,
+                rules: None,
             },
             admin: Addr::unchecked(""),
         });
@@ -1057,6 +1855,9 @@ mod tests {
             marketing: None,
             tax_map: tax_map_in,
             whale_info: None,
+            max_tax_rate: None,
+            tax_rate_limit: None,
+            approval: None,
         };
         let info = mock_info("creator", &[]);
         let env = mock_env();
@@ -1100,6 +1901,9 @@ mod tests {
             marketing: None,
             tax_map: None,
             whale_info: whale_info_in,
+            max_tax_rate: None,
+            tax_rate_limit: None,
+            approval: None,
         };
         let info = mock_info("creator", &[]);
         let env = mock_env();
@@ -1141,6 +1945,9 @@ mod tests {
                 marketing: None,
                 tax_map: None,
                 whale_info: None,
+                max_tax_rate: None,
+                tax_rate_limit: None,
+                approval: None,
             };
             let info = mock_info("creator", &[]);
             let env = mock_env();
@@ -1183,6 +1990,9 @@ mod tests {
                 marketing: None,
                 tax_map: None,
                 whale_info: None,
+                max_tax_rate: None,
+                tax_rate_limit: None,
+                approval: None,
             };
             let info = mock_info("creator", &[]);
             let env = mock_env();
@@ -1232,6 +2042,9 @@ mod tests {
                 marketing: None,
                 tax_map: None,
                 whale_info: None,
+                max_tax_rate: None,
+                tax_rate_limit: None,
+                approval: None,
             };
             let info = mock_info("creator", &[]);
             let env = mock_env();
@@ -1262,6 +2075,9 @@ mod tests {
                     }),
                     tax_map: None,
                     whale_info: None,
+                    max_tax_rate: None,
+                    tax_rate_limit: None,
+                    approval: None,
                 };
 
                 let info = mock_info("creator", &[]);
@@ -1303,6 +2119,9 @@ mod tests {
                     }),
                     tax_map: None,
                     whale_info: None,
+                    max_tax_rate: None,
+                    tax_rate_limit: None,
+                    approval: None,
                 };
 
                 let info = mock_info("creator", &[]);
@@ -1519,6 +2338,9 @@ mod tests {
             marketing: None,
             tax_map: None,
             whale_info: None,
+            max_tax_rate: None,
+            tax_rate_limit: None,
+            approval: None,
         };
         let err =
             instantiate(deps.as_mut(), env.clone(), info.clone(), instantiate_msg).unwrap_err();
@@ -1543,6 +2365,9 @@ mod tests {
             marketing: None,
             tax_map: None,
             whale_info: None,
+            max_tax_rate: None,
+            tax_rate_limit: None,
+            approval: None,
         };
         let res = instantiate(deps.as_mut(), env, info, instantiate_msg).unwrap();
         assert_eq!(0, res.messages.len());
@@ -1606,6 +2431,12 @@ mod tests {
                     tax_rate: Decimal::percent(10),
                 }),
                 proceeds: Addr::unchecked(String::from("proceeds")),
+                admin: None,
+                discounts: vec![],
+                referral_share: Decimal::zero(),
+                mode: TaxMode::Deduct,
+                native_payment: None,
+                rules: None,
             },
             on_send: TaxInfo {
                 src_cond: TaxCondition::Always(TaxAlwaysCondition {
@@ -1615,16 +2446,34 @@ mod tests {
                     tax_rate: Decimal::percent(10),
                 }),
                 proceeds: Addr::unchecked(String::from("proceeds")),
+                admin: None,
+                discounts: vec![],
+                referral_share: Decimal::zero(),
+                mode: TaxMode::Deduct,
+                native_payment: None,
+                rules: None,
             },
             on_send_from: TaxInfo {
                 src_cond: TaxCondition::Never(TaxNeverCondition {}),
                 dst_cond: TaxCondition::Never(TaxNeverCondition {}),
                 proceeds: Addr::unchecked(""),
+                admin: None,
+                discounts: vec![],
+                referral_share: Decimal::zero(),
+                mode: TaxMode::Deduct,
+                native_payment: None,
+                rules: None,
             },
             on_transfer_from: TaxInfo {
                 src_cond: TaxCondition::Never(TaxNeverCondition {}),
                 dst_cond: TaxCondition::Never(TaxNeverCondition {}),
                 proceeds: Addr::unchecked(""),
+                admin: None,
+                discounts: vec![],
+                referral_share: Decimal::zero(),
+                mode: TaxMode::Deduct,
+                native_payment: None,
+                rules: None,
             },
             admin: Addr::unchecked(admin),
         }
@@ -1640,6 +2489,12 @@ mod tests {
                     tax_rate: Decimal::percent(110),
                 }),
                 proceeds: Addr::unchecked(String::from("proceeds")),
+                admin: None,
+                discounts: vec![],
+                referral_share: Decimal::zero(),
+                mode: TaxMode::Deduct,
+                native_payment: None,
+                rules: None,
             },
             on_send: TaxInfo {
                 src_cond: TaxCondition::Always(TaxAlwaysCondition {
@@ -1649,16 +2504,34 @@ mod tests {
                     tax_rate: Decimal::percent(10),
                 }),
                 proceeds: Addr::unchecked(String::from("proceeds")),
+                admin: None,
+                discounts: vec![],
+                referral_share: Decimal::zero(),
+                mode: TaxMode::Deduct,
+                native_payment: None,
+                rules: None,
             },
             on_send_from: TaxInfo {
                 src_cond: TaxCondition::Never(TaxNeverCondition {}),
                 dst_cond: TaxCondition::Never(TaxNeverCondition {}),
                 proceeds: Addr::unchecked(""),
+                admin: None,
+                discounts: vec![],
+                referral_share: Decimal::zero(),
+                mode: TaxMode::Deduct,
+                native_payment: None,
+                rules: None,
             },
             on_transfer_from: TaxInfo {
                 src_cond: TaxCondition::Never(TaxNeverCondition {}),
                 dst_cond: TaxCondition::Never(TaxNeverCondition {}),
                 proceeds: Addr::unchecked(""),
+                admin: None,
+                discounts: vec![],
+                referral_share: Decimal::zero(),
+                mode: TaxMode::Deduct,
+                native_payment: None,
+                rules: None,
             },
             admin: Addr::unchecked(admin),
         }
@@ -1683,6 +2556,9 @@ mod tests {
             marketing: None,
             tax_map: Some(tax_map_in),
             whale_info: None,
+            max_tax_rate: None,
+            tax_rate_limit: None,
+            approval: None,
         };
         let info = mock_info("creator", &[]);
         let env = mock_env();
@@ -1690,6 +2566,89 @@ mod tests {
         assert_eq!(res.is_ok(), true);
     }
 
+    #[test]
+    fn set_tax_map_validates_addresses() {
+        let mut deps = mock_dependencies();
+        let instantiate_msg = InstantiateMsg {
+            name: "Auto Gen".to_string(),
+            symbol: "AUTO".to_string(),
+            decimals: 3,
+            initial_balances: vec![],
+            mint: None,
+            marketing: None,
+            tax_map: Some(mock_valid_tax_map("admin".to_string())),
+            whale_info: None,
+            max_tax_rate: None,
+            tax_rate_limit: None,
+            approval: None,
+        };
+        let info = mock_info("creator", &[]);
+        instantiate(deps.as_mut(), mock_env(), info, instantiate_msg).unwrap();
+
+        let mut tax_map = mock_valid_tax_map("admin".to_string());
+        tax_map.on_transfer.proceeds = Addr::unchecked("");
+        tax_map.on_send.proceeds = Addr::unchecked("Proceeds");
+        // proceeds of actions that are never taxed do not matter
+        tax_map.on_send_from.proceeds = Addr::unchecked("Unused");
+
+        // the dry run lists every problem
+        let res: ValidateTaxMapResponse = from_json(
+            query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::ValidateTaxMap {
+                    tax_map: Box::new(tax_map.clone()),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            res,
+            ValidateTaxMapResponse {
+                valid: false,
+                errors: vec![
+                    "Proceeds wallet of on_transfer cannot be empty".to_string(),
+                    "Invalid address Proceeds in on_send.proceeds of the tax map".to_string(),
+                ],
+            }
+        );
+
+        let info = mock_info("admin", &[]);
+        let msg = ExecuteMsg::SetTaxMap {
+            tax_map: Some(tax_map),
+        };
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InvalidTaxMap {
+                errors: vec![
+                    ContractError::MissingProceeds {
+                        action: "on_transfer".to_string(),
+                    },
+                    ContractError::InvalidTaxAddress {
+                        field: "on_send.proceeds".to_string(),
+                        address: "Proceeds".to_string(),
+                    },
+                ],
+            }
+        );
+
+        let res: ValidateTaxMapResponse = from_json(
+            query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::ValidateTaxMap {
+                    tax_map: Box::new(mock_valid_tax_map("admin".to_string())),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert!(res.valid);
+        assert!(res.errors.is_empty());
+    }
+
     #[test]
     fn cannot_set_tax_map_if_not_admin() {
         let mut deps = mock_dependencies();
@@ -1709,6 +2668,9 @@ mod tests {
             marketing: None,
             tax_map: Some(tax_map_in),
             whale_info: None,
+            max_tax_rate: None,
+            tax_rate_limit: None,
+            approval: None,
         };
         let info = mock_info("creator", &[]);
         let env = mock_env();
@@ -1744,6 +2706,9 @@ mod tests {
             marketing: None,
             tax_map: Some(tax_map_in),
             whale_info: None,
+            max_tax_rate: None,
+            tax_rate_limit: None,
+            approval: None,
         };
         let info = mock_info("creator", &[]);
         let env = mock_env();
@@ -1779,6 +2744,9 @@ mod tests {
             marketing: None,
             tax_map: Some(tax_map_valid),
             whale_info: None,
+            max_tax_rate: None,
+            tax_rate_limit: None,
+            approval: None,
         };
         let env = mock_env();
         let info = mock_info("creator", &[]);
@@ -1813,6 +2781,9 @@ mod tests {
             marketing: None,
             tax_map: Some(tax_map_in),
             whale_info: None,
+            max_tax_rate: None,
+            tax_rate_limit: None,
+            approval: None,
         };
         let info = mock_info("creator", &[]);
         let env = mock_env();
@@ -1820,6 +2791,255 @@ mod tests {
         assert_eq!(res.is_err(), true);
     }
 
+    #[test]
+    fn tax_admin_can_patch_single_tax_info() {
+        let mut deps = mock_dependencies();
+        do_instantiate_with_max_tax_rate(deps.as_mut(), Decimal::percent(20));
+        let tax_map_in = mock_valid_tax_map("admin".to_string());
+        let env = mock_env();
+
+        let patch_proceeds = ExecuteMsg::PatchTaxMap {
+            action: TaxAction::OnSend,
+            tax_info: None,
+            proceeds: Some(String::from("marketing")),
+        };
+        let info = mock_info("not_admin", &[]);
+        let res = execute(deps.as_mut(), env.clone(), info, patch_proceeds.clone());
+        assert_eq!(res.unwrap_err(), ContractError::Unauthorized {});
+
+        // only the proceeds of on_send change
+        let info = mock_info("admin", &[]);
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), patch_proceeds).unwrap();
+        let mut expected_tax_map = tax_map_in.clone();
+        expected_tax_map.on_send.proceeds = Addr::unchecked("marketing");
+        assert_eq!(TAX_INFO.load(&deps.storage).unwrap(), expected_tax_map);
+        assert_eq!(
+            res.events,
+            vec![
+                Event::new("tax_map_patched")
+                    .add_attribute("tax_action", "on_send")
+                    .add_attribute("changed", "proceeds")
+                    .add_attribute("old_proceeds", "proceeds")
+                    .add_attribute("new_proceeds", "marketing"),
+                Event::new("tax_map_updated")
+                    .add_attribute("action", "patch_tax_map")
+                    .add_attribute("admin", "admin")
+                    .add_attribute("tax_map_version", "2"),
+            ]
+        );
+
+        // the same proceeds again is no change at all
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            ExecuteMsg::PatchTaxMap {
+                action: TaxAction::OnSend,
+                tax_info: None,
+                proceeds: Some(String::from("marketing")),
+            },
+        );
+        assert_eq!(res.unwrap_err(), ContractError::EmptyTaxMapPatch {});
+
+        // patched tax infos are validated like a full tax map
+        let mut tax_info = tax_map_in.on_transfer.clone();
+        tax_info.src_cond = TaxCondition::Always(TaxAlwaysCondition {
+            tax_rate: Decimal::percent(30),
+        });
+        let msg = ExecuteMsg::PatchTaxMap {
+            action: TaxAction::OnTransfer,
+            tax_info: Some(tax_info.clone()),
+            proceeds: None,
+        };
+        assert!(execute(deps.as_mut(), env.clone(), info.clone(), msg).is_err());
+
+        tax_info.src_cond = TaxCondition::Always(TaxAlwaysCondition {
+            tax_rate: Decimal::percent(5),
+        });
+        let msg = ExecuteMsg::PatchTaxMap {
+            action: TaxAction::OnTransfer,
+            tax_info: Some(tax_info.clone()),
+            proceeds: None,
+        };
+        let res = execute(deps.as_mut(), env, info, msg).unwrap();
+        expected_tax_map.on_transfer = tax_info;
+        assert_eq!(TAX_INFO.load(&deps.storage).unwrap(), expected_tax_map);
+        assert_eq!(
+            res.events[0].attributes[1],
+            cosmwasm_std::attr("changed", "src_cond")
+        );
+        assert_eq!(
+            res.events[0].attributes[3],
+            cosmwasm_std::attr("new_src_cond", r#"{"Always":{"tax_rate":"0.05"}}"#)
+        );
+    }
+
+    #[test]
+    fn patch_reports_every_changed_field() {
+        let mut deps = mock_dependencies();
+        do_instantiate_with_max_tax_rate(deps.as_mut(), Decimal::percent(20));
+        let on_send = mock_valid_tax_map("admin".to_string()).on_send;
+
+        let mut discounts = on_send.clone();
+        discounts.discounts = vec![TaxDiscountTier {
+            min_balance: Uint128::new(1000),
+            discount: Decimal::percent(50),
+        }];
+        let mut referral_share = on_send.clone();
+        referral_share.referral_share = Decimal::percent(30);
+        let mut mode = on_send.clone();
+        mode.mode = TaxMode::OnTop;
+        let mut native_payment = on_send.clone();
+        native_payment.native_payment = Some(TaxNativePayment {
+            denom: "uluna".to_string(),
+            price: TaxNativePrice::Fixed(Decimal::percent(2)),
+        });
+
+        let patches = [
+            (
+                discounts,
+                "discounts",
+                "[]",
+                r#"[{"min_balance":"1000","discount":"0.5"}]"#,
+            ),
+            (referral_share, "referral_share", "0", "0.3"),
+            (mode, "mode", "deduct", "on_top"),
+            (
+                native_payment,
+                "native_payment",
+                "null",
+                r#"{"denom":"uluna","price":{"fixed":"0.02"}}"#,
+            ),
+        ];
+        for (tax_info, field, old, new) in patches {
+            let msg = ExecuteMsg::PatchTaxMap {
+                action: TaxAction::OnSend,
+                tax_info: Some(tax_info.clone()),
+                proceeds: None,
+            };
+            let res = execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+            assert_eq!(
+                res.events[0],
+                Event::new("tax_map_patched")
+                    .add_attribute("tax_action", "on_send")
+                    .add_attribute("changed", field)
+                    .add_attribute(format!("old_{}", field), old)
+                    .add_attribute(format!("new_{}", field), new)
+            );
+
+            // revert the field for the next patch
+            let msg = ExecuteMsg::PatchTaxMap {
+                action: TaxAction::OnSend,
+                tax_info: Some(on_send.clone()),
+                proceeds: None,
+            };
+            execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+        }
+    }
+
+    #[test]
+    fn scoped_tax_admin_controls_only_its_action() {
+        let mut deps = mock_dependencies();
+        do_instantiate_with_max_tax_rate(deps.as_mut(), Decimal::percent(20));
+        let env = mock_env();
+
+        // the super admin hands on_send over to marketing
+        let mut tax_info = mock_valid_tax_map("admin".to_string()).on_send;
+        tax_info.admin = Some(Addr::unchecked("marketing"));
+        let msg = ExecuteMsg::PatchTaxMap {
+            action: TaxAction::OnSend,
+            tax_info: Some(tax_info),
+            proceeds: None,
+        };
+        let res = execute(deps.as_mut(), env.clone(), mock_info("admin", &[]), msg).unwrap();
+        assert_eq!(
+            res.events[0].attributes[1],
+            cosmwasm_std::attr("changed", "admin")
+        );
+
+        let res: TaxAdminsResponse =
+            from_json(query(deps.as_ref(), env.clone(), QueryMsg::TaxAdmins {}).unwrap()).unwrap();
+        assert_eq!(res.admin, "admin");
+        assert_eq!(
+            res.actions,
+            vec![
+                TaxActionAdmin {
+                    action: TaxAction::OnTransfer,
+                    admin: "admin".to_string(),
+                },
+                TaxActionAdmin {
+                    action: TaxAction::OnTransferFrom,
+                    admin: "admin".to_string(),
+                },
+                TaxActionAdmin {
+                    action: TaxAction::OnSend,
+                    admin: "marketing".to_string(),
+                },
+                TaxActionAdmin {
+                    action: TaxAction::OnSendFrom,
+                    admin: "admin".to_string(),
+                },
+            ]
+        );
+
+        // marketing may tune on_send
+        let marketing = mock_info("marketing", &[]);
+        let mut tax_map = TAX_INFO.load(&deps.storage).unwrap();
+        tax_map.on_send.src_cond = TaxCondition::Always(TaxAlwaysCondition {
+            tax_rate: Decimal::percent(5),
+        });
+        let msg = ExecuteMsg::SetTaxMap {
+            tax_map: Some(tax_map.clone()),
+        };
+        execute(deps.as_mut(), env.clone(), marketing.clone(), msg).unwrap();
+        assert_eq!(TAX_INFO.load(&deps.storage).unwrap(), tax_map);
+        let msg = ExecuteMsg::PatchTaxMap {
+            action: TaxAction::OnSend,
+            tax_info: None,
+            proceeds: Some(String::from("marketing_wallet")),
+        };
+        execute(deps.as_mut(), env.clone(), marketing.clone(), msg).unwrap();
+
+        // ... but not on_transfer
+        let msg = ExecuteMsg::PatchTaxMap {
+            action: TaxAction::OnTransfer,
+            tax_info: None,
+            proceeds: Some(String::from("marketing_wallet")),
+        };
+        let err = execute(deps.as_mut(), env.clone(), marketing.clone(), msg).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        let mut tax_map = TAX_INFO.load(&deps.storage).unwrap();
+        tax_map.on_transfer.proceeds = Addr::unchecked("marketing_wallet");
+        let msg = ExecuteMsg::SetTaxMap {
+            tax_map: Some(tax_map),
+        };
+        let err = execute(deps.as_mut(), env.clone(), marketing.clone(), msg).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        // ... nor the admins themselves
+        let mut tax_map = TAX_INFO.load(&deps.storage).unwrap();
+        tax_map.on_send.admin = Some(Addr::unchecked("intern"));
+        let msg = ExecuteMsg::SetTaxMap {
+            tax_map: Some(tax_map),
+        };
+        let err = execute(deps.as_mut(), env.clone(), marketing.clone(), msg).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        let mut tax_map = TAX_INFO.load(&deps.storage).unwrap();
+        tax_map.admin = Addr::unchecked("marketing");
+        let msg = ExecuteMsg::SetTaxMap {
+            tax_map: Some(tax_map),
+        };
+        let err = execute(deps.as_mut(), env.clone(), marketing, msg).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        // unrelated senders cannot even resubmit the current tax map
+        let msg = ExecuteMsg::SetTaxMap {
+            tax_map: Some(TAX_INFO.load(&deps.storage).unwrap()),
+        };
+        let err = execute(deps.as_mut(), env, mock_info("not_admin", &[]), msg).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+    }
+
     #[test]
     fn ensure_setting_empty_tax_map_preserves_admin() {
         let mut deps = mock_dependencies();
@@ -1841,6 +3061,9 @@ mod tests {
             marketing: None,
             tax_map: Some(tax_map_in),
             whale_info: None,
+            max_tax_rate: None,
+            tax_rate_limit: None,
+            approval: None,
         };
         let info = mock_info("creator", &[]);
         let env = mock_env();
@@ -1854,6 +3077,62 @@ mod tests {
         assert_eq!(TAX_INFO.load(&deps.storage).unwrap(), expected_tax_map);
     }
 
+    #[test]
+    fn admins_cannot_be_replaced_without_handover() {
+        let mut deps = mock_dependencies();
+        let instantiate_msg = InstantiateMsg {
+            name: "Auto Gen".to_string(),
+            symbol: "AUTO".to_string(),
+            decimals: 3,
+            initial_balances: vec![],
+            mint: None,
+            marketing: None,
+            tax_map: Some(mock_valid_tax_map("admin".to_string())),
+            whale_info: None,
+            max_tax_rate: None,
+            tax_rate_limit: None,
+            approval: None,
+        };
+        let env = mock_env();
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            instantiate_msg,
+        )
+        .unwrap();
+
+        // neither handing over nor renouncing the tax admin via the tax map
+        for admin in ["other", ""] {
+            let msg = ExecuteMsg::SetTaxMap {
+                tax_map: Some(mock_valid_tax_map(admin.to_string())),
+            };
+            let err =
+                execute(deps.as_mut(), env.clone(), mock_info("admin", &[]), msg).unwrap_err();
+            assert_eq!(err, ContractError::AdminChangeNotAllowed {});
+        }
+        assert_eq!(
+            TAX_INFO.load(&deps.storage).unwrap().admin,
+            Addr::unchecked("admin")
+        );
+
+        // nor the whale admin via the whale info
+        let mut whale_info = ANTI_WHALE_INFO.load(&deps.storage).unwrap();
+        let whale_admin = whale_info.admin.clone();
+        whale_info.admin = Addr::unchecked("other");
+        let msg = ExecuteMsg::SetWhaleInfo {
+            whale_info: Some(whale_info),
+        };
+        let err = execute(
+            deps.as_mut(),
+            env,
+            mock_info(whale_admin.as_str(), &[]),
+            msg,
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::AdminChangeNotAllowed {});
+    }
+
     #[test]
     fn tax_admin_can_update_tax_admin() {
         let mut deps = mock_dependencies();
@@ -1875,6 +3154,9 @@ mod tests {
             marketing: None,
             tax_map: Some(tax_map_in),
             whale_info: None,
+            max_tax_rate: None,
+            tax_rate_limit: None,
+            approval: None,
         };
         let info = mock_info("creator", &[]);
         let env = mock_env();
@@ -1884,10 +3166,35 @@ mod tests {
         let info = mock_info("admin", &[]);
         let msg = ExecuteMsg::SetTaxAdmin {
             tax_admin: Some(String::from("new_admin")),
+            expires: None,
         };
         let res = execute(deps.as_mut(), env.clone(), info, msg);
         assert_eq!(res.is_ok(), true);
+
+        // nothing changes until the new admin accepts
+        let pending = query_pending_admin(PENDING_TAX_ADMIN.may_load(&deps.storage).unwrap());
+        assert_eq!(pending.pending_admin, Some(String::from("new_admin")));
+        assert_ne!(TAX_INFO.load(&deps.storage).unwrap(), expected_tax_map);
+
+        let info = mock_info("not_new_admin", &[]);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            info,
+            ExecuteMsg::AcceptTaxAdmin {},
+        );
+        assert_eq!(res.unwrap_err(), ContractError::Unauthorized {});
+
+        let info = mock_info("new_admin", &[]);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            info,
+            ExecuteMsg::AcceptTaxAdmin {},
+        );
+        assert!(res.is_ok());
         assert_eq!(TAX_INFO.load(&deps.storage).unwrap(), expected_tax_map);
+        assert_eq!(PENDING_TAX_ADMIN.may_load(&deps.storage).unwrap(), None);
     }
 
     #[test]
@@ -1909,6 +3216,9 @@ mod tests {
             marketing: None,
             tax_map: Some(tax_map_in.clone()),
             whale_info: None,
+            max_tax_rate: None,
+            tax_rate_limit: None,
+            approval: None,
         };
         let info = mock_info("creator", &[]);
         let env = mock_env();
@@ -1918,6 +3228,7 @@ mod tests {
         let info = mock_info("not_admin", &[]);
         let msg = ExecuteMsg::SetTaxAdmin {
             tax_admin: Some(String::from("new_admin")),
+            expires: None,
         };
         let res = execute(deps.as_mut(), env.clone(), info, msg);
         assert_eq!(res.is_err(), true);
@@ -1925,140 +3236,1096 @@ mod tests {
     }
 
     #[test]
-    fn ensure_sound_setting_of_empty_tax_admin() {
+    fn ensure_sound_setting_of_empty_tax_admin() {
+        let mut deps = mock_dependencies();
+        let addr1 = String::from("addr0001");
+        let amount1 = Uint128::from(12340000u128);
+        let tax_map_in = mock_valid_tax_map("admin".to_string());
+        let mut expected_tax_map = tax_map_in.clone();
+        expected_tax_map.admin = Addr::unchecked("");
+
+        let instantiate_msg = InstantiateMsg {
+            name: "Auto Gen".to_string(),
+            symbol: "AUTO".to_string(),
+            decimals: 3,
+            initial_balances: vec![Cw20Coin {
+                address: addr1.to_string(),
+                amount: amount1,
+            }],
+            mint: None,
+            marketing: None,
+            tax_map: Some(tax_map_in),
+            whale_info: None,
+            max_tax_rate: None,
+            tax_rate_limit: None,
+            approval: None,
+        };
+        let info = mock_info("creator", &[]);
+        let env = mock_env();
+        let res = instantiate(deps.as_mut(), env.clone(), info, instantiate_msg);
+        assert_eq!(res.is_ok(), true);
+
+        // empty input is rejected instead of silently dropping the admin
+        let info = mock_info("admin", &[]);
+        let msg = ExecuteMsg::SetTaxAdmin {
+            tax_admin: None,
+            expires: None,
+        };
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg);
+        assert_eq!(res.unwrap_err(), ContractError::EmptyAdmin {});
+        let msg = ExecuteMsg::SetTaxAdmin {
+            tax_admin: Some(String::from("")),
+            expires: None,
+        };
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg);
+        assert_eq!(res.unwrap_err(), ContractError::EmptyAdmin {});
+
+        // renouncing has to be explicit
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            info,
+            ExecuteMsg::RenounceTaxAdmin {},
+        );
+        assert!(res.is_ok());
+        assert_eq!(TAX_INFO.load(&deps.storage).unwrap(), expected_tax_map);
+    }
+
+    #[test]
+    fn tax_admin_proposal_can_expire_and_be_cancelled() {
+        let mut deps = mock_dependencies();
+        do_instantiate_with_max_tax_rate(deps.as_mut(), Decimal::one());
+        let mut env = mock_env();
+
+        let info = mock_info("admin", &[]);
+        let msg = ExecuteMsg::SetTaxAdmin {
+            tax_admin: Some(String::from("new_admin")),
+            expires: Some(Expiration::AtHeight(env.block.height + 10)),
+        };
+        execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        // too late to accept
+        env.block.height += 10;
+        let new_admin = mock_info("new_admin", &[]);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            new_admin.clone(),
+            ExecuteMsg::AcceptTaxAdmin {},
+        );
+        assert_eq!(res.unwrap_err(), ContractError::AdminProposalExpired {});
+
+        // the admin can withdraw the proposal
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            new_admin.clone(),
+            ExecuteMsg::CancelTaxAdmin {},
+        );
+        assert_eq!(res.unwrap_err(), ContractError::Unauthorized {});
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            info,
+            ExecuteMsg::CancelTaxAdmin {},
+        )
+        .unwrap();
+        let res = execute(deps.as_mut(), env, new_admin, ExecuteMsg::AcceptTaxAdmin {});
+        assert_eq!(res.unwrap_err(), ContractError::NoPendingAdmin {});
+        assert_eq!(
+            TAX_INFO.load(&deps.storage).unwrap().admin,
+            Addr::unchecked("admin")
+        );
+    }
+
+    fn do_instantiate_with_max_tax_rate(deps: DepsMut, max_tax_rate: Decimal) {
+        let instantiate_msg = InstantiateMsg {
+            name: "Auto Gen".to_string(),
+            symbol: "AUTO".to_string(),
+            decimals: 3,
+            initial_balances: vec![Cw20Coin {
+                address: String::from("addr0001"),
+                amount: Uint128::from(12340000u128),
+            }],
+            mint: None,
+            marketing: None,
+            tax_map: Some(mock_valid_tax_map("admin".to_string())),
+            whale_info: None,
+            max_tax_rate: Some(max_tax_rate),
+            tax_rate_limit: None,
+            approval: None,
+        };
+        let info = mock_info("creator", &[]);
+        instantiate(deps, mock_env(), info, instantiate_msg).unwrap();
+    }
+
+    #[test]
+    fn cannot_instantiate_with_tax_above_max_tax_rate() {
+        let mut deps = mock_dependencies();
+        let instantiate_msg = InstantiateMsg {
+            name: "Auto Gen".to_string(),
+            symbol: "AUTO".to_string(),
+            decimals: 3,
+            initial_balances: vec![],
+            mint: None,
+            marketing: None,
+            tax_map: Some(mock_valid_tax_map("admin".to_string())),
+            whale_info: None,
+            max_tax_rate: Some(Decimal::percent(5)),
+            tax_rate_limit: None,
+            approval: None,
+        };
+        let info = mock_info("creator", &[]);
+        let res = instantiate(deps.as_mut(), mock_env(), info, instantiate_msg);
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn cannot_set_tax_map_above_max_tax_rate() {
+        let mut deps = mock_dependencies();
+        do_instantiate_with_max_tax_rate(deps.as_mut(), Decimal::percent(10));
+
+        let mut tax_map_in = mock_valid_tax_map("admin".to_string());
+        tax_map_in.on_send.src_cond = TaxCondition::Always(TaxAlwaysCondition {
+            tax_rate: Decimal::percent(11),
+        });
+        let info = mock_info("admin", &[]);
+        let msg = ExecuteMsg::SetTaxMap {
+            tax_map: Some(tax_map_in),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, msg);
+        assert!(res.is_err());
+
+        let max: MaxTaxRateResponse =
+            from_json(query(deps.as_ref(), mock_env(), QueryMsg::MaxTaxRate {}).unwrap()).unwrap();
+        assert_eq!(max.max_tax_rate, Some(Decimal::percent(10)));
+    }
+
+    #[test]
+    fn max_tax_rate_can_only_be_lowered() {
+        let mut deps = mock_dependencies();
+        do_instantiate_with_max_tax_rate(deps.as_mut(), Decimal::percent(20));
+
+        // only the tax admin may lower the ceiling
+        let info = mock_info("not_admin", &[]);
+        let msg = ExecuteMsg::LowerMaxTaxRate {
+            max_tax_rate: Decimal::percent(15),
+        };
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        // raising is impossible, even for the admin
+        let info = mock_info("admin", &[]);
+        let msg = ExecuteMsg::LowerMaxTaxRate {
+            max_tax_rate: Decimal::percent(25),
+        };
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert_eq!(err, ContractError::CannotRaiseMaxTaxRate {});
+
+        // the active tax map charges 10% and must stay valid
+        let info = mock_info("admin", &[]);
+        let msg = ExecuteMsg::LowerMaxTaxRate {
+            max_tax_rate: Decimal::percent(5),
+        };
+        assert!(execute(deps.as_mut(), mock_env(), info, msg).is_err());
+
+        let info = mock_info("admin", &[]);
+        let msg = ExecuteMsg::LowerMaxTaxRate {
+            max_tax_rate: Decimal::percent(10),
+        };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(
+            query_max_tax_rate(deps.as_ref()).unwrap().max_tax_rate,
+            Some(Decimal::percent(10))
+        );
+    }
+
+    #[test]
+    fn tax_map_changes_are_rate_limited_per_epoch() {
+        let mut deps = mock_dependencies();
+        let instantiate_msg = InstantiateMsg {
+            name: "Auto Gen".to_string(),
+            symbol: "AUTO".to_string(),
+            decimals: 3,
+            initial_balances: vec![],
+            mint: None,
+            marketing: None,
+            tax_map: Some(mock_valid_tax_map("admin".to_string())),
+            whale_info: None,
+            max_tax_rate: None,
+            tax_rate_limit: Some(TaxRateLimit {
+                max_delta: Decimal::percent(2),
+                epoch: TaxEpoch::Blocks(100),
+            }),
+            approval: None,
+        };
+        let info = mock_info("creator", &[]);
+        instantiate(deps.as_mut(), mock_env(), info, instantiate_msg).unwrap();
+
+        let set_on_send_rate = |rate: u64| {
+            let mut tax_map = mock_valid_tax_map("admin".to_string());
+            tax_map.on_send.src_cond = TaxCondition::Always(TaxAlwaysCondition {
+                tax_rate: Decimal::percent(rate),
+            });
+            ExecuteMsg::SetTaxMap {
+                tax_map: Some(tax_map),
+            }
+        };
+
+        // 10% -> 12% is within the limit
+        let mut env = mock_env();
+        let info = mock_info("admin", &[]);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            set_on_send_rate(12),
+        )
+        .unwrap();
+
+        // successive changes within the same epoch are compared to the epoch start
+        env.block.height += 50;
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            set_on_send_rate(13),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::TaxRateChangeExceedsLimit {
+                action: "on_send".to_string(),
+                side: "src".to_string(),
+                previous: Decimal::percent(10),
+                requested: Decimal::percent(13),
+                max_delta: Decimal::percent(2),
+            }
+        );
+
+        // a new epoch starts from the rates in place
+        env.block.height += 50;
+        execute(deps.as_mut(), env.clone(), info, set_on_send_rate(13)).unwrap();
+        let limit = query_tax_rate_limit(deps.as_ref()).unwrap();
+        assert_eq!(limit.epoch_start_height, Some(env.block.height));
+    }
+
+    #[test]
+    fn tax_map_history_is_versioned_by_height() {
+        let mut deps = mock_dependencies();
+        let addr1 = String::from("addr0001");
+        let amount1 = Uint128::from(12340000u128);
+        let mut env = mock_env();
+        let instantiate_height = env.block.height;
+        do_instantiate_with_tax_on_transfer(deps.as_mut(), &addr1, amount1);
+
+        // change the tax map a few blocks later
+        env.block.height += 10;
+        let info = mock_info("", &[]);
+        let msg = ExecuteMsg::SetTaxMap { tax_map: None };
+        let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        assert!(res
+            .attributes
+            .contains(&cosmwasm_std::attr("tax_map_version", "2")));
+
+        let at = query_tax_map_at(deps.as_ref(), instantiate_height + 9).unwrap();
+        assert_eq!(at.version, 1);
+        assert_eq!(at.height, instantiate_height);
+        assert_eq!(at.tax_map.on_transfer.proceeds, Addr::unchecked("proceeds"));
+
+        let at = query_tax_map_at(deps.as_ref(), instantiate_height + 10).unwrap();
+        assert_eq!(at.version, 2);
+        assert_eq!(at.tax_map, TaxMap::default());
+
+        // nothing was active before instantiation
+        assert!(query_tax_map_at(deps.as_ref(), instantiate_height - 1).is_err());
+
+        let history: TaxMapHistoryResponse = from_json(
+            query(
+                deps.as_ref(),
+                env.clone(),
+                QueryMsg::TaxMapHistory {
+                    start_after: None,
+                    limit: Some(1),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(history.versions.len(), 1);
+        assert_eq!(history.versions[0].version, 1);
+
+        let history: TaxMapHistoryResponse = from_json(
+            query(
+                deps.as_ref(),
+                env,
+                QueryMsg::TaxMapHistory {
+                    start_after: Some(1),
+                    limit: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(history.versions.len(), 1);
+        assert_eq!(history.versions[0].version, 2);
+    }
+
+    #[test]
+    fn transfer() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        let addr1 = String::from("addr0001");
+        let addr2 = String::from("addr0002");
+        let amount1 = Uint128::from(12340000u128);
+        let transfer = Uint128::from(76543u128);
+        let too_much = Uint128::from(12340321u128);
+
+        do_instantiate(deps.as_mut(), &addr1, amount1);
+
+        // Allows transferring 0
+        let info = mock_info(addr1.as_ref(), &[]);
+        let env = mock_env();
+        let msg = ExecuteMsg::Transfer {
+            recipient: addr2.clone(),
+            amount: Uint128::zero(),
+        };
+        execute(deps.as_mut(), env, info, msg).unwrap();
+
+        // cannot send more than we have
+        let info = mock_info(addr1.as_ref(), &[]);
+        let env = mock_env();
+        let msg = ExecuteMsg::Transfer {
+            recipient: addr2.clone(),
+            amount: too_much,
+        };
+        let err = execute(deps.as_mut(), env, info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::Std(StdError::Overflow { .. })));
+
+        // cannot send from empty account
+        let info = mock_info(addr2.as_ref(), &[]);
+        let env = mock_env();
+        let msg = ExecuteMsg::Transfer {
+            recipient: addr1.clone(),
+            amount: transfer,
+        };
+        let err = execute(deps.as_mut(), env, info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::Std(StdError::Overflow { .. })));
+
+        // valid transfer
+        let info = mock_info(addr1.as_ref(), &[]);
+        let env = mock_env();
+        let msg = ExecuteMsg::Transfer {
+            recipient: addr2.clone(),
+            amount: transfer,
+        };
+        let res = execute(deps.as_mut(), env, info, msg).unwrap();
+        assert_eq!(res.messages.len(), 0);
+
+        let remainder = amount1.checked_sub(transfer).unwrap();
+        assert_eq!(get_balance(deps.as_ref(), addr1), remainder);
+        assert_eq!(get_balance(deps.as_ref(), addr2), transfer);
+        assert_eq!(
+            query_token_info(deps.as_ref()).unwrap().total_supply,
+            amount1
+        );
+    }
+
+    #[test]
+    fn transfer_with_tax() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        let addr1 = String::from("addr0001");
+        let addr2 = String::from("addr0002");
+        let amount1 = Uint128::from(12340000u128);
+        let transfer = Uint128::from(76543u128);
+        let expected_remainder = amount1.checked_sub(transfer).unwrap();
+        let expected_tax = Uint128::from(7654u128);
+        let expected_net = Uint128::from(68889u128);
+        let expected_tfer_msg = ExecuteMsg::SettleTax {
+            payer: addr1.clone(),
+            recipient: String::from("proceeds"),
+            amount: expected_tax.clone(),
+        };
+        let expected_proceeds_msg: CosmosMsg<Empty> = CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: String::from("cosmos2contract"),
+            msg: to_json_binary(&expected_tfer_msg).unwrap(),
+            funds: vec![],
+        });
+
+        do_instantiate_with_tax_on_transfer(deps.as_mut(), &addr1, amount1);
+
+        // test valid transfer
+        let info = mock_info(addr1.as_ref(), &[]);
+        let env = mock_env();
+        let msg = ExecuteMsg::Transfer {
+            recipient: addr2.clone(),
+            amount: transfer,
+        };
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        assert_eq!(res.messages.len(), 1); //expecting proceeds message
+        assert_eq!(res.messages[0].clone().msg, expected_proceeds_msg);
+        assert!(res
+            .attributes
+            .contains(&cosmwasm_std::attr("tax_map_version", "1")));
+        assert_eq!(
+            get_balance(deps.as_ref(), addr1.clone()),
+            expected_remainder
+        );
+        assert_eq!(get_balance(deps.as_ref(), addr2.clone()), expected_net);
+        assert_eq!(get_balance(deps.as_ref(), "cosmos2contract"), expected_tax);
+        assert_eq!(
+            query_token_info(deps.as_ref()).unwrap().total_supply,
+            amount1
+        );
+    }
+
+    #[test]
+    fn transfer_with_holding_period_tax() {
+        let mut deps = mock_dependencies();
+        let day = 24 * 60 * 60;
+        let holding_period = TaxCondition::HoldingPeriod(TaxHoldingPeriodCondition {
+            tiers: vec![
+                TaxHoldingTier {
+                    seconds: day,
+                    tax_rate: Decimal::percent(20),
+                },
+                TaxHoldingTier {
+                    seconds: 7 * day,
+                    tax_rate: Decimal::percent(10),
+                },
+            ],
+            tax_rate: Decimal::percent(2),
+        });
+        let tax_map = TaxMap {
+            on_transfer: TaxInfo {
+                src_cond: holding_period,
+                dst_cond: TaxCondition::Always(TaxAlwaysCondition {
+                    tax_rate: Decimal::zero(),
+                }),
+                proceeds: Addr::unchecked("proceeds"),
+                admin: None,
+                discounts: vec![],
+                referral_share: Decimal::zero(),
+                mode: TaxMode::Deduct,
+                native_payment: None,
+                rules: None,
+            },
+            admin: Addr::unchecked("admin"),
+            ..TaxMap::default()
+        };
+        let instantiate_msg = InstantiateMsg {
+            name: "Auto Gen".to_string(),
+            symbol: "AUTO".to_string(),
+            decimals: 3,
+            initial_balances: vec![
+                Cw20Coin {
+                    address: String::from("seller"),
+                    amount: Uint128::new(1000),
+                },
+                Cw20Coin {
+                    address: String::from("airdropper"),
+                    amount: Uint128::new(1000),
+                },
+            ],
+            mint: Some(MinterResponse {
+                minter: String::from("minter"),
+                cap: None,
+            }),
+            marketing: None,
+            tax_map: Some(tax_map),
+            whale_info: None,
+            max_tax_rate: None,
+            tax_rate_limit: None,
+            approval: None,
+        };
+        let mut env = mock_env();
+        let launch = env.block.time;
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            instantiate_msg,
+        )
+        .unwrap();
+
+        // sells to the pair and returns the tax paid
+        fn sell(deps: DepsMut, env: &Env, sender: &str, amount: u128) -> Uint128 {
+            let msg = ExecuteMsg::Transfer {
+                recipient: String::from("pair"),
+                amount: Uint128::new(amount),
+            };
+            let res = execute(deps, env.clone(), mock_info(sender, &[]), msg).unwrap();
+            res.attributes
+                .iter()
+                .find(|x| x.key == "tax")
+                .map(|x| x.value.parse().unwrap())
+                .unwrap_or_default()
+        }
+
+        // flipping right after launch
+        assert_eq!(sell(deps.as_mut(), &env, "seller", 100), Uint128::new(20));
+
+        // partial sells do not reset the holding period
+        env.block.time = launch.plus_seconds(2 * day);
+        assert_eq!(sell(deps.as_mut(), &env, "seller", 100), Uint128::new(10));
+        assert_eq!(sell(deps.as_mut(), &env, "seller", 100), Uint128::new(10));
+        assert_eq!(
+            ACQUIRED_AT
+                .load(&deps.storage, &Addr::unchecked("seller"))
+                .unwrap(),
+            launch
+        );
+
+        // long term holders pay the base rate, even after receiving some dust
+        env.block.time = launch.plus_seconds(8 * day);
+        let msg = ExecuteMsg::Transfer {
+            recipient: String::from("seller"),
+            amount: Uint128::new(10),
+        };
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("airdropper", &[]),
+            msg,
+        )
+        .unwrap();
+        assert_eq!(sell(deps.as_mut(), &env, "seller", 100), Uint128::new(2));
+
+        // minting as much as is held halves the holding period
+        let msg = ExecuteMsg::Mint {
+            recipient: String::from("seller"),
+            amount: Uint128::new(610),
+        };
+        execute(deps.as_mut(), env.clone(), mock_info("minter", &[]), msg).unwrap();
+        assert_eq!(sell(deps.as_mut(), &env, "seller", 100), Uint128::new(10));
+
+        // fresh accounts start their holding period with the first credit
+        let msg = ExecuteMsg::Transfer {
+            recipient: String::from("fresh"),
+            amount: Uint128::new(100),
+        };
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("airdropper", &[]),
+            msg,
+        )
+        .unwrap();
+        assert_eq!(
+            ACQUIRED_AT
+                .load(&deps.storage, &Addr::unchecked("fresh"))
+                .unwrap(),
+            env.block.time
+        );
+        assert_eq!(sell(deps.as_mut(), &env, "fresh", 50), Uint128::new(10));
+    }
+
+    #[test]
+    fn send_with_hook_tax() {
+        let mut deps = mock_dependencies();
+        let tax_map = TaxMap {
+            on_send: TaxInfo {
+                src_cond: TaxCondition::SendHook(TaxSendHookCondition {
+                    swap_rate: Decimal::percent(10),
+                    default_rate: Decimal::percent(2),
+                }),
+                dst_cond: TaxCondition::Always(TaxAlwaysCondition {
+                    tax_rate: Decimal::zero(),
+                }),
+                proceeds: Addr::unchecked("proceeds"),
+                admin: None,
+                discounts: vec![],
+                referral_share: Decimal::zero(),
+                mode: TaxMode::Deduct,
+                native_payment: None,
+                rules: None,
+            },
+            admin: Addr::unchecked("admin"),
+            ..TaxMap::default()
+        };
+        let instantiate_msg = InstantiateMsg {
+            name: "Auto Gen".to_string(),
+            symbol: "AUTO".to_string(),
+            decimals: 3,
+            initial_balances: vec![Cw20Coin {
+                address: String::from("trader"),
+                amount: Uint128::new(10000),
+            }],
+            mint: None,
+            marketing: None,
+            tax_map: Some(tax_map),
+            whale_info: None,
+            max_tax_rate: None,
+            tax_rate_limit: None,
+            approval: None,
+        };
+        let env = mock_env();
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            instantiate_msg,
+        )
+        .unwrap();
+
+        // sends to the pair and returns the tax paid
+        fn send(deps: DepsMut, env: &Env, hook: &str) -> Uint128 {
+            let msg = ExecuteMsg::Send {
+                contract: String::from("pair"),
+                amount: Uint128::new(1000),
+                msg: Binary::from(hook.as_bytes()),
+            };
+            let res = execute(deps, env.clone(), mock_info("trader", &[]), msg).unwrap();
+            res.attributes
+                .iter()
+                .find(|x| x.key == "tax")
+                .map(|x| x.value.parse().unwrap())
+                .unwrap_or_default()
+        }
+
+        assert_eq!(
+            send(deps.as_mut(), &env, r#"{"swap":{"max_spread":"0.01"}}"#),
+            Uint128::new(100)
+        );
+        assert_eq!(
+            send(
+                deps.as_mut(),
+                &env,
+                r#"{"provide_liquidity":{"assets":[]}}"#
+            ),
+            Uint128::zero()
+        );
+        assert_eq!(
+            send(deps.as_mut(), &env, r#"{"withdraw_liquidity":{}}"#),
+            Uint128::zero()
+        );
+        assert_eq!(
+            send(deps.as_mut(), &env, r#"{"some":123}"#),
+            Uint128::new(20)
+        );
+        assert_eq!(
+            get_balance(deps.as_ref(), env.contract.address.clone()),
+            Uint128::new(120)
+        );
+    }
+
+    #[test]
+    fn transfer_with_holder_discounts() {
+        let mut deps = mock_dependencies();
+        let tier = |min_balance, percent| TaxDiscountTier {
+            min_balance: Uint128::new(min_balance),
+            discount: Decimal::percent(percent),
+        };
+        let tax_map = TaxMap {
+            on_transfer: TaxInfo {
+                src_cond: TaxCondition::Always(TaxAlwaysCondition {
+                    tax_rate: Decimal::percent(10),
+                }),
+                dst_cond: TaxCondition::Always(TaxAlwaysCondition {
+                    tax_rate: Decimal::zero(),
+                }),
+                proceeds: Addr::unchecked("proceeds"),
+                admin: None,
+                discounts: vec![tier(1000, 50), tier(5000, 100)],
+                referral_share: Decimal::zero(),
+                mode: TaxMode::Deduct,
+                native_payment: None,
+                rules: None,
+            },
+            admin: Addr::unchecked("admin"),
+            ..TaxMap::default()
+        };
+        let instantiate_msg = InstantiateMsg {
+            name: "Auto Gen".to_string(),
+            symbol: "AUTO".to_string(),
+            decimals: 3,
+            initial_balances: vec![
+                Cw20Coin {
+                    address: String::from("shrimp"),
+                    amount: Uint128::new(500),
+                },
+                Cw20Coin {
+                    address: String::from("dolphin"),
+                    amount: Uint128::new(1000),
+                },
+                Cw20Coin {
+                    address: String::from("whale"),
+                    amount: Uint128::new(5000),
+                },
+            ],
+            mint: None,
+            marketing: None,
+            tax_map: Some(tax_map),
+            whale_info: None,
+            max_tax_rate: None,
+            tax_rate_limit: None,
+            approval: None,
+        };
+        let env = mock_env();
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            instantiate_msg,
+        )
+        .unwrap();
+
+        let query_discounts = |deps: Deps, address: &str| -> TaxDiscountsResponse {
+            let msg = QueryMsg::TaxDiscounts {
+                address: String::from(address),
+            };
+            from_json(query(deps, env.clone(), msg).unwrap()).unwrap()
+        };
+        let res = query_discounts(deps.as_ref(), "dolphin");
+        assert_eq!(res.balance, Uint128::new(1000));
+        assert_eq!(
+            res.discounts,
+            vec![TaxDiscount {
+                action: TaxAction::OnTransfer,
+                discount: Decimal::percent(50),
+            }]
+        );
+
+        // the discount is based on the balance before the transfer
+        let mut received = Uint128::zero();
+        for (sender, tax) in [("shrimp", 10u128), ("dolphin", 5), ("whale", 0)] {
+            let msg = ExecuteMsg::Transfer {
+                recipient: String::from("pair"),
+                amount: Uint128::new(100),
+            };
+            execute(deps.as_mut(), env.clone(), mock_info(sender, &[]), msg).unwrap();
+            received += Uint128::new(100 - tax);
+            assert_eq!(get_balance(deps.as_ref(), "pair"), received);
+        }
+        assert_eq!(
+            get_balance(deps.as_ref(), env.contract.address.clone()),
+            Uint128::new(15)
+        );
+
+        // the whale dropped below the full discount
+        let res = query_discounts(deps.as_ref(), "whale");
+        assert_eq!(res.discounts[0].discount, Decimal::percent(50));
+    }
+
+    #[test]
+    fn transfer_with_tax_rules() {
+        let mut deps = mock_dependencies();
+        let buy_on_a = TaxRule {
+            name: Some(String::from("buy on pair A")),
+            src_cond: TaxCondition::Addresses(TaxAddressCondition {
+                addresses: vec![Addr::unchecked("pair_a")],
+                tax_rate: Decimal::percent(2),
+            }),
+            dst_cond: TaxCondition::Always(TaxAlwaysCondition {
+                tax_rate: Decimal::zero(),
+            }),
+            proceeds: Addr::unchecked("treasury"),
+        };
+        let sell_on_b = TaxRule {
+            name: Some(String::from("sell on pair B")),
+            src_cond: TaxCondition::Always(TaxAlwaysCondition {
+                tax_rate: Decimal::percent(5),
+            }),
+            dst_cond: TaxCondition::Addresses(TaxAddressCondition {
+                addresses: vec![Addr::unchecked("pair_b")],
+                tax_rate: Decimal::zero(),
+            }),
+            proceeds: Addr::unchecked("marketing"),
+        };
+        let tax_map = TaxMap {
+            on_transfer: TaxInfo {
+                rules: Some(vec![buy_on_a, sell_on_b]),
+                ..TaxInfo::default()
+            },
+            admin: Addr::unchecked("admin"),
+            ..TaxMap::default()
+        };
+        let instantiate_msg = InstantiateMsg {
+            name: "Auto Gen".to_string(),
+            symbol: "AUTO".to_string(),
+            decimals: 3,
+            initial_balances: vec![Cw20Coin {
+                address: String::from("pair_a"),
+                amount: Uint128::new(10000),
+            }],
+            mint: None,
+            marketing: None,
+            tax_map: Some(tax_map),
+            whale_info: None,
+            max_tax_rate: None,
+            tax_rate_limit: None,
+            approval: None,
+        };
+        let env = mock_env();
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            instantiate_msg,
+        )
+        .unwrap();
+
+        let simulate = |deps: Deps, sender: &str, recipient: &str| -> SimulateTaxResponse {
+            let msg = QueryMsg::SimulateTax {
+                action: TaxAction::OnTransfer,
+                sender: String::from(sender),
+                recipient: String::from(recipient),
+                amount: Uint128::new(1000),
+                msg: None,
+            };
+            from_json(query(deps, env.clone(), msg).unwrap()).unwrap()
+        };
+        assert_eq!(
+            simulate(deps.as_ref(), "pair_a", "user"),
+            SimulateTaxResponse {
+                net: Uint128::new(980),
+                gross: Uint128::new(1000),
+                tax: Uint128::new(20),
+                proceeds: Some(String::from("treasury")),
+                rule: Some(TaxRuleMatch {
+                    index: 0,
+                    name: Some(String::from("buy on pair A")),
+                }),
+            }
+        );
+        assert_eq!(
+            simulate(deps.as_ref(), "user", "pair_a"),
+            SimulateTaxResponse {
+                net: Uint128::new(1000),
+                gross: Uint128::new(1000),
+                tax: Uint128::zero(),
+                proceeds: None,
+                rule: None,
+            }
+        );
+
+        // buy on pair A, then sell on pair B and pair A
+        let transfer = |deps: DepsMut, sender: &str, recipient: &str, amount: u128| {
+            let msg = ExecuteMsg::Transfer {
+                recipient: String::from(recipient),
+                amount: Uint128::new(amount),
+            };
+            execute(deps, env.clone(), mock_info(sender, &[]), msg).unwrap()
+        };
+        let res = transfer(deps.as_mut(), "pair_a", "user", 1000);
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::reply_always(
+                WasmMsg::Execute {
+                    contract_addr: env.contract.address.to_string(),
+                    msg: to_json_binary(&ExecuteMsg::SettleTax {
+                        payer: String::from("pair_a"),
+                        recipient: String::from("treasury"),
+                        amount: Uint128::new(20),
+                    })
+                    .unwrap(),
+                    funds: vec![],
+                },
+                1,
+            )]
+        );
+        let res = transfer(deps.as_mut(), "user", "pair_b", 500);
+        assert!(res
+            .attributes
+            .contains(&cosmwasm_std::attr("proceeds", "marketing")));
+        assert!(res.attributes.contains(&cosmwasm_std::attr("tax", "25")));
+        let res = transfer(deps.as_mut(), "user", "pair_a", 480);
+        assert!(res.messages.is_empty());
+        assert_eq!(get_balance(deps.as_ref(), "user"), Uint128::zero());
+        assert_eq!(get_balance(deps.as_ref(), "pair_a"), Uint128::new(9480));
+        assert_eq!(get_balance(deps.as_ref(), "pair_b"), Uint128::new(475));
+    }
+
+    #[test]
+    fn transfer_with_tax_on_top() {
         let mut deps = mock_dependencies();
-        let addr1 = String::from("addr0001");
-        let amount1 = Uint128::from(12340000u128);
-        let tax_map_in = mock_valid_tax_map("admin".to_string());
-        let mut expected_tax_map = tax_map_in.clone();
-        expected_tax_map.admin = Addr::unchecked("");
-
+        let tax_map = TaxMap {
+            on_transfer: TaxInfo {
+                src_cond: TaxCondition::Always(TaxAlwaysCondition {
+                    tax_rate: Decimal::percent(10),
+                }),
+                dst_cond: TaxCondition::Always(TaxAlwaysCondition {
+                    tax_rate: Decimal::zero(),
+                }),
+                proceeds: Addr::unchecked("proceeds"),
+                mode: TaxMode::OnTop,
+                ..TaxInfo::default()
+            },
+            admin: Addr::unchecked("admin"),
+            ..TaxMap::default()
+        };
         let instantiate_msg = InstantiateMsg {
             name: "Auto Gen".to_string(),
             symbol: "AUTO".to_string(),
             decimals: 3,
             initial_balances: vec![Cw20Coin {
-                address: addr1.to_string(),
-                amount: amount1,
+                address: String::from("payer"),
+                amount: Uint128::new(1000),
             }],
             mint: None,
             marketing: None,
-            tax_map: Some(tax_map_in),
+            tax_map: Some(tax_map),
             whale_info: None,
+            max_tax_rate: None,
+            tax_rate_limit: None,
+            approval: None,
         };
-        let info = mock_info("creator", &[]);
         let env = mock_env();
-        let res = instantiate(deps.as_mut(), env.clone(), info, instantiate_msg);
-        assert_eq!(res.is_ok(), true);
-
-        let info = mock_info("admin", &[]);
-        let msg = ExecuteMsg::SetTaxAdmin { tax_admin: None };
-        let res = execute(deps.as_mut(), env.clone(), info, msg);
-        assert_eq!(res.is_ok(), true);
-        assert_eq!(TAX_INFO.load(&deps.storage).unwrap(), expected_tax_map);
-    }
-
-    #[test]
-    fn transfer() {
-        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
-        let addr1 = String::from("addr0001");
-        let addr2 = String::from("addr0002");
-        let amount1 = Uint128::from(12340000u128);
-        let transfer = Uint128::from(76543u128);
-        let too_much = Uint128::from(12340321u128);
-
-        do_instantiate(deps.as_mut(), &addr1, amount1);
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            instantiate_msg,
+        )
+        .unwrap();
 
-        // Allows transferring 0
-        let info = mock_info(addr1.as_ref(), &[]);
-        let env = mock_env();
-        let msg = ExecuteMsg::Transfer {
-            recipient: addr2.clone(),
-            amount: Uint128::zero(),
+        let msg = QueryMsg::SimulateTax {
+            action: TaxAction::OnTransfer,
+            sender: String::from("payer"),
+            recipient: String::from("merchant"),
+            amount: Uint128::new(500),
+            msg: None,
         };
-        execute(deps.as_mut(), env, info, msg).unwrap();
-
-        // cannot send more than we have
-        let info = mock_info(addr1.as_ref(), &[]);
-        let env = mock_env();
-        let msg = ExecuteMsg::Transfer {
-            recipient: addr2.clone(),
-            amount: too_much,
+        let res: SimulateTaxResponse =
+            from_json(query(deps.as_ref(), env.clone(), msg).unwrap()).unwrap();
+        assert_eq!(res.net, Uint128::new(500));
+        assert_eq!(res.gross, Uint128::new(550));
+
+        let transfer = |deps: DepsMut, amount: u128| {
+            let msg = ExecuteMsg::Transfer {
+                recipient: String::from("merchant"),
+                amount: Uint128::new(amount),
+            };
+            execute(deps, env.clone(), mock_info("payer", &[]), msg)
         };
-        let err = execute(deps.as_mut(), env, info, msg).unwrap_err();
-        assert!(matches!(err, ContractError::Std(StdError::Overflow { .. })));
 
-        // cannot send from empty account
-        let info = mock_info(addr2.as_ref(), &[]);
-        let env = mock_env();
-        let msg = ExecuteMsg::Transfer {
-            recipient: addr1.clone(),
-            amount: transfer,
-        };
-        let err = execute(deps.as_mut(), env, info, msg).unwrap_err();
-        assert!(matches!(err, ContractError::Std(StdError::Overflow { .. })));
+        // the merchant receives exactly the invoiced amount
+        let res = transfer(deps.as_mut(), 500).unwrap();
+        for (key, value) in [
+            ("amount", "500"),
+            ("net", "500"),
+            ("tax", "50"),
+            ("gross", "550"),
+            ("tax_mode", "on_top"),
+        ] {
+            assert!(res.attributes.contains(&cosmwasm_std::attr(key, value)));
+        }
+        assert_eq!(get_balance(deps.as_ref(), "merchant"), Uint128::new(500));
+        assert_eq!(get_balance(deps.as_ref(), "payer"), Uint128::new(450));
 
-        // valid transfer
-        let info = mock_info(addr1.as_ref(), &[]);
-        let env = mock_env();
-        let msg = ExecuteMsg::Transfer {
-            recipient: addr2.clone(),
-            amount: transfer,
-        };
-        let res = execute(deps.as_mut(), env, info, msg).unwrap();
-        assert_eq!(res.messages.len(), 0);
+        // the balance covers the amount but not the tax on top of it
+        let err = transfer(deps.as_mut(), 450).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InsufficientBalance {
+                required: Uint128::new(495),
+                balance: Uint128::new(450),
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "Insufficient balance: 495 required including the tax on top, but only 450 available"
+        );
 
-        let remainder = amount1.checked_sub(transfer).unwrap();
-        assert_eq!(get_balance(deps.as_ref(), addr1), remainder);
-        assert_eq!(get_balance(deps.as_ref(), addr2), transfer);
+        transfer(deps.as_mut(), 409).unwrap();
+        assert_eq!(get_balance(deps.as_ref(), "payer"), Uint128::new(1));
         assert_eq!(
-            query_token_info(deps.as_ref()).unwrap().total_supply,
-            amount1
+            get_balance(deps.as_ref(), env.contract.address.clone()),
+            Uint128::new(90)
         );
     }
 
     #[test]
-    fn transfer_with_tax() {
-        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
-        let addr1 = String::from("addr0001");
-        let addr2 = String::from("addr0002");
-        let amount1 = Uint128::from(12340000u128);
-        let transfer = Uint128::from(76543u128);
-        let expected_remainder = amount1.checked_sub(transfer).unwrap();
-        let expected_tax = Uint128::from(7654u128);
-        let expected_net = Uint128::from(68889u128);
-        let expected_tfer_msg = ExecuteMsg::Transfer {
-            recipient: String::from("proceeds"),
-            amount: expected_tax.clone(),
+    fn transfer_with_rolling_volume_allowance() {
+        let mut deps = mock_dependencies();
+        let hour = 60 * 60;
+        let tax_map = TaxMap {
+            on_transfer: TaxInfo {
+                src_cond: TaxCondition::Volume(TaxVolumeCondition {
+                    allowance: Uint128::new(1000),
+                    window: 24 * hour,
+                    tax_rate: Decimal::percent(10),
+                }),
+                dst_cond: TaxCondition::Always(TaxAlwaysCondition {
+                    tax_rate: Decimal::zero(),
+                }),
+                proceeds: Addr::unchecked("proceeds"),
+                admin: None,
+                discounts: vec![],
+                referral_share: Decimal::zero(),
+                mode: TaxMode::Deduct,
+                native_payment: None,
+                rules: None,
+            },
+            admin: Addr::unchecked("admin"),
+            ..TaxMap::default()
         };
-        let expected_proceeds_msg: CosmosMsg<Empty> = CosmosMsg::Wasm(WasmMsg::Execute {
-            contract_addr: String::from("cosmos2contract"),
-            msg: to_json_binary(&expected_tfer_msg).unwrap(),
-            funds: vec![],
-        });
+        let instantiate_msg = InstantiateMsg {
+            name: "Auto Gen".to_string(),
+            symbol: "AUTO".to_string(),
+            decimals: 3,
+            initial_balances: vec![Cw20Coin {
+                address: String::from("payer"),
+                amount: Uint128::new(10000),
+            }],
+            mint: None,
+            marketing: None,
+            tax_map: Some(tax_map),
+            whale_info: None,
+            max_tax_rate: None,
+            tax_rate_limit: None,
+            approval: None,
+        };
+        let mut env = mock_env();
+        let start = env.block.time;
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            instantiate_msg,
+        )
+        .unwrap();
 
-        do_instantiate_with_tax_on_transfer(deps.as_mut(), &addr1, amount1);
+        fn remaining(deps: Deps, env: &Env) -> Uint128 {
+            let msg = QueryMsg::TaxFreeVolume {
+                address: String::from("payer"),
+            };
+            let res: TaxFreeVolumeResponse =
+                from_json(query(deps, env.clone(), msg).unwrap()).unwrap();
+            assert_eq!(res.headroom.len(), 1);
+            assert_eq!(res.headroom[0].action, TaxAction::OnTransfer);
+            res.headroom[0].remaining
+        }
 
-        // test valid transfer
-        let info = mock_info(addr1.as_ref(), &[]);
-        let env = mock_env();
-        let msg = ExecuteMsg::Transfer {
-            recipient: addr2.clone(),
-            amount: transfer,
-        };
-        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
-        assert_eq!(res.messages.len(), 1); //expecting proceeds message
-        assert_eq!(res.messages[0].clone().msg, expected_proceeds_msg);
-        assert_eq!(
-            get_balance(deps.as_ref(), addr1.clone()),
-            expected_remainder
-        );
-        assert_eq!(get_balance(deps.as_ref(), addr2.clone()), expected_net);
-        assert_eq!(get_balance(deps.as_ref(), "cosmos2contract"), expected_tax);
-        assert_eq!(
-            query_token_info(deps.as_ref()).unwrap().total_supply,
-            amount1
-        );
+        fn pay(deps: DepsMut, env: &Env, amount: u128) -> Uint128 {
+            let msg = ExecuteMsg::Transfer {
+                recipient: String::from("merchant"),
+                amount: Uint128::new(amount),
+            };
+            let res = execute(deps, env.clone(), mock_info("payer", &[]), msg).unwrap();
+            res.attributes
+                .iter()
+                .find(|x| x.key == "tax")
+                .map(|x| x.value.parse().unwrap())
+                .unwrap_or_default()
+        }
+
+        assert_eq!(remaining(deps.as_ref(), &env), Uint128::new(1000));
+        assert_eq!(pay(deps.as_mut(), &env, 600), Uint128::zero());
+        assert_eq!(remaining(deps.as_ref(), &env), Uint128::new(400));
+
+        // only the 200 tokens exceeding the allowance are taxed
+        env.block.time = start.plus_seconds(12 * hour);
+        assert_eq!(pay(deps.as_mut(), &env, 600), Uint128::new(20));
+        assert_eq!(get_balance(deps.as_ref(), "merchant"), Uint128::new(1180));
+        assert_eq!(remaining(deps.as_ref(), &env), Uint128::zero());
+        assert_eq!(pay(deps.as_mut(), &env, 100), Uint128::new(10));
+
+        // the first payment leaves the window after a day
+        env.block.time = start.plus_seconds(25 * hour);
+        assert_eq!(remaining(deps.as_ref(), &env), Uint128::new(300));
+        env.block.time = start.plus_seconds(37 * hour);
+        assert_eq!(remaining(deps.as_ref(), &env), Uint128::new(1000));
+        assert_eq!(pay(deps.as_mut(), &env, 1000), Uint128::zero());
     }
 
     #[test]
@@ -2385,7 +4652,8 @@ mod tests {
         let expected_remainder = amount1.checked_sub(transfer).unwrap();
         let expected_tax = Uint128::from(7654u128);
         let expected_net = Uint128::from(68889u128);
-        let expected_tfer_msg = ExecuteMsg::Transfer {
+        let expected_tfer_msg = ExecuteMsg::SettleTax {
+            payer: addr1.clone(),
             recipient: String::from("proceeds"),
             amount: expected_tax.clone(),
         };
@@ -2477,6 +4745,7 @@ mod tests {
                 crate::contract::instantiate,
                 crate::contract::query,
             )
+            .with_reply(crate::contract::reply)
             .with_migrate(crate::contract::migrate);
             Box::new(contract)
         }
@@ -2502,6 +4771,9 @@ mod tests {
                         marketing: None,
                         tax_map: None,
                         whale_info: None,
+                        max_tax_rate: None,
+                        tax_rate_limit: None,
+                        approval: None,
                     },
                     &[],
                     "TOKEN",
@@ -2548,6 +4820,9 @@ mod tests {
                         tax_map: None,
                         whale_info: None,
                         new_info: None,
+                        max_tax_rate: None,
+                        tax_rate_limit: None,
+                        approval: None,
                     })
                     .unwrap(),
                 }),
@@ -2609,6 +4884,9 @@ mod tests {
                     tax_map: None,
                     whale_info: None,
                     new_info: None,
+                    max_tax_rate: None,
+                    tax_rate_limit: None,
+                    approval: None,
                 },
             )
             .unwrap();
@@ -2644,6 +4922,11 @@ mod tests {
                 }
                 Err(_) => panic!("Expected Tax map to be available!"),
             }
+
+            // and starts the tax map history
+            let at = query_tax_map_at(deps.as_ref(), mock_env().block.height).unwrap();
+            assert_eq!(at.version, 1);
+            assert_eq!(at.tax_map, TaxMap::default());
         }
 
         #[test]
@@ -2663,16 +4946,34 @@ mod tests {
                     src_cond: TaxCondition::Never(TaxNeverCondition {}),
                     dst_cond: TaxCondition::Never(TaxNeverCondition {}),
                     proceeds: Addr::unchecked("proceeds1"),
+                    admin: None,
+                    discounts: vec![],
+                    referral_share: Decimal::zero(),
+                    mode: TaxMode::Deduct,
+                    native_payment: None,
+                    rules: None,
                 },
                 on_transfer_from: TaxInfo {
                     src_cond: TaxCondition::Never(TaxNeverCondition {}),
                     dst_cond: TaxCondition::Never(TaxNeverCondition {}),
                     proceeds: Addr::unchecked("proceeds2"),
+                    admin: None,
+                    discounts: vec![],
+                    referral_share: Decimal::zero(),
+                    mode: TaxMode::Deduct,
+                    native_payment: None,
+                    rules: None,
                 },
                 on_send: TaxInfo {
                     src_cond: TaxCondition::Never(TaxNeverCondition {}),
                     dst_cond: TaxCondition::Never(TaxNeverCondition {}),
                     proceeds: Addr::unchecked("proceeds3"),
+                    admin: None,
+                    discounts: vec![],
+                    referral_share: Decimal::zero(),
+                    mode: TaxMode::Deduct,
+                    native_payment: None,
+                    rules: None,
                 },
                 on_send_from: TaxInfo {
                     src_cond: TaxCondition::Always(TaxAlwaysCondition {
@@ -2680,6 +4981,12 @@ mod tests {
                     }),
                     dst_cond: TaxCondition::Never(TaxNeverCondition {}),
                     proceeds: Addr::unchecked("proceeds4"),
+                    admin: None,
+                    discounts: vec![],
+                    referral_share: Decimal::zero(),
+                    mode: TaxMode::Deduct,
+                    native_payment: None,
+                    rules: None,
                 },
             };
 
@@ -2691,6 +4998,9 @@ mod tests {
                     tax_map: Some(tax.clone()),
                     whale_info: None,
                     new_info: None,
+                    max_tax_rate: None,
+                    tax_rate_limit: None,
+                    approval: None,
                 },
             )
             .unwrap();
@@ -2765,6 +5075,9 @@ mod tests {
                     tax_map: None,
                     whale_info: None,
                     new_info: Some(new_info),
+                    max_tax_rate: None,
+                    tax_rate_limit: None,
+                    approval: None,
                 },
             )
             .unwrap();
@@ -2801,6 +5114,192 @@ mod tests {
                 Err(_) => panic!("Expected Token Info to be available!"),
             }
         }
+        #[test]
+        fn test_migrate_sets_max_tax_rate_once() {
+            let mut deps = mock_dependencies_with_terraport_balances(vec![(
+                Addr::unchecked("addr1"),
+                Uint128::new(1234),
+                123,
+            )]);
+
+            crate::contract::migrate(
+                deps.as_mut(),
+                mock_env(),
+                MigrateMsg {
+                    tax_map: None,
+                    whale_info: None,
+                    new_info: None,
+                    max_tax_rate: Some(Decimal::percent(10)),
+                    tax_rate_limit: None,
+                    approval: None,
+                },
+            )
+            .unwrap();
+            assert_eq!(
+                MAX_TAX_RATE.load(deps.as_ref().storage).unwrap(),
+                Decimal::percent(10)
+            );
+
+            // a later migration cannot raise the ceiling again
+            let err = crate::contract::migrate(
+                deps.as_mut(),
+                mock_env(),
+                MigrateMsg {
+                    tax_map: None,
+                    whale_info: None,
+                    new_info: None,
+                    max_tax_rate: Some(Decimal::percent(20)),
+                    tax_rate_limit: None,
+                    approval: None,
+                },
+            )
+            .unwrap_err();
+            assert_eq!(err, ContractError::CannotRaiseMaxTaxRate {});
+        }
+
+        #[test]
+        fn test_migrate_validates_and_updates_config() {
+            let mut deps = mock_dependencies_with_terraport_balances(vec![(
+                Addr::unchecked("addr1"),
+                Uint128::new(1234),
+                123,
+            )]);
+            let empty_msg = || MigrateMsg {
+                tax_map: None,
+                whale_info: None,
+                new_info: None,
+                max_tax_rate: None,
+                tax_rate_limit: None,
+                approval: None,
+            };
+            crate::contract::migrate(deps.as_mut(), mock_env(), empty_msg()).unwrap();
+
+            // the supplied config is validated like on instantiate
+            let new_info = MigrateTokenInfo {
+                name: "New Token".to_string(),
+                symbol: "N3W".to_string(),
+                mint: None,
+            };
+            let err = crate::contract::migrate(
+                deps.as_mut(),
+                mock_env(),
+                MigrateMsg {
+                    new_info: Some(new_info),
+                    ..empty_msg()
+                },
+            )
+            .unwrap_err();
+            assert_eq!(
+                err,
+                ContractError::Std(StdError::generic_err(
+                    "Ticker symbol is not in expected format [a-zA-Z\\-]{3,12}"
+                ))
+            );
+
+            let new_info = MigrateTokenInfo {
+                name: "New Token".to_string(),
+                symbol: "NEW".to_string(),
+                mint: Some(MinterData {
+                    minter: Addr::unchecked("Minter"),
+                    cap: None,
+                }),
+            };
+            let msg = MigrateMsg {
+                new_info: Some(new_info),
+                ..empty_msg()
+            };
+            assert!(crate::contract::migrate(deps.as_mut(), mock_env(), msg).is_err());
+
+            let whale_info = WhaleInfo {
+                threshold: Decimal::percent(150),
+                whitelist: vec![],
+                admin: Addr::unchecked("admin"),
+            };
+            let err = crate::contract::migrate(
+                deps.as_mut(),
+                mock_env(),
+                MigrateMsg {
+                    whale_info: Some(whale_info),
+                    ..empty_msg()
+                },
+            )
+            .unwrap_err();
+            assert_eq!(
+                err,
+                ContractError::InvalidWhaleThreshold {
+                    threshold: Decimal::percent(150),
+                }
+            );
+
+            // so are the addresses of the whale info
+            let invalid_whale_infos = [
+                WhaleInfo {
+                    threshold: Decimal::percent(10),
+                    whitelist: vec![],
+                    admin: Addr::unchecked("Admin"),
+                },
+                WhaleInfo {
+                    threshold: Decimal::percent(10),
+                    whitelist: vec![Addr::unchecked("pair"), Addr::unchecked("Pool")],
+                    admin: Addr::unchecked("admin"),
+                },
+            ];
+            for whale_info in invalid_whale_infos {
+                let msg = MigrateMsg {
+                    whale_info: Some(whale_info),
+                    ..empty_msg()
+                };
+                assert!(crate::contract::migrate(deps.as_mut(), mock_env(), msg).is_err());
+            }
+
+            let mut tax_map = TaxMap {
+                admin: Addr::unchecked("admin"),
+                ..TaxMap::default()
+            };
+            tax_map.on_transfer.src_cond = TaxCondition::Always(TaxAlwaysCondition {
+                tax_rate: Decimal::percent(5),
+            });
+            let err = crate::contract::migrate(
+                deps.as_mut(),
+                mock_env(),
+                MigrateMsg {
+                    tax_map: Some(tax_map.clone()),
+                    ..empty_msg()
+                },
+            )
+            .unwrap_err();
+            assert_eq!(
+                err,
+                ContractError::InvalidTaxMap {
+                    errors: vec![ContractError::MissingProceeds {
+                        action: "on_transfer".to_string(),
+                    }],
+                }
+            );
+
+            // valid config replaces the existing one, even after the tax map exists
+            tax_map.on_transfer.proceeds = Addr::unchecked("proceeds");
+            let whale_info = WhaleInfo {
+                threshold: Decimal::percent(5),
+                whitelist: vec![],
+                admin: Addr::unchecked("admin"),
+            };
+            crate::contract::migrate(
+                deps.as_mut(),
+                mock_env(),
+                MigrateMsg {
+                    tax_map: Some(tax_map.clone()),
+                    whale_info: Some(whale_info.clone()),
+                    ..empty_msg()
+                },
+            )
+            .unwrap();
+            assert_eq!(TAX_INFO.load(deps.as_ref().storage).unwrap(), tax_map);
+            assert_eq!(
+                ANTI_WHALE_INFO.load(deps.as_ref().storage).unwrap(),
+                whale_info
+            );
+        }
     }
 
     mod marketing {
@@ -2823,6 +5322,9 @@ mod tests {
                 }),
                 tax_map: None,
                 whale_info: None,
+                max_tax_rate: None,
+                tax_rate_limit: None,
+                approval: None,
             };
 
             let info = mock_info("creator", &[]);
@@ -2878,6 +5380,9 @@ mod tests {
                 }),
                 tax_map: None,
                 whale_info: None,
+                max_tax_rate: None,
+                tax_rate_limit: None,
+                approval: None,
             };
 
             let info = mock_info("creator", &[]);
@@ -2932,6 +5437,9 @@ mod tests {
                 }),
                 tax_map: None,
                 whale_info: None,
+                max_tax_rate: None,
+                tax_rate_limit: None,
+                approval: None,
             };
 
             let info = mock_info("creator", &[]);
@@ -2986,6 +5494,9 @@ mod tests {
                 }),
                 tax_map: None,
                 whale_info: None,
+                max_tax_rate: None,
+                tax_rate_limit: None,
+                approval: None,
             };
 
             let info = mock_info("creator", &[]);
@@ -3040,6 +5551,9 @@ mod tests {
                 }),
                 tax_map: None,
                 whale_info: None,
+                max_tax_rate: None,
+                tax_rate_limit: None,
+                approval: None,
             };
 
             let info = mock_info("creator", &[]);
@@ -3094,6 +5608,9 @@ mod tests {
                 }),
                 tax_map: None,
                 whale_info: None,
+                max_tax_rate: None,
+                tax_rate_limit: None,
+                approval: None,
             };
 
             let info = mock_info("creator", &[]);
@@ -3148,6 +5665,9 @@ mod tests {
                 }),
                 tax_map: None,
                 whale_info: None,
+                max_tax_rate: None,
+                tax_rate_limit: None,
+                approval: None,
             };
 
             let info = mock_info("creator", &[]);
@@ -3205,6 +5725,9 @@ mod tests {
                 }),
                 tax_map: None,
                 whale_info: None,
+                max_tax_rate: None,
+                tax_rate_limit: None,
+                approval: None,
             };
 
             let info = mock_info("creator", &[]);
@@ -3259,6 +5782,9 @@ mod tests {
                 }),
                 tax_map: None,
                 whale_info: None,
+                max_tax_rate: None,
+                tax_rate_limit: None,
+                approval: None,
             };
 
             let info = mock_info("creator", &[]);
@@ -3309,6 +5835,9 @@ mod tests {
                 }),
                 tax_map: None,
                 whale_info: None,
+                max_tax_rate: None,
+                tax_rate_limit: None,
+                approval: None,
             };
 
             let info = mock_info("creator", &[]);
@@ -3361,6 +5890,9 @@ mod tests {
                 }),
                 tax_map: None,
                 whale_info: None,
+                max_tax_rate: None,
+                tax_rate_limit: None,
+                approval: None,
             };
 
             let info = mock_info("creator", &[]);
@@ -3414,6 +5946,9 @@ mod tests {
                 }),
                 tax_map: None,
                 whale_info: None,
+                max_tax_rate: None,
+                tax_rate_limit: None,
+                approval: None,
             };
 
             let info = mock_info("creator", &[]);
@@ -3465,6 +6000,9 @@ mod tests {
                 }),
                 tax_map: None,
                 whale_info: None,
+                max_tax_rate: None,
+                tax_rate_limit: None,
+                approval: None,
             };
 
             let info = mock_info("creator", &[]);
@@ -3523,6 +6061,9 @@ mod tests {
                 }),
                 tax_map: None,
                 whale_info: None,
+                max_tax_rate: None,
+                tax_rate_limit: None,
+                approval: None,
             };
 
             let info = mock_info("creator", &[]);
@@ -3574,6 +6115,9 @@ mod tests {
                 }),
                 tax_map: None,
                 whale_info: None,
+                max_tax_rate: None,
+                tax_rate_limit: None,
+                approval: None,
             };
 
             let info = mock_info("creator", &[]);
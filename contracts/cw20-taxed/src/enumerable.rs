@@ -4,7 +4,12 @@ use cw20::{
     SpenderAllowanceInfo,
 };
 
-use crate::state::{ALLOWANCES, ALLOWANCES_SPENDER, BALANCES};
+use crate::msg::{
+    AllEscrowedTaxResponse, EscrowedTaxInfo, ProposalsResponse, TaxMapHistoryResponse,
+};
+use crate::state::{
+    ALLOWANCES, ALLOWANCES_SPENDER, BALANCES, ESCROWED_TAX, PROPOSALS, TAX_MAP_HISTORY,
+};
 use cw_storage_plus::Bound;
 
 // settings for pagination
@@ -78,6 +83,62 @@ pub fn query_all_accounts(
     Ok(AllAccountsResponse { accounts })
 }
 
+pub fn query_all_escrowed_tax(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<AllEscrowedTaxResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(|s| Bound::ExclusiveRaw(s.into()));
+
+    let escrowed = ESCROWED_TAX
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            item.map(|(recipient, amount)| EscrowedTaxInfo {
+                recipient: recipient.into(),
+                amount,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+
+    Ok(AllEscrowedTaxResponse { escrowed })
+}
+
+pub fn query_tax_map_history(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<TaxMapHistoryResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let versions = TAX_MAP_HISTORY
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, version)| version))
+        .collect::<StdResult<_>>()?;
+
+    Ok(TaxMapHistoryResponse { versions })
+}
+
+pub fn query_proposals(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<ProposalsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let proposals = PROPOSALS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, proposal)| proposal))
+        .collect::<StdResult<_>>()?;
+
+    Ok(ProposalsResponse { proposals })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -103,6 +164,9 @@ mod tests {
             marketing: None,
             tax_map: None,
             whale_info: None,
+            max_tax_rate: None,
+            tax_rate_limit: None,
+            approval: None,
         };
         let info = mock_info("creator", &[]);
         let env = mock_env();
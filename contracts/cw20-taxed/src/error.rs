@@ -1,4 +1,4 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{Decimal, StdError, Uint128};
 use thiserror::Error;
 
 #[derive(Error, Debug, PartialEq)]
@@ -40,4 +40,128 @@ pub enum ContractError {
 
     #[error("Duplicate initial balance addresses")]
     DuplicateInitialBalanceAddresses {},
+
+    #[error("Admin cannot be empty, renounce the role explicitly instead")]
+    EmptyAdmin {},
+
+    #[error("No admin has been proposed")]
+    NoPendingAdmin {},
+
+    #[error("Admin can only be handed over by proposing a new admin that accepts, or renounced")]
+    AdminChangeNotAllowed {},
+
+    #[error("Admin proposal is expired")]
+    AdminProposalExpired {},
+
+    #[error("Tax map patch does not change anything")]
+    EmptyTaxMapPatch {},
+
+    #[error("Maximum tax rate can only be lowered")]
+    CannotRaiseMaxTaxRate {},
+
+    #[error("Tax rate change of {action} ({side}) from {previous} to {requested} exceeds the allowed delta of {max_delta} per epoch")]
+    TaxRateChangeExceedsLimit {
+        action: String,
+        side: String,
+        previous: Decimal,
+        requested: Decimal,
+        max_delta: Decimal,
+    },
+
+    #[error("Invalid tax map: {}", join_errors(.errors))]
+    InvalidTaxMap { errors: Vec<ContractError> },
+
+    #[error("Tax rate {rate} of {action} ({side}) must not exceed 1")]
+    InvalidTaxRate {
+        action: String,
+        side: String,
+        rate: Decimal,
+    },
+
+    #[error("Tax rate {rate} of {action} ({side}) exceeds the maximum tax rate {max}")]
+    TaxRateExceedsMax {
+        action: String,
+        side: String,
+        rate: Decimal,
+        max: Decimal,
+    },
+
+    #[error("Invalid tax condition of {action} ({side})")]
+    InvalidTaxCondition { action: String, side: String },
+
+    #[error("Proceeds wallet of {action} cannot be empty")]
+    MissingProceeds { action: String },
+
+    #[error("Invalid address {address} in {field} of the tax map")]
+    InvalidTaxAddress { field: String, address: String },
+
+    #[error("Invalid tax info of {action}: {reason}")]
+    InvalidTaxInfo { action: String, reason: String },
+
+    #[error("Address {address} is holding too many tokens. Max allowed: {max}. Tx results in: {resulting}")]
+    WhaleLimitExceeded {
+        address: String,
+        max: Uint128,
+        resulting: Uint128,
+    },
+
+    #[error("Whale threshold {threshold} must be between 0 and 1")]
+    InvalidWhaleThreshold { threshold: Decimal },
+
+    #[error("This change has to be proposed to and approved by the signers")]
+    ApprovalRequired {},
+
+    #[error("No signers are configured")]
+    NoApprovalConfig {},
+
+    #[error("Proposal is expired")]
+    ProposalExpired {},
+
+    #[error("Proposal has already been executed")]
+    ProposalExecuted {},
+
+    #[error("Proposal has already been approved by this signer")]
+    AlreadyApproved {},
+
+    #[error("Referrer has already been set")]
+    ReferrerAlreadySet {},
+
+    #[error("Cannot refer yourself or a wallet that refers you")]
+    InvalidReferrer {},
+
+    #[error("Insufficient balance: {required} required including the tax on top, but only {balance} available")]
+    InsufficientBalance { required: Uint128, balance: Uint128 },
+
+    #[error("Tax can only be paid with a single coin of {denom}")]
+    InvalidNativeTaxFunds { denom: String },
+
+    #[error("Tax of this action cannot be paid with native coins")]
+    NativeTaxNotAccepted {},
+
+    #[error(
+        "Insufficient native tax: {required}{denom} required, but only {paid}{denom} attached"
+    )]
+    InsufficientNativeTax {
+        required: Uint128,
+        paid: Uint128,
+        denom: String,
+    },
+
+    #[error("Tax settlement of {amount} to {recipient} failed: {reason}")]
+    TaxSettlementFailed {
+        recipient: String,
+        amount: Uint128,
+        reason: String,
+    },
+
+    #[error("No tax is parked in escrow for this recipient")]
+    NoEscrowedTax {},
+}
+
+fn join_errors(errors: &[ContractError]) -> String {
+    errors
+        .iter()
+        .map(|err| err.to_string())
+        .collect::<Vec<_>>()
+        .join("; ")
 }
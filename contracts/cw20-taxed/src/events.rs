@@ -0,0 +1,475 @@
+//! Custom events with a stable attribute schema for indexers. CosmWasm
+//! prefixes their type with `wasm-`, so e.g. `cw20_transfer` arrives as
+//! `wasm-cw20_transfer`. Attributes are only ever added to these events, never
+//! renamed or removed. Optional attributes are left out rather than emitted
+//! empty.
+
+use cosmwasm_std::{Addr, Coin, Event, Uint128};
+
+use crate::referral::ReferralPayout;
+use crate::tax::{TaxAction, TaxMode};
+use crate::whale::WhaleInfo;
+
+/// `wasm-cw20_transfer`, emitted by every transfer, send, transfer_from and
+/// send_from whether it is taxed or not.
+///
+/// | attribute | value |
+/// |-----------|-------|
+/// | `action`  | `transfer`, `send`, `transfer_from` or `send_from` |
+/// | `from`    | owner of the tokens |
+/// | `to`      | recipient |
+/// | `by`      | spender, only for `transfer_from` and `send_from` |
+/// | `amount`  | requested amount |
+/// | `net`     | amount the recipient received |
+/// | `gross`   | amount taken from `from` |
+pub struct Cw20TransferEvent<'a> {
+    pub action: &'a str,
+    pub from: &'a Addr,
+    pub to: &'a Addr,
+    pub by: Option<&'a Addr>,
+    pub amount: Uint128,
+    pub net: Uint128,
+    pub gross: Uint128,
+}
+
+impl From<Cw20TransferEvent<'_>> for Event {
+    fn from(x: Cw20TransferEvent) -> Self {
+        let mut event = Event::new("cw20_transfer")
+            .add_attribute("action", x.action)
+            .add_attribute("from", x.from)
+            .add_attribute("to", x.to);
+        if let Some(by) = x.by {
+            event = event.add_attribute("by", by);
+        }
+        event
+            .add_attribute("amount", x.amount)
+            .add_attribute("net", x.net)
+            .add_attribute("gross", x.gross)
+    }
+}
+
+/// `wasm-cw20_tax`, emitted next to `wasm-cw20_transfer` if a tax was paid,
+/// in tokens or in native coins.
+///
+/// | attribute         | value |
+/// |-------------------|-------|
+/// | `tax_action`      | `on_transfer`, `on_send`, `on_transfer_from` or `on_send_from` |
+/// | `payer`           | wallet that paid the tax |
+/// | `proceeds`        | wallet the tax went to |
+/// | `tax`             | token tax, including the referral share |
+/// | `tax_mode`        | `deduct` or `on_top` |
+/// | `tax_map_version` | version of the tax map that was applied |
+/// | `referrer`        | referrer of the payer, only if paid a share |
+/// | `referral`        | token share of the referrer, only if paid a share |
+/// | `native_tax`      | coin the tax was paid in instead, e.g. `50uluna` |
+pub struct Cw20TaxEvent<'a> {
+    pub tax_action: &'a TaxAction,
+    pub payer: &'a Addr,
+    pub proceeds: &'a str,
+    pub tax: Uint128,
+    pub tax_mode: &'a TaxMode,
+    pub tax_map_version: u64,
+    pub referral: Option<&'a ReferralPayout>,
+    pub native_tax: Option<&'a Coin>,
+}
+
+impl From<Cw20TaxEvent<'_>> for Event {
+    fn from(x: Cw20TaxEvent) -> Self {
+        let mut event = Event::new("cw20_tax")
+            .add_attribute("tax_action", x.tax_action.as_str())
+            .add_attribute("payer", x.payer)
+            .add_attribute("proceeds", x.proceeds)
+            .add_attribute("tax", x.tax)
+            .add_attribute("tax_mode", x.tax_mode.to_string())
+            .add_attribute("tax_map_version", x.tax_map_version.to_string());
+        if let Some(referral) = x.referral {
+            event = event
+                .add_attribute("referrer", &referral.referrer)
+                .add_attribute("referral", referral.amount);
+        }
+        if let Some(native_tax) = x.native_tax {
+            event = event.add_attribute("native_tax", native_tax.to_string());
+        }
+        event
+    }
+}
+
+/// `wasm-tax_map_updated`, emitted whenever a new tax map version is stored by
+/// `set_tax_map`, `patch_tax_map` or `migrate`.
+///
+/// | attribute         | value |
+/// |-------------------|-------|
+/// | `action`          | message that changed the tax map |
+/// | `admin`           | tax map admin afterwards, empty if renounced |
+/// | `tax_map_version` | new version |
+pub struct TaxMapUpdatedEvent<'a> {
+    pub action: &'a str,
+    pub admin: &'a Addr,
+    pub tax_map_version: u64,
+}
+
+impl From<TaxMapUpdatedEvent<'_>> for Event {
+    fn from(x: TaxMapUpdatedEvent) -> Self {
+        Event::new("tax_map_updated")
+            .add_attribute("action", x.action)
+            .add_attribute("admin", x.admin)
+            .add_attribute("tax_map_version", x.tax_map_version.to_string())
+    }
+}
+
+/// `wasm-whale_info_updated`, emitted by `set_whale_info` and `migrate`.
+///
+/// | attribute   | value |
+/// |-------------|-------|
+/// | `admin`     | whale admin |
+/// | `threshold` | share of the total supply a wallet may hold |
+/// | `whitelist` | comma separated wallets exempt from the threshold |
+pub struct WhaleInfoUpdatedEvent<'a> {
+    pub whale_info: &'a WhaleInfo,
+}
+
+impl From<WhaleInfoUpdatedEvent<'_>> for Event {
+    fn from(x: WhaleInfoUpdatedEvent) -> Self {
+        let whitelist = x
+            .whale_info
+            .whitelist
+            .iter()
+            .map(|addr| addr.as_str())
+            .collect::<Vec<_>>()
+            .join(",");
+        Event::new("whale_info_updated")
+            .add_attribute("admin", &x.whale_info.admin)
+            .add_attribute("threshold", x.whale_info.threshold.to_string())
+            .add_attribute("whitelist", whitelist)
+    }
+}
+
+/// `wasm-admin_changed`, emitted when an admin role is handed over or
+/// renounced.
+///
+/// | attribute  | value |
+/// |------------|-------|
+/// | `role`     | `tax` or `whale` |
+/// | `previous` | previous admin |
+/// | `new`      | new admin, empty if renounced |
+pub struct AdminChangedEvent<'a> {
+    pub role: &'a str,
+    pub previous: &'a Addr,
+    pub new: &'a Addr,
+}
+
+impl From<AdminChangedEvent<'_>> for Event {
+    fn from(x: AdminChangedEvent) -> Self {
+        Event::new("admin_changed")
+            .add_attribute("role", x.role)
+            .add_attribute("previous", x.previous)
+            .add_attribute("new", x.new)
+    }
+}
+
+/// `wasm-tax_parked`, emitted when a tax share could not be settled and was
+/// parked in escrow of the token contract instead.
+///
+/// | attribute   | value |
+/// |-------------|-------|
+/// | `payer`     | wallet that paid the tax, the token contract for flushes |
+/// | `recipient` | wallet the share is parked for |
+/// | `amount`    | parked amount |
+/// | `reason`    | error of the failed settlement |
+pub struct TaxParkedEvent<'a> {
+    pub payer: &'a Addr,
+    pub recipient: &'a Addr,
+    pub amount: Uint128,
+    pub reason: &'a str,
+}
+
+impl From<TaxParkedEvent<'_>> for Event {
+    fn from(x: TaxParkedEvent) -> Self {
+        Event::new("tax_parked")
+            .add_attribute("payer", x.payer)
+            .add_attribute("recipient", x.recipient)
+            .add_attribute("amount", x.amount)
+            .add_attribute("reason", x.reason)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info, MockApi, MockQuerier};
+    use cosmwasm_std::{Binary, Decimal, MemoryStorage, OwnedDeps};
+    use cw20::Cw20Coin;
+
+    use super::*;
+    use crate::contract::{execute, instantiate, migrate};
+    use crate::msg::{Cw20TaxedExecuteMsg as ExecuteMsg, InstantiateMsg, MigrateMsg};
+    use crate::tax::{TaxAlwaysCondition, TaxCondition, TaxInfo, TaxMap};
+
+    type Deps = OwnedDeps<MemoryStorage, MockApi, MockQuerier>;
+
+    fn taxed(tax_rate: u64) -> TaxInfo {
+        TaxInfo {
+            src_cond: TaxCondition::Always(TaxAlwaysCondition {
+                tax_rate: Decimal::percent(tax_rate),
+            }),
+            dst_cond: TaxCondition::Always(TaxAlwaysCondition {
+                tax_rate: Decimal::zero(),
+            }),
+            proceeds: Addr::unchecked("proceeds"),
+            ..TaxInfo::default()
+        }
+    }
+
+    fn tax_map(admin: &str) -> TaxMap {
+        TaxMap {
+            on_transfer: taxed(10),
+            on_transfer_from: taxed(10),
+            on_send: taxed(10),
+            on_send_from: taxed(10),
+            admin: Addr::unchecked(admin),
+        }
+    }
+
+    fn setup() -> Deps {
+        let mut deps = mock_dependencies();
+        let msg = InstantiateMsg {
+            name: "Token".to_string(),
+            symbol: "TOKEN".to_string(),
+            decimals: 6,
+            initial_balances: vec![Cw20Coin {
+                address: "owner".to_string(),
+                amount: Uint128::new(10000),
+            }],
+            mint: None,
+            marketing: None,
+            tax_map: Some(tax_map("admin")),
+            whale_info: None,
+            max_tax_rate: None,
+            tax_rate_limit: None,
+            approval: None,
+        };
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        deps
+    }
+
+    fn exec(deps: &mut Deps, sender: &str, msg: ExecuteMsg) -> Vec<Event> {
+        execute(deps.as_mut(), mock_env(), mock_info(sender, &[]), msg)
+            .unwrap()
+            .events
+    }
+
+    fn transfer_event(action: &str, from: &str, to: &str, amount: u128, net: u128) -> Event {
+        Event::new("cw20_transfer")
+            .add_attribute("action", action)
+            .add_attribute("from", from)
+            .add_attribute("to", to)
+            .add_attribute("amount", amount.to_string())
+            .add_attribute("net", net.to_string())
+            .add_attribute("gross", amount.to_string())
+    }
+
+    fn tax_event(tax_action: &str, tax: u128) -> Event {
+        Event::new("cw20_tax")
+            .add_attribute("tax_action", tax_action)
+            .add_attribute("payer", "owner")
+            .add_attribute("proceeds", "proceeds")
+            .add_attribute("tax", tax.to_string())
+            .add_attribute("tax_mode", "deduct")
+            .add_attribute("tax_map_version", "1")
+    }
+
+    fn admin_changed_event(role: &str, previous: &str, new: &str) -> Event {
+        Event::new("admin_changed")
+            .add_attribute("role", role)
+            .add_attribute("previous", previous)
+            .add_attribute("new", new)
+    }
+
+    #[test]
+    fn transfer_and_send_events() {
+        let mut deps = setup();
+
+        let msg = ExecuteMsg::Transfer {
+            recipient: "rcpt".to_string(),
+            amount: Uint128::new(1000),
+        };
+        assert_eq!(
+            exec(&mut deps, "owner", msg),
+            vec![
+                transfer_event("transfer", "owner", "rcpt", 1000, 900),
+                tax_event("on_transfer", 100),
+            ]
+        );
+
+        // no tax, no tax event
+        let msg = ExecuteMsg::Transfer {
+            recipient: "rcpt".to_string(),
+            amount: Uint128::new(5),
+        };
+        assert_eq!(
+            exec(&mut deps, "owner", msg),
+            vec![transfer_event("transfer", "owner", "rcpt", 5, 5)]
+        );
+
+        let msg = ExecuteMsg::Send {
+            contract: "contract".to_string(),
+            amount: Uint128::new(1000),
+            msg: Binary::default(),
+        };
+        assert_eq!(
+            exec(&mut deps, "owner", msg),
+            vec![
+                transfer_event("send", "owner", "contract", 1000, 900),
+                tax_event("on_send", 100),
+            ]
+        );
+    }
+
+    #[test]
+    fn transfer_from_and_send_from_events() {
+        let mut deps = setup();
+        let msg = ExecuteMsg::IncreaseAllowance {
+            spender: "spender".to_string(),
+            amount: Uint128::new(10000),
+            expires: None,
+        };
+        exec(&mut deps, "owner", msg);
+
+        let msg = ExecuteMsg::TransferFrom {
+            owner: "owner".to_string(),
+            recipient: "rcpt".to_string(),
+            amount: Uint128::new(1000),
+        };
+        assert_eq!(
+            exec(&mut deps, "spender", msg),
+            vec![
+                Event::new("cw20_transfer")
+                    .add_attribute("action", "transfer_from")
+                    .add_attribute("from", "owner")
+                    .add_attribute("to", "rcpt")
+                    .add_attribute("by", "spender")
+                    .add_attribute("amount", "1000")
+                    .add_attribute("net", "900")
+                    .add_attribute("gross", "1000"),
+                tax_event("on_transfer_from", 100),
+            ]
+        );
+
+        let msg = ExecuteMsg::SendFrom {
+            owner: "owner".to_string(),
+            contract: "contract".to_string(),
+            amount: Uint128::new(1000),
+            msg: Binary::default(),
+        };
+        assert_eq!(
+            exec(&mut deps, "spender", msg),
+            vec![
+                Event::new("cw20_transfer")
+                    .add_attribute("action", "send_from")
+                    .add_attribute("from", "owner")
+                    .add_attribute("to", "contract")
+                    .add_attribute("by", "spender")
+                    .add_attribute("amount", "1000")
+                    .add_attribute("net", "900")
+                    .add_attribute("gross", "1000"),
+                tax_event("on_send_from", 100),
+            ]
+        );
+    }
+
+    #[test]
+    fn tax_map_and_tax_admin_events() {
+        let mut deps = setup();
+
+        let msg = ExecuteMsg::SetTaxMap {
+            tax_map: Some(tax_map("admin")),
+        };
+        assert_eq!(
+            exec(&mut deps, "admin", msg),
+            vec![Event::new("tax_map_updated")
+                .add_attribute("action", "set_tax_map")
+                .add_attribute("admin", "admin")
+                .add_attribute("tax_map_version", "2")]
+        );
+
+        let msg = ExecuteMsg::SetTaxAdmin {
+            tax_admin: Some("next_admin".to_string()),
+            expires: None,
+        };
+        assert!(exec(&mut deps, "admin", msg).is_empty());
+        assert_eq!(
+            exec(&mut deps, "next_admin", ExecuteMsg::AcceptTaxAdmin {}),
+            vec![admin_changed_event("tax", "admin", "next_admin")]
+        );
+        assert_eq!(
+            exec(&mut deps, "next_admin", ExecuteMsg::RenounceTaxAdmin {}),
+            vec![admin_changed_event("tax", "next_admin", "")]
+        );
+    }
+
+    #[test]
+    fn whale_info_and_whale_admin_events() {
+        let mut deps = setup();
+
+        let whale_info = WhaleInfo {
+            threshold: Decimal::percent(10),
+            whitelist: vec![Addr::unchecked("pair"), Addr::unchecked("treasury")],
+            admin: Addr::unchecked("creator"),
+        };
+        let msg = ExecuteMsg::SetWhaleInfo {
+            whale_info: Some(whale_info),
+        };
+        assert_eq!(
+            exec(&mut deps, "creator", msg),
+            vec![Event::new("whale_info_updated")
+                .add_attribute("admin", "creator")
+                .add_attribute("threshold", "0.1")
+                .add_attribute("whitelist", "pair,treasury")]
+        );
+
+        let msg = ExecuteMsg::SetWhaleAdmin {
+            whale_admin: Some("next_admin".to_string()),
+            expires: None,
+        };
+        assert!(exec(&mut deps, "creator", msg).is_empty());
+        assert_eq!(
+            exec(&mut deps, "next_admin", ExecuteMsg::AcceptWhaleAdmin {}),
+            vec![admin_changed_event("whale", "creator", "next_admin")]
+        );
+        assert_eq!(
+            exec(&mut deps, "next_admin", ExecuteMsg::RenounceWhaleAdmin {}),
+            vec![admin_changed_event("whale", "next_admin", "")]
+        );
+    }
+
+    #[test]
+    fn migrate_events() {
+        let mut deps = setup();
+        let whale_info = WhaleInfo {
+            threshold: Decimal::percent(5),
+            whitelist: vec![],
+            admin: Addr::unchecked("creator"),
+        };
+        let msg = MigrateMsg {
+            tax_map: Some(tax_map("admin")),
+            whale_info: Some(whale_info),
+            new_info: None,
+            max_tax_rate: None,
+            tax_rate_limit: None,
+            approval: None,
+        };
+        let res = migrate(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(
+            res.events,
+            vec![
+                Event::new("tax_map_updated")
+                    .add_attribute("action", "migrate")
+                    .add_attribute("admin", "admin")
+                    .add_attribute("tax_map_version", "2"),
+                Event::new("whale_info_updated")
+                    .add_attribute("admin", "creator")
+                    .add_attribute("threshold", "0.05")
+                    .add_attribute("whitelist", ""),
+            ]
+        );
+    }
+}
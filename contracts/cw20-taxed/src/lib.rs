@@ -15,12 +15,19 @@ For more information on this contract, please check out the
 */
 
 pub mod allowances;
+pub mod approval;
 pub mod contract;
 pub mod enumerable;
 mod error;
+pub mod events;
 pub mod msg;
+pub mod receiver;
+pub mod referral;
+pub mod settlement;
 pub mod state;
 pub mod tax;
+#[cfg(test)]
+mod testing;
 pub mod whale;
 
 pub use crate::error::ContractError;
@@ -1,11 +1,17 @@
 use crate::state::{MigrateTokenInfo, TokenInfo};
 use cosmwasm_schema::{cw_serde, QueryResponses};
-use cosmwasm_std::{Binary, StdError, StdResult, Uint128};
+use cosmwasm_std::{Binary, Decimal, StdError, StdResult, Timestamp, Uint128};
 use cw20::{Cw20Coin, Expiration, Logo, MinterResponse};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::{tax::TaxMap, whale::WhaleInfo};
+use crate::{
+    approval::{ApprovalConfig, Proposal},
+    error::ContractError,
+    settlement::TaxSettlementPolicy,
+    tax::{TaxAction, TaxInfo, TaxMap, TaxMapVersion, TaxRateLimit},
+    whale::WhaleInfo,
+};
 
 #[cw_serde]
 pub struct InstantiateMarketingInfo {
@@ -26,10 +32,51 @@ pub struct InstantiateMsg {
     pub marketing: Option<InstantiateMarketingInfo>,
     pub tax_map: Option<TaxMap>,
     pub whale_info: Option<WhaleInfo>,
+    /// Hard ceiling for all tax rates. Can only be lowered afterwards
+    pub max_tax_rate: Option<Decimal>,
+    /// Limits how much tax rates may change per epoch
+    pub tax_rate_limit: Option<TaxRateLimit>,
+    /// Signers that have to approve tax and whale configuration changes
+    pub approval: Option<ApprovalConfig>,
 }
 
 pub struct InstantiateTaxMap {}
 
+fn validate_name_and_symbol(name: &str, symbol: &str) -> StdResult<()> {
+    if !is_valid_name(name) {
+        return Err(StdError::generic_err(
+            "Name is not in the expected format (3-50 UTF-8 bytes)",
+        ));
+    }
+    if !is_valid_symbol(symbol) {
+        return Err(StdError::generic_err(
+            "Ticker symbol is not in expected format [a-zA-Z\\-]{3,12}",
+        ));
+    }
+    Ok(())
+}
+
+fn is_valid_name(name: &str) -> bool {
+    let bytes = name.as_bytes();
+    if bytes.len() < 3 || bytes.len() > 50 {
+        return false;
+    }
+    true
+}
+
+fn is_valid_symbol(symbol: &str) -> bool {
+    let bytes = symbol.as_bytes();
+    if bytes.len() < 3 || bytes.len() > 12 {
+        return false;
+    }
+    for byte in bytes.iter() {
+        if (*byte != 45) && (*byte < 65 || *byte > 90) && (*byte < 97 || *byte > 122) {
+            return false;
+        }
+    }
+    true
+}
+
 impl InstantiateMsg {
     pub fn get_cap(&self) -> Option<Uint128> {
         self.mint.as_ref().and_then(|v| v.cap)
@@ -37,48 +84,22 @@ impl InstantiateMsg {
 
     pub fn validate(&self) -> StdResult<()> {
         // Check name, symbol, decimals
-        if !self.has_valid_name() {
-            return Err(StdError::generic_err(
-                "Name is not in the expected format (3-50 UTF-8 bytes)",
-            ));
-        }
-        if !self.has_valid_symbol() {
-            return Err(StdError::generic_err(
-                "Ticker symbol is not in expected format [a-zA-Z\\-]{3,12}",
-            ));
-        }
+        validate_name_and_symbol(&self.name, &self.symbol)?;
         if self.decimals > 18 {
             return Err(StdError::generic_err("Decimals must not exceed 18"));
         }
-        Ok(())
-    }
-
-    fn has_valid_name(&self) -> bool {
-        let bytes = self.name.as_bytes();
-        if bytes.len() < 3 || bytes.len() > 50 {
-            return false;
-        }
-        true
-    }
-
-    fn has_valid_symbol(&self) -> bool {
-        let bytes = self.symbol.as_bytes();
-        if bytes.len() < 3 || bytes.len() > 12 {
-            return false;
-        }
-        for byte in bytes.iter() {
-            if (*byte != 45) && (*byte < 65 || *byte > 90) && (*byte < 97 || *byte > 122) {
-                return false;
+        if let Some(max_tax_rate) = self.max_tax_rate {
+            if max_tax_rate > Decimal::one() {
+                return Err(StdError::generic_err("Maximum tax rate must not exceed 1"));
             }
         }
-        true
-    }
-
-    fn has_valid_whale_info(&self) -> bool {
-        if let Some(whale_info) = &self.whale_info {
-            return whale_info.validate().is_ok();
+        if let Some(tax_rate_limit) = &self.tax_rate_limit {
+            tax_rate_limit.validate()?;
+        }
+        if let Some(approval) = &self.approval {
+            approval.validate()?;
         }
-        true
+        Ok(())
     }
 }
 
@@ -134,6 +155,216 @@ pub enum QueryMsg {
     DownloadLogo {},
     #[returns(TaxMap)]
     TaxMap {},
+    /// Returns how much the given address can still move tax free per action
+    /// with a volume allowance.
+    #[returns(TaxFreeVolumeResponse)]
+    TaxFreeVolume { address: String },
+    /// Returns the discount the given address currently gets on the tax of
+    /// each action with discount tiers.
+    #[returns(TaxDiscountsResponse)]
+    TaxDiscounts { address: String },
+    /// Explains how a transfer would be taxed right now: the tax, the wallet it
+    /// goes to and which rule matched for tax infos with rules.
+    #[returns(SimulateTaxResponse)]
+    SimulateTax {
+        action: TaxAction,
+        /// Wallet that pays the tax, the owner for `*_from` actions
+        sender: String,
+        recipient: String,
+        amount: Uint128,
+        /// Hook message of sends
+        msg: Option<Binary>,
+    },
+    /// Checks whether a tax map would be accepted by `SetTaxMap` right now and
+    /// lists every problem if not.
+    #[returns(ValidateTaxMapResponse)]
+    ValidateTaxMap { tax_map: Box<TaxMap> },
+    /// Returns the tax map admin and who controls each taxed action.
+    #[returns(TaxAdminsResponse)]
+    TaxAdmins {},
+    /// Returns the immutable ceiling for tax rates, if any.
+    #[returns(MaxTaxRateResponse)]
+    MaxTaxRate {},
+    /// Returns the limit on tax rate changes and when the running epoch started.
+    #[returns(TaxRateLimitResponse)]
+    TaxRateLimit {},
+    /// Returns the tax map that was active at the given block height along with its version.
+    #[returns(TaxMapVersion)]
+    TaxMapAt { height: u64 },
+    /// Returns all accepted tax maps ordered by version. Supports pagination.
+    #[returns(TaxMapHistoryResponse)]
+    TaxMapHistory {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    /// Returns the proposed tax admin that did not accept yet, if any.
+    #[returns(PendingAdminResponse)]
+    PendingTaxAdmin {},
+    /// Returns the proposed whale admin that did not accept yet, if any.
+    #[returns(PendingAdminResponse)]
+    PendingWhaleAdmin {},
+    /// Returns the signers that have to approve configuration changes, if any.
+    #[returns(ApprovalConfigResponse)]
+    ApprovalConfig {},
+    #[returns(Proposal)]
+    Proposal { proposal_id: u64 },
+    /// Returns all proposals ordered by id. Supports pagination.
+    #[returns(ProposalsResponse)]
+    Proposals {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    /// Returns the referrer of the given address, if any.
+    #[returns(ReferrerResponse)]
+    Referrer { address: String },
+    /// Returns the total tax share the given referrer earned.
+    #[returns(ReferralEarningsResponse)]
+    ReferralEarnings { address: String },
+    /// Returns whether the given contract gets the extended receive message
+    /// including the tax of a send.
+    #[returns(ExtendedReceiveResponse)]
+    ExtendedReceive { address: String },
+    /// Returns what happens to a transfer if its tax cannot be settled.
+    #[returns(TaxSettlementPolicyResponse)]
+    TaxSettlementPolicy {},
+    /// Returns the tax parked in escrow for the given recipient.
+    #[returns(EscrowedTaxResponse)]
+    EscrowedTax { address: String },
+    /// Returns all recipients with tax parked in escrow. Supports pagination.
+    #[returns(AllEscrowedTaxResponse)]
+    AllEscrowedTax {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+}
+
+#[cw_serde]
+pub struct ReferrerResponse {
+    pub referrer: Option<String>,
+}
+
+#[cw_serde]
+pub struct ReferralEarningsResponse {
+    pub earnings: Uint128,
+}
+
+#[cw_serde]
+pub struct ExtendedReceiveResponse {
+    pub enabled: bool,
+}
+
+#[cw_serde]
+pub struct TaxSettlementPolicyResponse {
+    pub policy: TaxSettlementPolicy,
+}
+
+#[cw_serde]
+pub struct EscrowedTaxResponse {
+    pub amount: Uint128,
+}
+
+#[cw_serde]
+pub struct EscrowedTaxInfo {
+    pub recipient: String,
+    pub amount: Uint128,
+}
+
+#[cw_serde]
+pub struct AllEscrowedTaxResponse {
+    pub escrowed: Vec<EscrowedTaxInfo>,
+}
+
+#[cw_serde]
+pub struct ApprovalConfigResponse {
+    pub approval: Option<ApprovalConfig>,
+}
+
+#[cw_serde]
+pub struct ProposalsResponse {
+    pub proposals: Vec<Proposal>,
+}
+
+#[cw_serde]
+pub struct PendingAdminResponse {
+    pub pending_admin: Option<String>,
+    pub expires: Option<Expiration>,
+}
+
+#[cw_serde]
+pub struct TaxFreeHeadroom {
+    pub action: TaxAction,
+    pub allowance: Uint128,
+    pub remaining: Uint128,
+}
+
+#[cw_serde]
+pub struct TaxFreeVolumeResponse {
+    pub headroom: Vec<TaxFreeHeadroom>,
+}
+
+#[cw_serde]
+pub struct TaxDiscount {
+    pub action: TaxAction,
+    pub discount: Decimal,
+}
+
+#[cw_serde]
+pub struct TaxDiscountsResponse {
+    pub balance: Uint128,
+    pub discounts: Vec<TaxDiscount>,
+}
+
+#[cw_serde]
+pub struct SimulateTaxResponse {
+    pub net: Uint128,
+    /// Amount taken from the sender, more than the amount if the tax is on top
+    pub gross: Uint128,
+    pub tax: Uint128,
+    /// Wallet the tax goes to, none if the transfer is not taxed
+    pub proceeds: Option<String>,
+    /// Rule that matched, if the tax info has rules and any of them matched
+    pub rule: Option<TaxRuleMatch>,
+}
+
+#[cw_serde]
+pub struct ValidateTaxMapResponse {
+    pub valid: bool,
+    pub errors: Vec<String>,
+}
+
+#[cw_serde]
+pub struct TaxRuleMatch {
+    pub index: u32,
+    pub name: Option<String>,
+}
+
+#[cw_serde]
+pub struct TaxActionAdmin {
+    pub action: TaxAction,
+    pub admin: String,
+}
+
+#[cw_serde]
+pub struct TaxAdminsResponse {
+    pub admin: String,
+    pub actions: Vec<TaxActionAdmin>,
+}
+
+#[cw_serde]
+pub struct MaxTaxRateResponse {
+    pub max_tax_rate: Option<Decimal>,
+}
+
+#[cw_serde]
+pub struct TaxMapHistoryResponse {
+    pub versions: Vec<TaxMapVersion>,
+}
+
+#[cw_serde]
+pub struct TaxRateLimitResponse {
+    pub tax_rate_limit: Option<TaxRateLimit>,
+    pub epoch_start_height: Option<u64>,
+    pub epoch_start_time: Option<Timestamp>,
 }
 
 #[derive(Serialize, Deserialize, JsonSchema)]
@@ -141,6 +372,37 @@ pub struct MigrateMsg {
     pub tax_map: Option<TaxMap>,
     pub whale_info: Option<WhaleInfo>,
     pub new_info: Option<MigrateTokenInfo>,
+    // sets the tax rate ceiling if there is none yet, otherwise may only lower it
+    pub max_tax_rate: Option<Decimal>,
+    pub tax_rate_limit: Option<TaxRateLimit>,
+    // sets the signers, afterwards they can only be changed by their own approval
+    pub approval: Option<ApprovalConfig>,
+}
+
+impl MigrateMsg {
+    /// Applies the checks of `InstantiateMsg::validate` to the configuration
+    /// supplied with the migration. Tax maps and addresses are validated by
+    /// `migrate`, which knows the stored tax rate ceiling.
+    pub fn validate(&self) -> Result<(), ContractError> {
+        if let Some(new_info) = &self.new_info {
+            validate_name_and_symbol(&new_info.name, &new_info.symbol)?;
+        }
+        if let Some(max_tax_rate) = self.max_tax_rate {
+            if max_tax_rate > Decimal::one() {
+                return Err(StdError::generic_err("Maximum tax rate must not exceed 1").into());
+            }
+        }
+        if let Some(tax_rate_limit) = &self.tax_rate_limit {
+            tax_rate_limit.validate()?;
+        }
+        if let Some(approval) = &self.approval {
+            approval.validate()?;
+        }
+        if let Some(whale_info) = &self.whale_info {
+            whale_info.validate()?;
+        }
+        Ok(())
+    }
 }
 
 #[cw_serde]
@@ -227,9 +489,21 @@ pub enum Cw20TaxedExecuteMsg {
     SetTaxMap {
         tax_map: Option<TaxMap>,
     }, // empty resets tax map to default
+    PatchTaxMap {
+        action: TaxAction,
+        tax_info: Option<TaxInfo>,
+        proceeds: Option<String>,
+    }, // replaces the tax info of a single action and/or only its proceeds
     SetTaxAdmin {
         tax_admin: Option<String>,
-    }, // empty resets tax_admin to ""
+        expires: Option<Expiration>,
+    }, // proposes a new tax admin that needs to accept, empty is rejected
+    AcceptTaxAdmin {},
+    CancelTaxAdmin {},
+    RenounceTaxAdmin {}, // resets tax_admin to "" for good
+    LowerMaxTaxRate {
+        max_tax_rate: Decimal,
+    }, // can never be raised again
 
     /// Whale extension related
     SetWhaleInfo {
@@ -237,7 +511,84 @@ pub enum Cw20TaxedExecuteMsg {
     }, // empty resets whale_info to default
     SetWhaleAdmin {
         whale_admin: Option<String>,
-    }, // empty resets whale_admin to ""
+        expires: Option<Expiration>,
+    }, // proposes a new whale admin that needs to accept, empty is rejected
+    AcceptWhaleAdmin {},
+    CancelWhaleAdmin {},
+    RenounceWhaleAdmin {}, // resets whale_admin to "" for good
+
+    /// Approval extension related
+    Propose {
+        msg: GovernedMsg,
+        expires: Option<Expiration>,
+    }, // executed as soon as enough signers approved
+    Approve {
+        proposal_id: u64,
+    },
+
+    /// Referral extension related
+    SetReferrer {
+        referrer: String,
+    }, // can be set only once
+
+    /// Receiver extension related
+    SetExtendedReceive {
+        contract: Option<String>,
+        enabled: bool,
+    }, // empty sets it for the sender, other contracts need the tax admin
+
+    /// Tax settlement related
+    SetTaxSettlementPolicy {
+        policy: TaxSettlementPolicy,
+    }, // only the tax admin, applies to settlements that fail from now on
+    FlushEscrowedTax {
+        recipient: String,
+    }, // retries the settlement of all tax parked for the recipient
+
+    /// Tax settlement, only callable by the contract itself
+    SettleTax {
+        payer: String,
+        recipient: String,
+        amount: Uint128,
+    }, // moves a collected tax share to its recipient as a transfer of the payer
+}
+
+/// Configuration changes that need the approval of the signers once
+/// an approval config is set
+#[cw_serde]
+pub enum GovernedMsg {
+    SetTaxMap {
+        tax_map: Option<TaxMap>,
+    },
+    PatchTaxMap {
+        action: TaxAction,
+        tax_info: Option<TaxInfo>,
+        proceeds: Option<String>,
+    },
+    SetTaxAdmin {
+        tax_admin: Option<String>,
+        expires: Option<Expiration>,
+    },
+    CancelTaxAdmin {},
+    RenounceTaxAdmin {},
+    LowerMaxTaxRate {
+        max_tax_rate: Decimal,
+    },
+    SetTaxSettlementPolicy {
+        policy: TaxSettlementPolicy,
+    },
+    SetWhaleInfo {
+        whale_info: Option<WhaleInfo>,
+    },
+    SetWhaleAdmin {
+        whale_admin: Option<String>,
+        expires: Option<Expiration>,
+    },
+    CancelWhaleAdmin {},
+    RenounceWhaleAdmin {},
+    SetApprovalConfig {
+        approval: Option<ApprovalConfig>,
+    }, // empty removes the approval requirement
 }
 
 #[cfg(test)]
@@ -247,44 +598,31 @@ mod tests {
     #[test]
     fn validate_instantiatemsg_name() {
         // Too short
-        let mut msg = InstantiateMsg {
-            name: str::repeat("a", 2),
-            ..InstantiateMsg::default()
-        };
-        assert!(!msg.has_valid_name());
+        assert!(validate_name_and_symbol(&str::repeat("a", 2), "TOKEN").is_err());
 
         // In the correct length range
-        msg.name = str::repeat("a", 3);
-        assert!(msg.has_valid_name());
+        assert!(validate_name_and_symbol(&str::repeat("a", 3), "TOKEN").is_ok());
 
         // Too long
-        msg.name = str::repeat("a", 51);
-        assert!(!msg.has_valid_name());
+        assert!(validate_name_and_symbol(&str::repeat("a", 51), "TOKEN").is_err());
     }
 
     #[test]
     fn validate_instantiatemsg_symbol() {
         // Too short
-        let mut msg = InstantiateMsg {
-            symbol: str::repeat("a", 2),
-            ..InstantiateMsg::default()
-        };
-        assert!(!msg.has_valid_symbol());
+        assert!(validate_name_and_symbol("Token", &str::repeat("a", 2)).is_err());
 
         // In the correct length range
-        msg.symbol = str::repeat("a", 3);
-        assert!(msg.has_valid_symbol());
+        assert!(validate_name_and_symbol("Token", &str::repeat("a", 3)).is_ok());
 
         // Too long
-        msg.symbol = str::repeat("a", 13);
-        assert!(!msg.has_valid_symbol());
+        assert!(validate_name_and_symbol("Token", &str::repeat("a", 13)).is_err());
 
         // Has illegal char
         let illegal_chars = [[64u8], [91u8], [123u8]];
         illegal_chars.iter().for_each(|c| {
             let c = std::str::from_utf8(c).unwrap();
-            msg.symbol = str::repeat(c, 3);
-            assert!(!msg.has_valid_symbol());
+            assert!(validate_name_and_symbol("Token", &str::repeat(c, 3)).is_err());
         });
     }
 }
@@ -0,0 +1,225 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{
+    to_json_binary, Addr, Binary, CosmosMsg, Deps, DepsMut, Empty, MessageInfo, Response,
+    StdResult, Storage, Uint128, WasmMsg,
+};
+use cw20::Cw20ReceiveMsg;
+
+use crate::msg::ExtendedReceiveResponse;
+use crate::state::{EXTENDED_RECEIVERS, TAX_INFO};
+use crate::tax::TaxAction;
+use crate::ContractError;
+
+/// Receive message for contracts that opted in to learn about the tax of a
+/// send. It extends the plain cw20 `Cw20ReceiveMsg` by the gross amount, the
+/// tax and the taxed action, `amount` is still the net amount received.
+#[cw_serde]
+pub struct TaxedCw20ReceiveMsg {
+    pub sender: String,
+    pub amount: Uint128,
+    pub gross: Uint128,
+    pub tax: Uint128,
+    pub tax_action: TaxAction,
+    pub msg: Binary,
+}
+
+impl TaxedCw20ReceiveMsg {
+    /// serializes the message
+    pub fn into_json_binary(self) -> StdResult<Binary> {
+        let msg = ReceiverExecuteMsg::Receive(self);
+        to_json_binary(&msg)
+    }
+
+    /// creates a cosmos_msg sending this struct to the named contract
+    pub fn into_cosmos_msg<T: Into<String>>(self, contract_addr: T) -> StdResult<CosmosMsg> {
+        let msg = self.into_json_binary()?;
+        let execute = WasmMsg::Execute {
+            contract_addr: contract_addr.into(),
+            msg,
+            funds: vec![],
+        };
+        Ok(execute.into())
+    }
+}
+
+// This is just a helper to properly serialize the above message
+#[cw_serde]
+enum ReceiverExecuteMsg {
+    Receive(TaxedCw20ReceiveMsg),
+}
+
+/// Builds the receive message of a send. Contracts that opted in get the
+/// extended message, all others the plain cw20 one.
+pub fn receive_msg(
+    storage: &dyn Storage,
+    contract: &Addr,
+    msg: TaxedCw20ReceiveMsg,
+) -> StdResult<CosmosMsg> {
+    if EXTENDED_RECEIVERS.has(storage, contract) {
+        return msg.into_cosmos_msg(contract);
+    }
+    Cw20ReceiveMsg {
+        sender: msg.sender,
+        amount: msg.amount,
+        msg: msg.msg,
+    }
+    .into_cosmos_msg(contract)
+}
+
+/// Opts a contract in or out of the extended receive message. Contracts can
+/// only choose for themselves, the tax admin can choose for any contract.
+pub fn execute_set_extended_receive(
+    deps: DepsMut,
+    info: MessageInfo,
+    contract: Option<String>,
+    enabled: bool,
+) -> Result<Response, ContractError> {
+    let contract = match contract {
+        Some(x) => deps.api.addr_validate(&x)?,
+        None => info.sender.clone(),
+    };
+    if contract != info.sender && TAX_INFO.load(deps.storage)?.admin != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    if enabled {
+        EXTENDED_RECEIVERS.save(deps.storage, &contract, &Empty {})?;
+    } else {
+        EXTENDED_RECEIVERS.remove(deps.storage, &contract);
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "set_extended_receive")
+        .add_attribute("contract", contract)
+        .add_attribute("enabled", enabled.to_string()))
+}
+
+pub fn query_extended_receive(deps: Deps, address: String) -> StdResult<ExtendedReceiveResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    Ok(ExtendedReceiveResponse {
+        enabled: EXTENDED_RECEIVERS.has(deps.storage, &address),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::{Decimal, SubMsg};
+    use cw20::Cw20Coin;
+
+    use crate::contract::{execute, instantiate};
+    use crate::msg::{Cw20TaxedExecuteMsg as ExecuteMsg, InstantiateMsg};
+    use crate::tax::{TaxAlwaysCondition, TaxCondition, TaxMap};
+
+    // token with a 10% send tax
+    fn setup(deps: DepsMut) {
+        let mut tax_map = TaxMap {
+            admin: Addr::unchecked("admin"),
+            ..TaxMap::default()
+        };
+        tax_map.on_send.src_cond = TaxCondition::Always(TaxAlwaysCondition {
+            tax_rate: Decimal::percent(10),
+        });
+        tax_map.on_send.dst_cond = TaxCondition::Always(TaxAlwaysCondition {
+            tax_rate: Decimal::zero(),
+        });
+        tax_map.on_send.proceeds = Addr::unchecked("proceeds");
+        let msg = InstantiateMsg {
+            name: "Token".to_string(),
+            symbol: "TOKEN".to_string(),
+            decimals: 6,
+            initial_balances: vec![Cw20Coin {
+                address: "user".to_string(),
+                amount: Uint128::new(10000),
+            }],
+            mint: None,
+            marketing: None,
+            tax_map: Some(tax_map),
+            whale_info: None,
+            max_tax_rate: None,
+            tax_rate_limit: None,
+            approval: None,
+        };
+        instantiate(deps, mock_env(), mock_info("creator", &[]), msg).unwrap();
+    }
+
+    fn set_extended_receive(
+        deps: DepsMut,
+        sender: &str,
+        contract: Option<&str>,
+        enabled: bool,
+    ) -> Result<Response, ContractError> {
+        let msg = ExecuteMsg::SetExtendedReceive {
+            contract: contract.map(String::from),
+            enabled,
+        };
+        execute(deps, mock_env(), mock_info(sender, &[]), msg)
+    }
+
+    // receive message of a send of 1000 tokens to the pool
+    fn send(deps: DepsMut) -> SubMsg {
+        let msg = ExecuteMsg::Send {
+            contract: "pool".to_string(),
+            amount: Uint128::new(1000),
+            msg: Binary::from(b"{}"),
+        };
+        let res = execute(deps, mock_env(), mock_info("user", &[]), msg).unwrap();
+        res.messages[0].clone()
+    }
+
+    #[test]
+    fn extended_receive_is_opt_in() {
+        let mut deps = mock_dependencies();
+        setup(deps.as_mut());
+        let plain = SubMsg::new(
+            Cw20ReceiveMsg {
+                sender: "user".to_string(),
+                amount: Uint128::new(900),
+                msg: Binary::from(b"{}"),
+            }
+            .into_cosmos_msg("pool")
+            .unwrap(),
+        );
+        let extended = SubMsg::new(
+            TaxedCw20ReceiveMsg {
+                sender: "user".to_string(),
+                amount: Uint128::new(900),
+                gross: Uint128::new(1000),
+                tax: Uint128::new(100),
+                tax_action: TaxAction::OnSend,
+                msg: Binary::from(b"{}"),
+            }
+            .into_cosmos_msg("pool")
+            .unwrap(),
+        );
+        assert_eq!(send(deps.as_mut()), plain);
+
+        set_extended_receive(deps.as_mut(), "pool", None, true).unwrap();
+        let res = query_extended_receive(deps.as_ref(), "pool".to_string()).unwrap();
+        assert!(res.enabled);
+        assert_eq!(send(deps.as_mut()), extended);
+
+        set_extended_receive(deps.as_mut(), "pool", None, false).unwrap();
+        let res = query_extended_receive(deps.as_ref(), "pool".to_string()).unwrap();
+        assert!(!res.enabled);
+        assert_eq!(send(deps.as_mut()), plain);
+    }
+
+    #[test]
+    fn only_tax_admin_can_opt_in_other_contracts() {
+        let mut deps = mock_dependencies();
+        setup(deps.as_mut());
+        let err = set_extended_receive(deps.as_mut(), "user", Some("pool"), true).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        set_extended_receive(deps.as_mut(), "admin", Some("pool"), true).unwrap();
+        let res = query_extended_receive(deps.as_ref(), "pool".to_string()).unwrap();
+        assert!(res.enabled);
+
+        // a contract can still opt out on its own
+        set_extended_receive(deps.as_mut(), "pool", Some("pool"), false).unwrap();
+        let res = query_extended_receive(deps.as_ref(), "pool".to_string()).unwrap();
+        assert!(!res.enabled);
+    }
+}
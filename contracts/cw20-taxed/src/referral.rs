@@ -0,0 +1,224 @@
+use cosmwasm_std::{Addr, Deps, DepsMut, MessageInfo, Response, StdResult, Storage, Uint128};
+
+use crate::msg::{ReferralEarningsResponse, ReferrerResponse};
+use crate::state::{REFERRAL_EARNINGS, REFERRERS};
+use crate::tax::TaxInfo;
+use crate::ContractError;
+
+/// Share of a tax that was paid out to the referrer of the payer
+pub struct ReferralPayout {
+    pub referrer: Addr,
+    pub amount: Uint128,
+}
+
+/// Registers the referrer of the sender. It can only be set once and must
+/// neither be the sender itself nor be referred by the sender.
+pub fn execute_set_referrer(
+    deps: DepsMut,
+    info: MessageInfo,
+    referrer: String,
+) -> Result<Response, ContractError> {
+    let referrer = deps.api.addr_validate(&referrer)?;
+    if REFERRERS.has(deps.storage, &info.sender) {
+        return Err(ContractError::ReferrerAlreadySet {});
+    }
+
+    // walk up the referral chain, which is free of cycles as long as the
+    // sender is not part of it
+    let mut next = Some(referrer.clone());
+    while let Some(addr) = next {
+        if addr == info.sender {
+            return Err(ContractError::InvalidReferrer {});
+        }
+        next = REFERRERS.may_load(deps.storage, &addr)?;
+    }
+    REFERRERS.save(deps.storage, &info.sender, &referrer)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "set_referrer")
+        .add_attribute("referee", info.sender)
+        .add_attribute("referrer", referrer))
+}
+
+/// Computes the referral share of `tax` for the referrer of `payer`, if any.
+/// The caller has to settle the share to the referrer and only the rest of
+/// the tax to the proceeds wallet. The share counts as earned once settled.
+pub fn pay_referral(
+    storage: &dyn Storage,
+    tax_info: &TaxInfo,
+    payer: &Addr,
+    tax: Uint128,
+) -> StdResult<Option<ReferralPayout>> {
+    if tax.is_zero() || tax_info.referral_share.is_zero() {
+        return Ok(None);
+    }
+    let referrer = match REFERRERS.may_load(storage, payer)? {
+        Some(referrer) => referrer,
+        None => return Ok(None),
+    };
+    let amount = tax.mul_floor(tax_info.referral_share);
+    if amount.is_zero() {
+        return Ok(None);
+    }
+    Ok(Some(ReferralPayout { referrer, amount }))
+}
+
+/// Adds a referral share that reached the referrer to its earnings
+pub fn add_referral_earnings(
+    storage: &mut dyn Storage,
+    referrer: &Addr,
+    amount: Uint128,
+) -> StdResult<()> {
+    REFERRAL_EARNINGS.update(storage, referrer, |earnings| -> StdResult<_> {
+        Ok(earnings.unwrap_or_default().checked_add(amount)?)
+    })?;
+    Ok(())
+}
+
+pub fn query_referrer(deps: Deps, address: String) -> StdResult<ReferrerResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let referrer = REFERRERS
+        .may_load(deps.storage, &address)?
+        .map(|x| x.into_string());
+    Ok(ReferrerResponse { referrer })
+}
+
+pub fn query_referral_earnings(deps: Deps, address: String) -> StdResult<ReferralEarningsResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let earnings = REFERRAL_EARNINGS
+        .may_load(deps.storage, &address)?
+        .unwrap_or_default();
+    Ok(ReferralEarningsResponse { earnings })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cosmwasm_std::Decimal;
+    use cw20::BalanceResponse;
+    use cw_multi_test::{App, AppResponse, Executor};
+
+    use crate::msg::{Cw20TaxedExecuteMsg as ExecuteMsg, QueryMsg};
+    use crate::tax::{TaxAlwaysCondition, TaxCondition};
+    use crate::testing::{instantiate_cw20, instantiate_msg, transfer_tax_map};
+
+    // token with a 10% transfer tax of which the referrer gets 30%
+    fn setup() -> (App, Addr) {
+        let mut app = App::default();
+        let mut tax_map = transfer_tax_map(TaxCondition::Always(TaxAlwaysCondition {
+            tax_rate: Decimal::percent(10),
+        }));
+        tax_map.on_transfer.referral_share = Decimal::percent(30);
+        let msg = instantiate_msg()
+            .with_balance("referee", 10000)
+            .with_tax_map(tax_map);
+        let cw20_addr = instantiate_cw20(&mut app, &msg);
+        (app, cw20_addr)
+    }
+
+    fn set_referrer(
+        app: &mut App,
+        cw20_addr: &Addr,
+        sender: &str,
+        referrer: &str,
+    ) -> Result<AppResponse, ContractError> {
+        let msg = ExecuteMsg::SetReferrer {
+            referrer: referrer.to_string(),
+        };
+        app.execute_contract(Addr::unchecked(sender), cw20_addr.clone(), &msg, &[])
+            .map_err(|err| err.downcast().unwrap())
+    }
+
+    fn transfer(app: &mut App, cw20_addr: &Addr, amount: u128) {
+        let msg = ExecuteMsg::Transfer {
+            recipient: "pair".to_string(),
+            amount: Uint128::new(amount),
+        };
+        app.execute_contract(Addr::unchecked("referee"), cw20_addr.clone(), &msg, &[])
+            .unwrap();
+    }
+
+    fn balance(app: &App, cw20_addr: &Addr, address: &str) -> Uint128 {
+        let msg = QueryMsg::Balance {
+            address: address.to_string(),
+        };
+        let res: BalanceResponse = app.wrap().query_wasm_smart(cw20_addr, &msg).unwrap();
+        res.balance
+    }
+
+    #[test]
+    fn referrer_can_only_be_set_once() {
+        let (mut app, cw20_addr) = setup();
+        set_referrer(&mut app, &cw20_addr, "referee", "referrer").unwrap();
+        let err = set_referrer(&mut app, &cw20_addr, "referee", "other").unwrap_err();
+        assert_eq!(err, ContractError::ReferrerAlreadySet {});
+
+        let res: ReferrerResponse = app
+            .wrap()
+            .query_wasm_smart(
+                &cw20_addr,
+                &QueryMsg::Referrer {
+                    address: "referee".to_string(),
+                },
+            )
+            .unwrap();
+        assert_eq!(res.referrer, Some("referrer".to_string()));
+        let res: ReferrerResponse = app
+            .wrap()
+            .query_wasm_smart(
+                &cw20_addr,
+                &QueryMsg::Referrer {
+                    address: "referrer".to_string(),
+                },
+            )
+            .unwrap();
+        assert_eq!(res.referrer, None);
+    }
+
+    #[test]
+    fn self_and_cyclic_referrals_are_rejected() {
+        let (mut app, cw20_addr) = setup();
+        let err = set_referrer(&mut app, &cw20_addr, "alice", "alice").unwrap_err();
+        assert_eq!(err, ContractError::InvalidReferrer {});
+
+        // alice <- bob <- carol, so carol cannot refer alice
+        set_referrer(&mut app, &cw20_addr, "bob", "alice").unwrap();
+        set_referrer(&mut app, &cw20_addr, "carol", "bob").unwrap();
+        let err = set_referrer(&mut app, &cw20_addr, "alice", "carol").unwrap_err();
+        assert_eq!(err, ContractError::InvalidReferrer {});
+        set_referrer(&mut app, &cw20_addr, "alice", "dave").unwrap();
+    }
+
+    #[test]
+    fn referrer_earns_share_of_tax() {
+        let (mut app, cw20_addr) = setup();
+
+        // no referrer, the proceeds get everything
+        transfer(&mut app, &cw20_addr, 1000);
+        assert_eq!(balance(&app, &cw20_addr, "proceeds"), Uint128::new(100));
+
+        set_referrer(&mut app, &cw20_addr, "referee", "referrer").unwrap();
+        transfer(&mut app, &cw20_addr, 1000);
+        transfer(&mut app, &cw20_addr, 105);
+        assert_eq!(balance(&app, &cw20_addr, "pair"), Uint128::new(1895));
+        // 30% of the taxes of 100 and 10
+        assert_eq!(balance(&app, &cw20_addr, "referrer"), Uint128::new(33));
+        assert_eq!(balance(&app, &cw20_addr, "proceeds"), Uint128::new(177));
+        assert_eq!(
+            balance(&app, &cw20_addr, cw20_addr.as_str()),
+            Uint128::zero()
+        );
+
+        let res: ReferralEarningsResponse = app
+            .wrap()
+            .query_wasm_smart(
+                &cw20_addr,
+                &QueryMsg::ReferralEarnings {
+                    address: "referrer".to_string(),
+                },
+            )
+            .unwrap();
+        assert_eq!(res.earnings, Uint128::new(33));
+    }
+}
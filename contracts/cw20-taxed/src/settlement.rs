@@ -0,0 +1,608 @@
+use std::fmt;
+
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{
+    to_json_binary, Addr, Deps, DepsMut, Env, MessageInfo, Reply, Response, StdResult, Storage,
+    SubMsg, SubMsgResult, Uint128, WasmMsg,
+};
+
+use crate::events::TaxParkedEvent;
+use crate::msg::{
+    Cw20TaxedExecuteMsg as ExecuteMsg, EscrowedTaxResponse, TaxSettlementPolicyResponse,
+};
+use crate::referral::{add_referral_earnings, ReferralPayout};
+use crate::state::{
+    ANTI_WHALE_INFO, BALANCES, ESCROWED_REFERRAL, ESCROWED_TAX, PENDING_SETTLEMENTS,
+    SETTLEMENT_COUNT, TAX_INFO, TAX_SETTLEMENT_POLICY,
+};
+use crate::tax::record_acquisition;
+use crate::ContractError;
+
+/// What happens to the transfer if a tax share cannot be settled to its
+/// recipient, e.g. because the proceeds wallet would become a whale.
+#[cw_serde]
+#[derive(Default)]
+pub enum TaxSettlementPolicy {
+    /// The whole transfer fails with the reason of the failed settlement
+    #[default]
+    Revert,
+    /// The transfer goes through and the tax share is parked in escrow of this
+    /// contract until it is flushed to its recipient
+    Escrow,
+}
+
+impl fmt::Display for TaxSettlementPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TaxSettlementPolicy::Revert => write!(f, "revert"),
+            TaxSettlementPolicy::Escrow => write!(f, "escrow"),
+        }
+    }
+}
+
+/// A tax share dispatched for settlement that awaits its reply
+#[cw_serde]
+pub struct TaxSettlement {
+    pub payer: Addr,
+    pub recipient: Addr,
+    pub amount: Uint128,
+    /// Part of the amount that is a referral share, it counts as earned by
+    /// the recipient once settled
+    #[serde(default)]
+    pub referral: Uint128,
+}
+
+/// Builds the submessages that hand the tax collected by this contract on to
+/// the proceeds wallet and the referrer. Every share is settled by its own
+/// submessage so that it is reported as a plain transfer of the payer.
+pub fn settle_tax_msgs(
+    storage: &mut dyn Storage,
+    contract: &Addr,
+    payer: &Addr,
+    proceeds: &Addr,
+    proceeds_tax: Uint128,
+    referral: Option<&ReferralPayout>,
+) -> StdResult<Vec<SubMsg>> {
+    let mut shares = vec![(proceeds, proceeds_tax, Uint128::zero())];
+    if let Some(referral) = referral {
+        shares.push((&referral.referrer, referral.amount, referral.amount));
+    }
+    shares
+        .into_iter()
+        .filter(|(_, amount, _)| !amount.is_zero())
+        .map(|(recipient, amount, referral)| {
+            let settlement = TaxSettlement {
+                payer: payer.clone(),
+                recipient: recipient.clone(),
+                amount,
+                referral,
+            };
+            settle_tax_msg(storage, contract, settlement)
+        })
+        .collect()
+}
+
+/// Dispatches a single settlement, its reply applies the settlement policy if
+/// it fails.
+fn settle_tax_msg(
+    storage: &mut dyn Storage,
+    contract: &Addr,
+    settlement: TaxSettlement,
+) -> StdResult<SubMsg> {
+    let id = SETTLEMENT_COUNT.may_load(storage)?.unwrap_or_default() + 1;
+    SETTLEMENT_COUNT.save(storage, &id)?;
+    let msg = WasmMsg::Execute {
+        contract_addr: contract.to_string(),
+        msg: to_json_binary(&ExecuteMsg::SettleTax {
+            payer: settlement.payer.to_string(),
+            recipient: settlement.recipient.to_string(),
+            amount: settlement.amount,
+        })?,
+        funds: vec![],
+    };
+    PENDING_SETTLEMENTS.save(storage, id, &settlement)?;
+    Ok(SubMsg::reply_always(msg, id))
+}
+
+/// Moves a tax share held by this contract to its recipient. The transfer is
+/// reported on behalf of the payer, so indexers following the cw20 event
+/// convention book it against the account that actually paid the tax.
+pub fn execute_settle_tax(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    payer: String,
+    recipient: String,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    if info.sender != env.contract.address {
+        return Err(ContractError::Unauthorized {});
+    }
+    let rcpt_addr = deps.api.addr_validate(&recipient)?;
+
+    BALANCES.update(
+        deps.storage,
+        &env.contract.address,
+        |balance: Option<Uint128>| -> StdResult<_> {
+            Ok(balance.unwrap_or_default().checked_sub(amount)?)
+        },
+    )?;
+    let new_balance = BALANCES.update(
+        deps.storage,
+        &rcpt_addr,
+        |balance: Option<Uint128>| -> StdResult<_> { Ok(balance.unwrap_or_default() + amount) },
+    )?;
+    record_acquisition(deps.storage, &env.block, &rcpt_addr, amount)?;
+
+    // assert whale policy
+    let whale_info = ANTI_WHALE_INFO.load(deps.storage)?;
+    whale_info.assert_no_whale(deps.as_ref().storage, &rcpt_addr, new_balance)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "transfer")
+        .add_attribute("from", payer)
+        .add_attribute("to", rcpt_addr)
+        .add_attribute("amount", amount))
+}
+
+/// Handles the outcome of a settlement. A successful one books the referral
+/// share it carried as earned, a failed one either reverts the transfer or
+/// parks the tax share in escrow, depending on the policy.
+pub fn reply_settle_tax(deps: DepsMut, env: Env, msg: Reply) -> Result<Response, ContractError> {
+    let settlement = PENDING_SETTLEMENTS.load(deps.storage, msg.id)?;
+    PENDING_SETTLEMENTS.remove(deps.storage, msg.id);
+    let reason = match msg.result {
+        SubMsgResult::Ok(_) => {
+            if !settlement.referral.is_zero() {
+                add_referral_earnings(deps.storage, &settlement.recipient, settlement.referral)?;
+            }
+            return Ok(Response::new());
+        }
+        SubMsgResult::Err(err) => err,
+    };
+
+    let policy = TAX_SETTLEMENT_POLICY
+        .may_load(deps.storage)?
+        .unwrap_or_default();
+    if policy == TaxSettlementPolicy::Revert {
+        return Err(ContractError::TaxSettlementFailed {
+            recipient: settlement.recipient.into_string(),
+            amount: settlement.amount,
+            reason,
+        });
+    }
+
+    // the share is still held by this contract, it only has to be booked
+    ESCROWED_TAX.update(
+        deps.storage,
+        &settlement.recipient,
+        |escrowed| -> StdResult<_> {
+            Ok(escrowed
+                .unwrap_or_default()
+                .checked_add(settlement.amount)?)
+        },
+    )?;
+    if !settlement.referral.is_zero() {
+        ESCROWED_REFERRAL.update(
+            deps.storage,
+            &settlement.recipient,
+            |escrowed| -> StdResult<_> {
+                Ok(escrowed
+                    .unwrap_or_default()
+                    .checked_add(settlement.referral)?)
+            },
+        )?;
+    }
+    let mut res = Response::new().add_event(
+        TaxParkedEvent {
+            payer: &settlement.payer,
+            recipient: &settlement.recipient,
+            amount: settlement.amount,
+            reason: &reason,
+        }
+        .into(),
+    );
+    if settlement.payer != env.contract.address {
+        res = res
+            .add_attribute("action", "transfer")
+            .add_attribute("from", settlement.payer)
+            .add_attribute("to", env.contract.address)
+            .add_attribute("amount", settlement.amount);
+    }
+    Ok(res)
+}
+
+/// Retries the settlement of all tax parked in escrow for the given recipient.
+/// Anyone can trigger it as the tax can only go to its recipient.
+pub fn execute_flush_escrowed_tax(
+    deps: DepsMut,
+    env: Env,
+    recipient: String,
+) -> Result<Response, ContractError> {
+    let rcpt_addr = deps.api.addr_validate(&recipient)?;
+    let amount = ESCROWED_TAX
+        .may_load(deps.storage, &rcpt_addr)?
+        .unwrap_or_default();
+    if amount.is_zero() {
+        return Err(ContractError::NoEscrowedTax {});
+    }
+    let referral = ESCROWED_REFERRAL
+        .may_load(deps.storage, &rcpt_addr)?
+        .unwrap_or_default();
+    ESCROWED_TAX.remove(deps.storage, &rcpt_addr);
+    ESCROWED_REFERRAL.remove(deps.storage, &rcpt_addr);
+
+    let settlement = TaxSettlement {
+        payer: env.contract.address.clone(),
+        recipient: rcpt_addr.clone(),
+        amount,
+        referral,
+    };
+    let msg = settle_tax_msg(deps.storage, &env.contract.address, settlement)?;
+
+    Ok(Response::new()
+        .add_submessage(msg)
+        .add_attribute("action", "flush_escrowed_tax")
+        .add_attribute("recipient", rcpt_addr)
+        .add_attribute("amount", amount))
+}
+
+pub fn execute_set_tax_settlement_policy(
+    deps: DepsMut,
+    info: MessageInfo,
+    policy: TaxSettlementPolicy,
+) -> Result<Response, ContractError> {
+    let tax_map = TAX_INFO.load(deps.storage)?;
+    if tax_map.admin != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    TAX_SETTLEMENT_POLICY.save(deps.storage, &policy)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "set_tax_settlement_policy")
+        .add_attribute("policy", policy.to_string()))
+}
+
+pub fn query_tax_settlement_policy(deps: Deps) -> StdResult<TaxSettlementPolicyResponse> {
+    let policy = TAX_SETTLEMENT_POLICY
+        .may_load(deps.storage)?
+        .unwrap_or_default();
+    Ok(TaxSettlementPolicyResponse { policy })
+}
+
+pub fn query_escrowed_tax(deps: Deps, address: String) -> StdResult<EscrowedTaxResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let amount = ESCROWED_TAX
+        .may_load(deps.storage, &address)?
+        .unwrap_or_default();
+    Ok(EscrowedTaxResponse { amount })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cosmwasm_std::Decimal;
+    use cw20::BalanceResponse;
+    use cw_multi_test::{App, AppResponse, Executor};
+
+    use crate::msg::{AllEscrowedTaxResponse, EscrowedTaxInfo, QueryMsg, ReferralEarningsResponse};
+    use crate::tax::{TaxAlwaysCondition, TaxCondition, TaxMap};
+    use crate::testing::{instantiate_cw20, instantiate_msg, transfer_tax_map};
+    use crate::whale::WhaleInfo;
+
+    // token with a 10% transfer tax of which the referrer gets 30%
+    fn setup(whale_info: Option<WhaleInfo>) -> (App, Addr) {
+        let mut app = App::default();
+        let mut tax_map = transfer_tax_map(TaxCondition::Always(TaxAlwaysCondition {
+            tax_rate: Decimal::percent(10),
+        }));
+        tax_map.on_transfer.referral_share = Decimal::percent(30);
+        let mut msg = instantiate_msg()
+            .with_balance("payer", 10000)
+            .with_tax_map(tax_map);
+        msg.whale_info = whale_info;
+        let cw20_addr = instantiate_cw20(&mut app, &msg);
+        (app, cw20_addr)
+    }
+
+    fn execute(
+        app: &mut App,
+        cw20_addr: &Addr,
+        sender: &str,
+        msg: &ExecuteMsg,
+    ) -> Result<AppResponse, ContractError> {
+        app.execute_contract(Addr::unchecked(sender), cw20_addr.clone(), msg, &[])
+            .map_err(|err| err.downcast().unwrap())
+    }
+
+    fn balance(app: &App, cw20_addr: &Addr, address: &str) -> Uint128 {
+        let msg = QueryMsg::Balance {
+            address: address.to_string(),
+        };
+        let res: BalanceResponse = app.wrap().query_wasm_smart(cw20_addr, &msg).unwrap();
+        res.balance
+    }
+
+    // (from, to, amount) of every conventional transfer record
+    fn transfers(res: &AppResponse) -> Vec<(String, String, String)> {
+        res.events
+            .iter()
+            .filter(|event| event.ty == "wasm")
+            .filter(|event| {
+                event
+                    .attributes
+                    .iter()
+                    .any(|x| x.key == "action" && x.value.starts_with("transfer"))
+            })
+            .map(|event| {
+                let attr = |key: &str| {
+                    event
+                        .attributes
+                        .iter()
+                        .find(|x| x.key == key)
+                        .map(|x| x.value.clone())
+                        .unwrap()
+                };
+                (attr("from"), attr("to"), attr("amount"))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn every_tax_share_is_a_transfer_of_the_payer() {
+        let (mut app, cw20_addr) = setup(None);
+        let msg = ExecuteMsg::SetReferrer {
+            referrer: "referrer".to_string(),
+        };
+        execute(&mut app, &cw20_addr, "payer", &msg).unwrap();
+
+        let msg = ExecuteMsg::Transfer {
+            recipient: "pair".to_string(),
+            amount: Uint128::new(1000),
+        };
+        let res = execute(&mut app, &cw20_addr, "payer", &msg).unwrap();
+        let record =
+            |to: &str, amount: &str| ("payer".to_string(), to.to_string(), amount.to_string());
+        assert_eq!(
+            transfers(&res),
+            vec![
+                record("pair", "900"),
+                record("proceeds", "70"),
+                record("referrer", "30"),
+            ]
+        );
+
+        // the records add up to what the payer actually lost
+        assert_eq!(balance(&app, &cw20_addr, "payer"), Uint128::new(9000));
+        assert_eq!(balance(&app, &cw20_addr, "pair"), Uint128::new(900));
+        assert_eq!(balance(&app, &cw20_addr, "proceeds"), Uint128::new(70));
+        assert_eq!(balance(&app, &cw20_addr, "referrer"), Uint128::new(30));
+        assert_eq!(
+            balance(&app, &cw20_addr, cw20_addr.as_str()),
+            Uint128::zero()
+        );
+    }
+
+    #[test]
+    fn transfer_from_reports_the_owner_as_payer() {
+        let (mut app, cw20_addr) = setup(None);
+        let mut tax_map: TaxMap = app
+            .wrap()
+            .query_wasm_smart(&cw20_addr, &QueryMsg::TaxMap {})
+            .unwrap();
+        tax_map.on_transfer_from = tax_map.on_transfer.clone();
+        tax_map.on_transfer_from.referral_share = Decimal::zero();
+        let msg = ExecuteMsg::SetTaxMap {
+            tax_map: Some(tax_map),
+        };
+        execute(&mut app, &cw20_addr, "admin", &msg).unwrap();
+        let msg = ExecuteMsg::IncreaseAllowance {
+            spender: "spender".to_string(),
+            amount: Uint128::new(1000),
+            expires: None,
+        };
+        execute(&mut app, &cw20_addr, "payer", &msg).unwrap();
+
+        let msg = ExecuteMsg::TransferFrom {
+            owner: "payer".to_string(),
+            recipient: "pair".to_string(),
+            amount: Uint128::new(1000),
+        };
+        let res = execute(&mut app, &cw20_addr, "spender", &msg).unwrap();
+        let record =
+            |to: &str, amount: &str| ("payer".to_string(), to.to_string(), amount.to_string());
+        assert_eq!(
+            transfers(&res),
+            vec![record("pair", "900"), record("proceeds", "100")]
+        );
+    }
+
+    #[test]
+    fn settle_tax_is_only_callable_by_the_contract() {
+        let (mut app, cw20_addr) = setup(None);
+        let msg = ExecuteMsg::SettleTax {
+            payer: "payer".to_string(),
+            recipient: "thief".to_string(),
+            amount: Uint128::new(1),
+        };
+        let err = execute(&mut app, &cw20_addr, "payer", &msg).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+    }
+
+    // wallets but the payer and the pair may hold at most 1% of the supply
+    fn whale_limit(threshold: u64) -> WhaleInfo {
+        WhaleInfo {
+            threshold: Decimal::percent(threshold),
+            whitelist: vec![Addr::unchecked("payer"), Addr::unchecked("pair")],
+            admin: Addr::unchecked("admin"),
+        }
+    }
+
+    fn escrowed_tax(app: &App, cw20_addr: &Addr, address: &str) -> Uint128 {
+        let msg = QueryMsg::EscrowedTax {
+            address: address.to_string(),
+        };
+        let res: EscrowedTaxResponse = app.wrap().query_wasm_smart(cw20_addr, &msg).unwrap();
+        res.amount
+    }
+
+    fn transfer_2000(app: &mut App, cw20_addr: &Addr) -> Result<AppResponse, ContractError> {
+        let msg = ExecuteMsg::Transfer {
+            recipient: "pair".to_string(),
+            amount: Uint128::new(2000),
+        };
+        execute(app, cw20_addr, "payer", &msg)
+    }
+
+    #[test]
+    fn failed_settlement_reverts_by_default() {
+        let (mut app, cw20_addr) = setup(Some(whale_limit(1)));
+        let err = transfer_2000(&mut app, &cw20_addr).unwrap_err();
+        match err {
+            ContractError::TaxSettlementFailed {
+                recipient,
+                amount,
+                reason,
+            } => {
+                assert_eq!(recipient, "proceeds");
+                assert_eq!(amount, Uint128::new(200));
+                assert!(reason.contains("is holding too many tokens"), "{}", reason);
+            }
+            err => panic!("unexpected error {:?}", err),
+        }
+        assert_eq!(balance(&app, &cw20_addr, "payer"), Uint128::new(10000));
+        assert_eq!(balance(&app, &cw20_addr, "pair"), Uint128::zero());
+    }
+
+    #[test]
+    fn failed_settlement_is_parked_in_escrow() {
+        let (mut app, cw20_addr) = setup(Some(whale_limit(1)));
+        let msg = ExecuteMsg::SetTaxSettlementPolicy {
+            policy: TaxSettlementPolicy::Escrow,
+        };
+        let err = execute(&mut app, &cw20_addr, "payer", &msg).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        execute(&mut app, &cw20_addr, "admin", &msg).unwrap();
+        let res: TaxSettlementPolicyResponse = app
+            .wrap()
+            .query_wasm_smart(&cw20_addr, &QueryMsg::TaxSettlementPolicy {})
+            .unwrap();
+        assert_eq!(res.policy, TaxSettlementPolicy::Escrow);
+
+        // the transfer goes through and the tax stays with the token contract
+        let res = transfer_2000(&mut app, &cw20_addr).unwrap();
+        let contract = cw20_addr.to_string();
+        assert_eq!(
+            transfers(&res),
+            vec![
+                ("payer".to_string(), "pair".to_string(), "1800".to_string()),
+                ("payer".to_string(), contract.clone(), "200".to_string()),
+            ]
+        );
+        assert!(res.events.iter().any(|event| event.ty == "wasm-tax_parked"));
+        assert_eq!(balance(&app, &cw20_addr, "payer"), Uint128::new(8000));
+        assert_eq!(balance(&app, &cw20_addr, "pair"), Uint128::new(1800));
+        assert_eq!(balance(&app, &cw20_addr, &contract), Uint128::new(200));
+        assert_eq!(
+            escrowed_tax(&app, &cw20_addr, "proceeds"),
+            Uint128::new(200)
+        );
+        let res: AllEscrowedTaxResponse = app
+            .wrap()
+            .query_wasm_smart(
+                &cw20_addr,
+                &QueryMsg::AllEscrowedTax {
+                    start_after: None,
+                    limit: None,
+                },
+            )
+            .unwrap();
+        assert_eq!(
+            res.escrowed,
+            vec![EscrowedTaxInfo {
+                recipient: "proceeds".to_string(),
+                amount: Uint128::new(200),
+            }]
+        );
+
+        // flushing while the settlement still fails parks the tax again
+        let flush = ExecuteMsg::FlushEscrowedTax {
+            recipient: "proceeds".to_string(),
+        };
+        execute(&mut app, &cw20_addr, "anyone", &flush).unwrap();
+        assert_eq!(
+            escrowed_tax(&app, &cw20_addr, "proceeds"),
+            Uint128::new(200)
+        );
+        assert_eq!(balance(&app, &cw20_addr, "proceeds"), Uint128::zero());
+
+        // once the proceeds wallet may hold the tax, the flush settles it
+        let msg = ExecuteMsg::SetWhaleInfo {
+            whale_info: Some(whale_limit(5)),
+        };
+        execute(&mut app, &cw20_addr, "admin", &msg).unwrap();
+        let res = execute(&mut app, &cw20_addr, "anyone", &flush).unwrap();
+        assert_eq!(
+            transfers(&res),
+            vec![(contract.clone(), "proceeds".to_string(), "200".to_string())]
+        );
+        assert_eq!(escrowed_tax(&app, &cw20_addr, "proceeds"), Uint128::zero());
+        assert_eq!(balance(&app, &cw20_addr, "proceeds"), Uint128::new(200));
+        assert_eq!(balance(&app, &cw20_addr, &contract), Uint128::zero());
+
+        let err = execute(&mut app, &cw20_addr, "anyone", &flush).unwrap_err();
+        assert_eq!(err, ContractError::NoEscrowedTax {});
+    }
+
+    #[test]
+    fn parked_referral_share_is_earned_once_settled() {
+        let (mut app, cw20_addr) = setup(Some(whale_limit(1)));
+        let msg = ExecuteMsg::SetReferrer {
+            referrer: "referrer".to_string(),
+        };
+        execute(&mut app, &cw20_addr, "payer", &msg).unwrap();
+        let msg = ExecuteMsg::SetTaxSettlementPolicy {
+            policy: TaxSettlementPolicy::Escrow,
+        };
+        execute(&mut app, &cw20_addr, "admin", &msg).unwrap();
+        let earnings = |app: &App| {
+            let msg = QueryMsg::ReferralEarnings {
+                address: "referrer".to_string(),
+            };
+            let res: ReferralEarningsResponse =
+                app.wrap().query_wasm_smart(&cw20_addr, &msg).unwrap();
+            res.earnings
+        };
+
+        // the referral share of 120 exceeds the whale limit of 100 and is parked
+        let msg = ExecuteMsg::Transfer {
+            recipient: "pair".to_string(),
+            amount: Uint128::new(4000),
+        };
+        execute(&mut app, &cw20_addr, "payer", &msg).unwrap();
+        assert_eq!(
+            escrowed_tax(&app, &cw20_addr, "referrer"),
+            Uint128::new(120)
+        );
+        assert_eq!(earnings(&app), Uint128::zero());
+
+        let msg = ExecuteMsg::SetWhaleInfo {
+            whale_info: Some(whale_limit(5)),
+        };
+        execute(&mut app, &cw20_addr, "admin", &msg).unwrap();
+        let flush = ExecuteMsg::FlushEscrowedTax {
+            recipient: "referrer".to_string(),
+        };
+        execute(&mut app, &cw20_addr, "anyone", &flush).unwrap();
+        assert_eq!(balance(&app, &cw20_addr, "referrer"), Uint128::new(120));
+        assert_eq!(earnings(&app), Uint128::new(120));
+
+        // the proceeds share was no referral share
+        let flush = ExecuteMsg::FlushEscrowedTax {
+            recipient: "proceeds".to_string(),
+        };
+        execute(&mut app, &cw20_addr, "anyone", &flush).unwrap();
+        assert_eq!(earnings(&app), Uint128::new(120));
+    }
+}
@@ -1,10 +1,14 @@
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Addr, Uint128};
+use cosmwasm_std::{Addr, BlockInfo, Decimal, Empty, Timestamp, Uint128};
 use cw_storage_plus::{Item, Map};
 
-use cw20::{AllowanceResponse, Logo, MarketingInfoResponse};
+use cw20::{AllowanceResponse, Expiration, Logo, MarketingInfoResponse};
 
-use crate::tax::TaxMap;
+use crate::ContractError;
+
+use crate::approval::{ApprovalConfig, Proposal};
+use crate::settlement::{TaxSettlement, TaxSettlementPolicy};
+use crate::tax::{TaxMap, TaxMapVersion, TaxRateLimit, TaxRateWindow, VolumeBucket};
 use crate::whale::WhaleInfo;
 
 #[cw_serde]
@@ -38,6 +42,38 @@ impl TokenInfo {
     }
 }
 
+#[cw_serde]
+// Admin that was proposed by the current admin but
+// did not accept the role yet
+pub struct PendingAdmin {
+    pub admin: Addr,
+    pub expires: Expiration,
+}
+
+impl PendingAdmin {
+    pub fn new(
+        admin: Addr,
+        expires: Option<Expiration>,
+        block: &BlockInfo,
+    ) -> Result<Self, ContractError> {
+        let expires = expires.unwrap_or_default();
+        if expires.is_expired(block) {
+            return Err(ContractError::InvalidExpiration {});
+        }
+        Ok(PendingAdmin { admin, expires })
+    }
+
+    pub fn assert_can_accept(&self, sender: &Addr, block: &BlockInfo) -> Result<(), ContractError> {
+        if self.admin != sender {
+            return Err(ContractError::Unauthorized {});
+        }
+        if self.expires.is_expired(block) {
+            return Err(ContractError::AdminProposalExpired {});
+        }
+        Ok(())
+    }
+}
+
 pub const TOKEN_INFO: Item<TokenInfo> = Item::new("token_info");
 pub const MARKETING_INFO: Item<MarketingInfoResponse> = Item::new("marketing_info");
 pub const LOGO: Item<Logo> = Item::new("logo");
@@ -50,9 +86,52 @@ pub const ALLOWANCES_SPENDER: Map<(&Addr, &Addr), AllowanceResponse> =
 // specific for TAXED token
 pub const TAX_INFO: Item<TaxMap> = Item::new("tax_info");
 
+// every accepted tax map by version, and the version that is active by height
+pub const TAX_MAP_VERSION: Item<u64> = Item::new("tax_map_version");
+pub const TAX_MAP_HISTORY: Map<u64, TaxMapVersion> = Map::new("tax_map_history");
+pub const TAX_MAP_HEIGHTS: Map<u64, u64> = Map::new("tax_map_heights");
+
+// hard ceiling for every tax rate in the tax map. Once set it can only be lowered
+pub const MAX_TAX_RATE: Item<Decimal> = Item::new("max_tax_rate");
+
+// limits how fast tax rates may change and the epoch the last change fell into
+pub const TAX_RATE_LIMIT: Item<TaxRateLimit> = Item::new("tax_rate_limit");
+pub const TAX_RATE_WINDOW: Item<TaxRateWindow> = Item::new("tax_rate_window");
+
+// average time at which each account acquired its balance, see `record_acquisition`
+pub const ACQUIRED_AT: Map<&Addr, Timestamp> = Map::new("acquired_at");
+
+// outgoing volume of each account in hourly buckets, see `record_outgoing_volume`
+pub const OUTGOING_VOLUME: Map<&Addr, Vec<VolumeBucket>> = Map::new("outgoing_volume");
+
 // anti whale measures
 pub const ANTI_WHALE_INFO: Item<WhaleInfo> = Item::new("whale_info");
 
+// admins that were proposed but did not accept yet
+pub const PENDING_TAX_ADMIN: Item<PendingAdmin> = Item::new("pending_tax_admin");
+pub const PENDING_WHALE_ADMIN: Item<PendingAdmin> = Item::new("pending_whale_admin");
+
+// signers that have to approve configuration changes and their proposals
+pub const APPROVAL_CONFIG: Item<ApprovalConfig> = Item::new("approval_config");
+pub const PROPOSAL_COUNT: Item<u64> = Item::new("proposal_count");
+pub const PROPOSALS: Map<u64, Proposal> = Map::new("proposals");
+
+// referrer of each referred account and the tax share each referrer earned in total
+pub const REFERRERS: Map<&Addr, Addr> = Map::new("referrers");
+pub const REFERRAL_EARNINGS: Map<&Addr, Uint128> = Map::new("referral_earnings");
+
+// contracts that get the extended receive message including the tax of a send
+pub const EXTENDED_RECEIVERS: Map<&Addr, Empty> = Map::new("extended_receivers");
+
+// tax shares dispatched for settlement that await their reply, what happens if
+// a settlement fails and the tax parked in escrow per recipient, along with the
+// part of it that is a referral share
+pub const SETTLEMENT_COUNT: Item<u64> = Item::new("settlement_count");
+pub const PENDING_SETTLEMENTS: Map<u64, TaxSettlement> = Map::new("pending_settlements");
+pub const TAX_SETTLEMENT_POLICY: Item<TaxSettlementPolicy> = Item::new("tax_settlement_policy");
+pub const ESCROWED_TAX: Map<&Addr, Uint128> = Map::new("escrowed_tax");
+pub const ESCROWED_REFERRAL: Map<&Addr, Uint128> = Map::new("escrowed_referral");
+
 // specific only for migration from Terraport Tokens
 pub mod migrate_v1 {
     use std::str::FromStr;
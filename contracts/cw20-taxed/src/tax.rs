@@ -1,12 +1,80 @@
 use crate::error::ContractError;
+use crate::state::{
+    ACQUIRED_AT, BALANCES, OUTGOING_VOLUME, TAX_INFO, TAX_MAP_HEIGHTS, TAX_MAP_HISTORY,
+    TAX_MAP_VERSION, TAX_RATE_LIMIT, TAX_RATE_WINDOW,
+};
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Addr, Decimal, QuerierWrapper, StdError, StdResult, Uint128};
+use cosmwasm_std::{
+    coin, coins, from_json, Addr, Api, BankMsg, Binary, BlockInfo, Coin, Decimal, Deps, Env,
+    StdError, StdResult, Storage, Timestamp, Uint128, Uint256,
+};
 use schemars::JsonSchema;
+use serde::de::IgnoredAny;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Everything a tax condition may look at to decide on the tax
+pub struct TaxContext<'a> {
+    pub deps: Deps<'a>,
+    pub env: &'a Env,
+    /// Receiver of the transfer being taxed, if known
+    pub dst: Option<Addr>,
+    /// Gross amount of the transfer being taxed
+    pub amount: Uint128,
+    /// Hook message of sends
+    pub msg: Option<Binary>,
+    /// Holder whose balance decides the discount, if not the source
+    pub payer: Option<Addr>,
+    /// Whether an address holds an NFT of a collection, queried at most once
+    /// per execution
+    pub nft_holders: Rc<RefCell<HashMap<(Addr, Addr), bool>>>,
+}
+
+impl<'a> TaxContext<'a> {
+    pub fn new(deps: Deps<'a>, env: &'a Env) -> Self {
+        TaxContext {
+            deps,
+            env,
+            dst: None,
+            amount: Uint128::zero(),
+            msg: None,
+            payer: None,
+            nft_holders: Rc::default(),
+        }
+    }
+
+    /// The same context for a send with the given hook message
+    pub fn with_hook(mut self, msg: &Binary) -> Self {
+        self.msg = Some(msg.clone());
+        self
+    }
+
+    /// The same context for tokens taken from `payer` by someone else
+    pub fn with_payer(mut self, payer: &Addr) -> Self {
+        self.payer = Some(payer.clone());
+        self
+    }
+
+    /// The same context for a transfer of `amount` to `dst`
+    pub fn with_transfer(&self, dst: &Addr, amount: Uint128) -> Self {
+        TaxContext {
+            deps: self.deps,
+            env: self.env,
+            dst: Some(dst.clone()),
+            amount,
+            msg: self.msg.clone(),
+            payer: self.payer.clone(),
+            nft_holders: self.nft_holders.clone(),
+        }
+    }
+}
 
 trait TaxDeductible {
-    fn is_taxed(&self, q: &QuerierWrapper, addr: Addr) -> bool;
-    fn tax_rate(&self, q: &QuerierWrapper, addr: Addr) -> Decimal;
+    fn is_taxed(&self, ctx: &TaxContext, addr: Addr) -> bool;
+    fn tax_rate(&self, ctx: &TaxContext, addr: Addr) -> Decimal;
 }
 
 #[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
@@ -14,35 +82,77 @@ pub enum TaxCondition {
     Never(TaxNeverCondition),
     Always(TaxAlwaysCondition),
     ContractCode(TaxContractCodeCondition),
+    HoldingPeriod(TaxHoldingPeriodCondition),
+    Volume(TaxVolumeCondition),
+    Oracle(TaxOracleCondition),
+    PriceImpact(TaxPriceImpactCondition),
+    SendHook(TaxSendHookCondition),
+    NftHolder(TaxNftHolderCondition),
+    Addresses(TaxAddressCondition),
 }
 
 impl TaxCondition {
-    pub fn is_taxed(&self, q: &QuerierWrapper, addr: Addr) -> bool {
+    /// Condition that never taxes, used for omitted conditions
+    pub fn never() -> Self {
+        TaxCondition::Never(TaxNeverCondition {})
+    }
+
+    pub fn is_taxed(&self, ctx: &TaxContext, addr: Addr) -> bool {
         match self {
-            TaxCondition::Never(c) => c.is_taxed(q, addr),
-            TaxCondition::Always(c) => c.is_taxed(q, addr),
-            TaxCondition::ContractCode(c) => c.is_taxed(q, addr),
+            TaxCondition::Never(c) => c.is_taxed(ctx, addr),
+            TaxCondition::Always(c) => c.is_taxed(ctx, addr),
+            TaxCondition::ContractCode(c) => c.is_taxed(ctx, addr),
+            TaxCondition::HoldingPeriod(c) => c.is_taxed(ctx, addr),
+            TaxCondition::Volume(c) => c.is_taxed(ctx, addr),
+            TaxCondition::Oracle(c) => c.is_taxed(ctx, addr),
+            TaxCondition::PriceImpact(c) => c.is_taxed(ctx, addr),
+            TaxCondition::SendHook(c) => c.is_taxed(ctx, addr),
+            TaxCondition::NftHolder(c) => c.is_taxed(ctx, addr),
+            TaxCondition::Addresses(c) => c.is_taxed(ctx, addr),
         }
     }
 
-    pub fn tax_rate(&self, q: &QuerierWrapper, addr: Addr) -> Decimal {
+    pub fn tax_rate(&self, ctx: &TaxContext, addr: Addr) -> Decimal {
         match self {
-            TaxCondition::Never(c) => c.tax_rate(q, addr),
-            TaxCondition::Always(c) => c.tax_rate(q, addr),
-            TaxCondition::ContractCode(c) => c.tax_rate(q, addr),
+            TaxCondition::Never(c) => c.tax_rate(ctx, addr),
+            TaxCondition::Always(c) => c.tax_rate(ctx, addr),
+            TaxCondition::ContractCode(c) => c.tax_rate(ctx, addr),
+            TaxCondition::HoldingPeriod(c) => c.tax_rate(ctx, addr),
+            TaxCondition::Volume(c) => c.tax_rate(ctx, addr),
+            TaxCondition::Oracle(c) => c.tax_rate(ctx, addr),
+            TaxCondition::PriceImpact(c) => c.tax_rate(ctx, addr),
+            TaxCondition::SendHook(c) => c.tax_rate(ctx, addr),
+            TaxCondition::NftHolder(c) => c.tax_rate(ctx, addr),
+            TaxCondition::Addresses(c) => c.tax_rate(ctx, addr),
+        }
+    }
+
+    /// Portion of `amount` the tax rate applies to
+    pub fn taxable_amount(&self, ctx: &TaxContext, addr: &Addr, amount: Uint128) -> Uint128 {
+        match self {
+            TaxCondition::Volume(c) => {
+                let headroom = c
+                    .headroom(ctx.deps.storage, &ctx.env.block, addr)
+                    .unwrap_or_default();
+                amount.saturating_sub(headroom)
+            }
+            _ => amount,
         }
     }
 
     fn tax_deduction(
         &self,
-        q: &QuerierWrapper,
+        ctx: &TaxContext,
         addr: Addr,
         amount: Uint128,
     ) -> Result<(Uint128, Uint128), ContractError> {
-        let tax_rate = self.tax_rate(q, addr);
+        let taxable = self.taxable_amount(ctx, &addr, amount);
+        let tax_rate = self.tax_rate(ctx, addr);
         let gross_amount = Decimal::from_atomics(amount, 0)
             .map_err(|_| ContractError::Std(StdError::generic_err("Invalid amount")))?;
-        let tax = tax_rate.checked_mul(gross_amount).unwrap();
+        let taxable_amount = Decimal::from_atomics(taxable, 0)
+            .map_err(|_| ContractError::Std(StdError::generic_err("Invalid amount")))?;
+        let tax = tax_rate.checked_mul(taxable_amount).unwrap();
         let net_amount = gross_amount.checked_sub(tax).map_err(|_| {
             ContractError::Std(StdError::generic_err("Taxed amount cannot be negative"))
         })?;
@@ -53,15 +163,15 @@ impl TaxCondition {
         Ok((net_out, net_tax))
     }
 
-    pub fn get_tax(&self, q: &QuerierWrapper, addr: Addr, amount: Uint128) -> Uint128 {
-        match self.tax_deduction(q, addr, amount) {
+    pub fn get_tax(&self, ctx: &TaxContext, addr: Addr, amount: Uint128) -> Uint128 {
+        match self.tax_deduction(ctx, addr, amount) {
             Ok((_, tax)) => tax,
             Err(_) => Uint128::zero(),
         }
     }
 
-    pub fn get_net(&self, q: &QuerierWrapper, addr: Addr, amount: Uint128) -> Uint128 {
-        match self.tax_deduction(q, addr, amount) {
+    pub fn get_net(&self, ctx: &TaxContext, addr: Addr, amount: Uint128) -> Uint128 {
+        match self.tax_deduction(ctx, addr, amount) {
             Ok((net, _)) => net,
             Err(_) => Uint128::zero(),
         }
@@ -72,15 +182,178 @@ impl TaxCondition {
             TaxCondition::Never(x) => x.validate(),
             TaxCondition::Always(x) => x.validate(),
             TaxCondition::ContractCode(x) => x.validate(),
+            TaxCondition::HoldingPeriod(x) => x.validate(),
+            TaxCondition::Volume(x) => x.validate(),
+            TaxCondition::Oracle(x) => x.validate(),
+            TaxCondition::PriceImpact(x) => x.validate(),
+            TaxCondition::SendHook(x) => x.validate(),
+            TaxCondition::NftHolder(x) => x.validate(),
+            TaxCondition::Addresses(x) => x.validate(),
+        }
+    }
+
+    /// Every rate this condition can charge, in a fixed order per kind of condition
+    pub fn tax_rates(&self) -> Vec<Decimal> {
+        match self {
+            TaxCondition::Never(_) => vec![Decimal::zero()],
+            TaxCondition::Always(x) => vec![x.tax_rate],
+            TaxCondition::ContractCode(x) => vec![x.tax_rate],
+            TaxCondition::HoldingPeriod(x) => std::iter::once(x.tax_rate)
+                .chain(x.tiers.iter().map(|tier| tier.tax_rate))
+                .collect(),
+            TaxCondition::Volume(x) => vec![x.tax_rate],
+            TaxCondition::Oracle(x) => [x.tax_rate, x.fallback_rate]
+                .into_iter()
+                .chain(x.bands.iter().map(|band| band.tax_rate))
+                .collect(),
+            TaxCondition::PriceImpact(x) => x.curve.iter().map(|step| step.tax_rate).collect(),
+            TaxCondition::SendHook(x) => vec![x.swap_rate, x.default_rate],
+            TaxCondition::NftHolder(x) => vec![x.tax_rate],
+            TaxCondition::Addresses(x) => vec![x.tax_rate],
+        }
+    }
+
+    /// Highest rate this condition can ever charge, independent of the address
+    pub fn max_tax_rate(&self) -> Decimal {
+        match self {
+            TaxCondition::Never(_) => Decimal::zero(),
+            TaxCondition::Always(x) => x.tax_rate,
+            TaxCondition::ContractCode(x) => x.tax_rate,
+            TaxCondition::HoldingPeriod(x) => x
+                .tiers
+                .iter()
+                .map(|tier| tier.tax_rate)
+                .fold(x.tax_rate, Decimal::max),
+            TaxCondition::Volume(x) => x.tax_rate,
+            TaxCondition::Oracle(x) => x
+                .bands
+                .iter()
+                .map(|band| band.tax_rate)
+                .fold(x.tax_rate.max(x.fallback_rate), Decimal::max),
+            TaxCondition::PriceImpact(x) => x
+                .curve
+                .iter()
+                .map(|step| step.tax_rate)
+                .max()
+                .unwrap_or_default(),
+            TaxCondition::SendHook(x) => x.swap_rate.max(x.default_rate),
+            TaxCondition::NftHolder(x) => x.tax_rate,
+            TaxCondition::Addresses(x) => x.tax_rate,
+        }
+    }
+
+    /// Addresses the condition relies on
+    pub fn addresses(&self) -> Vec<&Addr> {
+        match self {
+            TaxCondition::Oracle(x) => vec![&x.oracle],
+            TaxCondition::NftHolder(x) => vec![&x.collection],
+            TaxCondition::Addresses(x) => x.addresses.iter().collect(),
+            _ => vec![],
         }
     }
 }
 
 #[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
 pub struct TaxInfo {
+    #[serde(default = "TaxCondition::never")]
     pub src_cond: TaxCondition,
+    #[serde(default = "TaxCondition::never")]
     pub dst_cond: TaxCondition,
+    #[serde(default = "no_proceeds")]
     pub proceeds: Addr,
+    /// May change this tax info in addition to the tax map admin
+    #[serde(default)]
+    pub admin: Option<Addr>,
+    /// Discounts on the tax for holders with large balances
+    #[serde(default)]
+    pub discounts: Vec<TaxDiscountTier>,
+    /// Share of the tax that goes to the referrer of the payer instead of the proceeds
+    #[serde(default)]
+    pub referral_share: Decimal,
+    /// Whether the tax is taken out of the amount or charged on top of it
+    #[serde(default)]
+    pub mode: TaxMode,
+    /// Native coin the tax can be paid in instead of tokens
+    #[serde(default)]
+    pub native_payment: Option<TaxNativePayment>,
+    /// Ordered rules of which the first matching one taxes the transfer. If
+    /// set, `src_cond`, `dst_cond` and `proceeds` are ignored.
+    #[serde(default)]
+    pub rules: Option<Vec<TaxRule>>,
+}
+
+/// With `Deduct` the recipient receives the amount less the tax, with `OnTop`
+/// the recipient receives the full amount and the payer pays the tax on top
+#[cw_serde]
+#[derive(Default)]
+pub enum TaxMode {
+    #[default]
+    Deduct,
+    OnTop,
+}
+
+impl std::fmt::Display for TaxMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TaxMode::Deduct => f.write_str("deduct"),
+            TaxMode::OnTop => f.write_str("on_top"),
+        }
+    }
+}
+
+fn no_proceeds() -> Addr {
+    Addr::unchecked("")
+}
+
+/// Conditions and proceeds of a single rule, they work just like those of
+/// the legacy tax info form
+#[cw_serde]
+pub struct TaxRule {
+    /// Label that tax simulations report
+    #[serde(default)]
+    pub name: Option<String>,
+    pub src_cond: TaxCondition,
+    pub dst_cond: TaxCondition,
+    pub proceeds: Addr,
+}
+
+impl TaxRule {
+    pub fn validate(&self) -> bool {
+        self.src_cond.validate() && self.dst_cond.validate() && !self.proceeds.as_str().is_empty()
+    }
+
+    /// Whether the rule charges a tax under any circumstances
+    pub fn can_tax(&self) -> bool {
+        !self.src_cond.max_tax_rate().is_zero()
+    }
+
+    pub fn is_taxed(&self, ctx: &TaxContext, src: &Addr, dst: &Addr) -> bool {
+        self.src_cond.is_taxed(ctx, src.clone())
+            && self.dst_cond.is_taxed(ctx, dst.clone())
+            && self.proceeds != dst
+    }
+}
+
+/// Outcome of taxing a single transfer
+#[derive(Debug, PartialEq)]
+pub struct TaxDeduction {
+    /// Amount the recipient receives
+    pub net: Uint128,
+    /// Amount taken from the payer
+    pub gross: Uint128,
+    pub tax: Uint128,
+    /// Wallet the tax goes to
+    pub proceeds: Addr,
+    /// Index of the rule that matched, if the tax info has rules
+    pub rule: Option<usize>,
+}
+
+/// Holders with a balance of at least `min_balance` get `discount` off the
+/// tax they pay
+#[cw_serde]
+pub struct TaxDiscountTier {
+    pub min_balance: Uint128,
+    pub discount: Decimal,
 }
 
 #[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
@@ -89,6 +362,7 @@ pub struct TaxMap {
     pub on_transfer_from: TaxInfo,
     pub on_send: TaxInfo,
     pub on_send_from: TaxInfo,
+    /// Super admin that controls all actions and the per-action admins
     pub admin: Addr,
 }
 
@@ -105,15 +379,200 @@ impl Default for TaxMap {
 }
 
 impl TaxMap {
-    pub fn validate(&self) -> StdResult<()> {
-        match self.on_transfer.validate()
-            && self.on_transfer_from.validate()
-            && self.on_send.validate()
-            && self.on_send_from.validate()
-        {
-            true => Ok(()),
-            false => Err(StdError::generic_err(String::from("invalid tax map"))),
+    /// Validates all tax infos. If a `max_tax_rate` ceiling is given, every rate
+    /// of every condition must not exceed it. All problems found are reported
+    /// at once.
+    pub fn validate(&self, max_tax_rate: Option<Decimal>) -> Result<(), ContractError> {
+        into_result(self.errors(max_tax_rate))
+    }
+
+    /// Like `validate`, but additionally checks every address the tax map
+    /// relies on. Tax maps supplied by users have to pass this check.
+    pub fn validate_with_api(
+        &self,
+        api: &dyn Api,
+        max_tax_rate: Option<Decimal>,
+    ) -> Result<(), ContractError> {
+        let mut errors = self.errors(max_tax_rate);
+        errors.extend(self.address_errors(api));
+        into_result(errors)
+    }
+
+    fn errors(&self, max_tax_rate: Option<Decimal>) -> Vec<ContractError> {
+        let mut errors = vec![];
+        for (action, tax_info) in self.tax_infos() {
+            errors.extend(tax_info.errors(&action));
+            let Some(max) = max_tax_rate else {
+                continue;
+            };
+            let sides = [
+                ("src", tax_info.max_src_tax_rate()),
+                ("dst", tax_info.max_dst_tax_rate()),
+            ];
+            for (side, rate) in sides {
+                if rate > max && rate <= Decimal::one() {
+                    errors.push(ContractError::TaxRateExceedsMax {
+                        action: action.to_string(),
+                        side: side.to_string(),
+                        rate,
+                        max,
+                    });
+                }
+            }
         }
+        errors
+    }
+
+    /// Invalid addresses of the admins, the proceeds wallets of rules that can
+    /// tax and the conditions. Missing proceeds are reported by `validate`.
+    fn address_errors(&self, api: &dyn Api) -> Vec<ContractError> {
+        let mut addresses: Vec<(String, Addr)> = vec![];
+        if !self.admin.as_str().is_empty() {
+            addresses.push(("admin".to_string(), self.admin.clone()));
+        }
+        for (action, tax_info) in self.tax_infos() {
+            if let Some(admin) = &tax_info.admin {
+                addresses.push((format!("{}.admin", action), admin.clone()));
+            }
+            if let Some(TaxNativePrice::Oracle { oracle, .. }) =
+                tax_info.native_payment.as_ref().map(|x| &x.price)
+            {
+                addresses.push((format!("{}.native_payment", action), oracle.clone()));
+            }
+            let prefixes: Vec<String> = match &tax_info.rules {
+                Some(rules) => (0..rules.len())
+                    .map(|i| format!("{}.rules[{}]", action, i))
+                    .collect(),
+                None => vec![action.to_string()],
+            };
+            for (prefix, rule) in prefixes.into_iter().zip(tax_info.effective_rules().iter()) {
+                for (side, cond) in [("src_cond", &rule.src_cond), ("dst_cond", &rule.dst_cond)] {
+                    for addr in cond.addresses() {
+                        addresses.push((format!("{}.{}", prefix, side), addr.clone()));
+                    }
+                }
+                if rule.can_tax() && !rule.proceeds.as_str().is_empty() {
+                    addresses.push((format!("{}.proceeds", prefix), rule.proceeds.clone()));
+                }
+            }
+        }
+        addresses
+            .into_iter()
+            .filter(|(_, addr)| api.addr_validate(addr.as_str()).is_err())
+            .map(|(field, addr)| ContractError::InvalidTaxAddress {
+                field,
+                address: addr.into_string(),
+            })
+            .collect()
+    }
+
+    /// Highest rate of any condition in any tax info
+    pub fn max_tax_rate(&self) -> Decimal {
+        self.tax_infos()
+            .iter()
+            .map(|(_, x)| x.max_tax_rate())
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Longest window of any volume condition, if outgoing volume needs to be tracked
+    pub fn volume_window(&self) -> Option<u64> {
+        self.tax_infos()
+            .iter()
+            .flat_map(|(_, x)| x.effective_rules().into_owned())
+            .flat_map(|x| [x.src_cond, x.dst_cond])
+            .filter_map(|x| match x {
+                TaxCondition::Volume(x) => Some(x.window),
+                _ => None,
+            })
+            .max()
+    }
+
+    /// All tax infos along with the action they apply to
+    pub fn tax_infos(&self) -> [(TaxAction, &TaxInfo); 4] {
+        [
+            (TaxAction::OnTransfer, &self.on_transfer),
+            (TaxAction::OnTransferFrom, &self.on_transfer_from),
+            (TaxAction::OnSend, &self.on_send),
+            (TaxAction::OnSendFrom, &self.on_send_from),
+        ]
+    }
+
+    /// Who controls the tax info of the given action
+    pub fn action_admin(&self, action: &TaxAction) -> &Addr {
+        self.tax_info(action).admin.as_ref().unwrap_or(&self.admin)
+    }
+
+    /// Ensures `sender` may turn this tax map into `new`: the super admin may
+    /// change anything but the admin itself, per-action admins only the rates
+    /// and proceeds of the actions they control.
+    pub fn assert_can_change(&self, new: &TaxMap, sender: &Addr) -> Result<(), ContractError> {
+        if self.admin == sender {
+            if self.admin != new.admin {
+                return Err(ContractError::AdminChangeNotAllowed {});
+            }
+            return Ok(());
+        }
+        let is_action_admin = self
+            .tax_infos()
+            .iter()
+            .any(|(_, x)| x.admin.as_ref() == Some(sender));
+        if !is_action_admin || self.admin != new.admin {
+            return Err(ContractError::Unauthorized {});
+        }
+        for ((_, old_info), (_, new_info)) in self.tax_infos().iter().zip(new.tax_infos()) {
+            if *old_info == new_info {
+                continue;
+            }
+            if old_info.admin.as_ref() != Some(sender) || old_info.admin != new_info.admin {
+                return Err(ContractError::Unauthorized {});
+            }
+        }
+        Ok(())
+    }
+
+    pub fn tax_info(&self, action: &TaxAction) -> &TaxInfo {
+        match action {
+            TaxAction::OnTransfer => &self.on_transfer,
+            TaxAction::OnTransferFrom => &self.on_transfer_from,
+            TaxAction::OnSend => &self.on_send,
+            TaxAction::OnSendFrom => &self.on_send_from,
+        }
+    }
+
+    pub fn tax_info_mut(&mut self, action: &TaxAction) -> &mut TaxInfo {
+        match action {
+            TaxAction::OnTransfer => &mut self.on_transfer,
+            TaxAction::OnTransferFrom => &mut self.on_transfer_from,
+            TaxAction::OnSend => &mut self.on_send,
+            TaxAction::OnSendFrom => &mut self.on_send_from,
+        }
+    }
+}
+
+/// The actions that can be taxed, one per tax info of the tax map
+#[cw_serde]
+pub enum TaxAction {
+    OnTransfer,
+    OnTransferFrom,
+    OnSend,
+    OnSendFrom,
+}
+
+impl TaxAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TaxAction::OnTransfer => "on_transfer",
+            TaxAction::OnTransferFrom => "on_transfer_from",
+            TaxAction::OnSend => "on_send",
+            TaxAction::OnSendFrom => "on_send_from",
+        }
+    }
+}
+
+impl std::fmt::Display for TaxAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
     }
 }
 
@@ -123,13 +582,148 @@ impl Default for TaxInfo {
             src_cond: TaxCondition::Never(TaxNeverCondition {}),
             dst_cond: TaxCondition::Never(TaxNeverCondition {}),
             proceeds: Addr::unchecked(""),
+            admin: None,
+            discounts: vec![],
+            referral_share: Decimal::zero(),
+            mode: TaxMode::Deduct,
+            native_payment: None,
+            rules: None,
         }
     }
 }
 
 impl TaxInfo {
     pub fn validate(&self) -> bool {
-        self.src_cond.validate() && self.dst_cond.validate()
+        // the action only labels the errors
+        self.errors(&TaxAction::OnTransfer).is_empty()
+    }
+
+    /// Every problem of this tax info when used for `action`
+    pub fn errors(&self, action: &TaxAction) -> Vec<ContractError> {
+        let mut errors = vec![];
+        let invalid = |reason: &str| ContractError::InvalidTaxInfo {
+            action: action.to_string(),
+            reason: reason.to_string(),
+        };
+
+        if self.rules.is_none()
+            && self.proceeds.as_str().is_empty()
+            && !self.src_cond.max_tax_rate().is_zero()
+        {
+            errors.push(ContractError::MissingProceeds {
+                action: action.to_string(),
+            });
+        }
+        let conds: Vec<(String, &TaxCondition)> = match &self.rules {
+            Some(rules) => {
+                if rules.is_empty() {
+                    errors.push(invalid("rules cannot be empty"));
+                }
+                if rules.iter().any(|x| x.proceeds.as_str().is_empty()) {
+                    errors.push(ContractError::MissingProceeds {
+                        action: action.to_string(),
+                    });
+                }
+                rules
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(i, x)| {
+                        [
+                            (format!("rule {} src", i), &x.src_cond),
+                            (format!("rule {} dst", i), &x.dst_cond),
+                        ]
+                    })
+                    .collect()
+            }
+            None => vec![
+                ("src".to_string(), &self.src_cond),
+                ("dst".to_string(), &self.dst_cond),
+            ],
+        };
+        for (side, cond) in conds {
+            let rate = cond.max_tax_rate();
+            if rate > Decimal::one() {
+                errors.push(ContractError::InvalidTaxRate {
+                    action: action.to_string(),
+                    side,
+                    rate,
+                });
+            } else if !cond.validate() {
+                errors.push(ContractError::InvalidTaxCondition {
+                    action: action.to_string(),
+                    side,
+                });
+            }
+        }
+
+        if self
+            .discounts
+            .iter()
+            .any(|tier| tier.discount > Decimal::one())
+        {
+            errors.push(invalid("discounts must not exceed 1"));
+        }
+        if !self
+            .discounts
+            .windows(2)
+            .all(|x| x[0].min_balance < x[1].min_balance)
+        {
+            errors.push(invalid(
+                "discount tiers must be ordered by ascending min_balance",
+            ));
+        }
+        if self.referral_share > Decimal::one() {
+            errors.push(invalid("referral share must not exceed 1"));
+        }
+        if !self.native_payment.as_ref().map_or(true, |x| x.validate()) {
+            errors.push(invalid("native payment needs a denom and a non-zero price"));
+        }
+        errors
+    }
+
+    /// Discount for a holder with the given balance, the highest tier reached applies
+    pub fn discount(&self, balance: Uint128) -> Decimal {
+        self.discounts
+            .iter()
+            .rev()
+            .find(|tier| balance >= tier.min_balance)
+            .map(|tier| tier.discount)
+            .unwrap_or_default()
+    }
+
+    pub fn max_tax_rate(&self) -> Decimal {
+        self.max_src_tax_rate().max(self.max_dst_tax_rate())
+    }
+
+    /// Highest rate of any source condition in effect
+    pub fn max_src_tax_rate(&self) -> Decimal {
+        self.effective_rules()
+            .iter()
+            .map(|x| x.src_cond.max_tax_rate())
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Highest rate of any destination condition in effect
+    pub fn max_dst_tax_rate(&self) -> Decimal {
+        self.effective_rules()
+            .iter()
+            .map(|x| x.dst_cond.max_tax_rate())
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Rules in effect, the legacy form acts as a single rule
+    pub fn effective_rules(&self) -> Cow<[TaxRule]> {
+        match &self.rules {
+            Some(rules) => Cow::Borrowed(rules),
+            None => Cow::Owned(vec![TaxRule {
+                name: None,
+                src_cond: self.src_cond.clone(),
+                dst_cond: self.dst_cond.clone(),
+                proceeds: self.proceeds.clone(),
+            }]),
+        }
     }
 }
 
@@ -153,6 +747,19 @@ impl TaxAlwaysCondition {
     }
 }
 
+/// Taxes only the listed addresses, e.g. a single pair
+#[cw_serde]
+pub struct TaxAddressCondition {
+    pub addresses: Vec<Addr>,
+    pub tax_rate: Decimal,
+}
+
+impl TaxAddressCondition {
+    pub fn validate(&self) -> bool {
+        self.tax_rate.le(&Decimal::one())
+    }
+}
+
 #[cw_serde]
 pub struct TaxContractCodeCondition {
     pub code_ids: Vec<u64>,
@@ -165,68 +772,960 @@ impl TaxContractCodeCondition {
     }
 }
 
+/// Taxes sells depending on how long the seller has been holding the tokens.
+/// The first tier whose holding period was not reached yet applies, everybody
+/// who held longer than all tiers pays `tax_rate`.
+#[cw_serde]
+pub struct TaxHoldingPeriodCondition {
+    pub tiers: Vec<TaxHoldingTier>,
+    pub tax_rate: Decimal,
+}
+
+/// Rate for holders that acquired their tokens less than `seconds` ago
+#[cw_serde]
+pub struct TaxHoldingTier {
+    pub seconds: u64,
+    pub tax_rate: Decimal,
+}
+
+impl TaxHoldingPeriodCondition {
+    pub fn validate(&self) -> bool {
+        let ascending = self.tiers.windows(2).all(|x| x[0].seconds < x[1].seconds);
+        ascending
+            && self.tax_rate.le(&Decimal::one())
+            && self.tiers.iter().all(|x| x.tax_rate.le(&Decimal::one()))
+    }
+}
+
+/// Moves the acquisition time of `addr` towards now, weighted by the credited
+/// `amount` against the balance held before. Partial sells leave it untouched
+/// and dust cannot reset the holding period of a large balance. Balances from
+/// before acquisitions were recorded count as held since genesis.
+pub fn record_acquisition(
+    storage: &mut dyn Storage,
+    block: &BlockInfo,
+    addr: &Addr,
+    amount: Uint128,
+) -> StdResult<()> {
+    if amount.is_zero() {
+        return Ok(());
+    }
+    let balance = BALANCES.may_load(storage, addr)?.unwrap_or_default();
+    let held = balance.checked_sub(amount)?;
+    let acquired_at = if held.is_zero() {
+        block.time
+    } else {
+        let prev = ACQUIRED_AT.may_load(storage, addr)?.unwrap_or_default();
+        let weighted = Uint128::from(prev.nanos()).full_mul(held)
+            + Uint128::from(block.time.nanos()).full_mul(amount);
+        let nanos = Uint128::try_from(weighted / Uint256::from(balance))?;
+        Timestamp::from_nanos(nanos.u128() as u64)
+    };
+    ACQUIRED_AT.save(storage, addr, &acquired_at)
+}
+
+/// Derives the tax rate from the token price reported by an oracle contract.
+/// The first band whose `below` price is above the current price applies,
+/// `tax_rate` applies to prices above all bands. If the oracle fails or its
+/// price is older than `max_age` seconds, `fallback_rate` applies.
+#[cw_serde]
+pub struct TaxOracleCondition {
+    pub oracle: Addr,
+    pub bands: Vec<TaxPriceBand>,
+    pub tax_rate: Decimal,
+    pub max_age: Option<u64>,
+    pub fallback_rate: Decimal,
+}
+
+/// Rate for prices below `below`
+#[cw_serde]
+pub struct TaxPriceBand {
+    pub below: Decimal,
+    pub tax_rate: Decimal,
+}
+
+/// Query the oracle contract has to answer
+#[cw_serde]
+pub enum OracleQueryMsg {
+    Price {},
+}
+
+#[cw_serde]
+pub struct OraclePriceResponse {
+    pub price: Decimal,
+    /// Required to check for stale prices if the condition sets `max_age`
+    #[serde(default)]
+    pub updated_at: Option<Timestamp>,
+}
+
+impl TaxOracleCondition {
+    pub fn validate(&self) -> bool {
+        let ascending = self.bands.windows(2).all(|x| x[0].below < x[1].below);
+        ascending
+            && self.tax_rate.le(&Decimal::one())
+            && self.fallback_rate.le(&Decimal::one())
+            && self.bands.iter().all(|x| x.tax_rate.le(&Decimal::one()))
+    }
+
+    /// Current oracle price, fails if it cannot be queried or is stale
+    pub fn price(&self, ctx: &TaxContext) -> StdResult<Decimal> {
+        query_oracle_price(ctx, &self.oracle, self.max_age)
+    }
+}
+
+/// Price reported by `oracle`, fails if it cannot be queried or is older
+/// than `max_age` seconds
+pub fn query_oracle_price(
+    ctx: &TaxContext,
+    oracle: &Addr,
+    max_age: Option<u64>,
+) -> StdResult<Decimal> {
+    let res: OraclePriceResponse = ctx
+        .deps
+        .querier
+        .query_wasm_smart(oracle, &OracleQueryMsg::Price {})?;
+    if let Some(max_age) = max_age {
+        let updated_at = res
+            .updated_at
+            .ok_or_else(|| StdError::generic_err("Oracle price has no timestamp"))?;
+        if ctx.env.block.time > updated_at.plus_seconds(max_age) {
+            return Err(StdError::generic_err("Oracle price is stale"));
+        }
+    }
+    Ok(res.price)
+}
+
+/// Lets payers settle the tax in a native coin instead of tokens
+#[cw_serde]
+pub struct TaxNativePayment {
+    pub denom: String,
+    pub price: TaxNativePrice,
+}
+
+/// Native coins per token
+#[cw_serde]
+pub enum TaxNativePrice {
+    Fixed(Decimal),
+    /// Price reported by an oracle answering `{"price": {}}`
+    Oracle {
+        oracle: Addr,
+        max_age: Option<u64>,
+    },
+}
+
+impl TaxNativePayment {
+    pub fn validate(&self) -> bool {
+        let valid_price = match &self.price {
+            TaxNativePrice::Fixed(price) => !price.is_zero(),
+            TaxNativePrice::Oracle { .. } => true,
+        };
+        !self.denom.is_empty() && valid_price
+    }
+
+    /// Native coins worth the token `tax`, rounded up
+    pub fn native_amount(&self, ctx: &TaxContext, tax: Uint128) -> StdResult<Uint128> {
+        let price = match &self.price {
+            TaxNativePrice::Fixed(price) => *price,
+            TaxNativePrice::Oracle { oracle, max_age } => {
+                query_oracle_price(ctx, oracle, *max_age)?
+            }
+        };
+        Ok(tax.mul_ceil(price))
+    }
+}
+
+/// Tax that was paid in native coins along with the bank messages paying the
+/// proceeds and refunding any overpayment
+pub struct NativeTaxPayment {
+    pub tax: Coin,
+    pub messages: Vec<BankMsg>,
+}
+
+/// Settles the tax with the native coins attached by `sender`, if the tax info
+/// accepts them. The deduction then carries no token tax anymore. Without
+/// attached coins the tax is paid in tokens as usual, coins attached to an
+/// action that does not accept them are rejected.
+pub fn pay_native_tax(
+    ctx: &TaxContext,
+    tax_info: &TaxInfo,
+    deduction: &mut TaxDeduction,
+    amount: Uint128,
+    sender: &Addr,
+    funds: &[Coin],
+) -> Result<Option<NativeTaxPayment>, ContractError> {
+    if funds.is_empty() {
+        return Ok(None);
+    }
+    let payment = match &tax_info.native_payment {
+        Some(payment) => payment,
+        None => return Err(ContractError::NativeTaxNotAccepted {}),
+    };
+    let paid = match funds {
+        [coin] if coin.denom == payment.denom => coin.amount,
+        _ => {
+            return Err(ContractError::InvalidNativeTaxFunds {
+                denom: payment.denom.clone(),
+            })
+        }
+    };
+    let due = payment.native_amount(ctx, deduction.tax)?;
+    if paid < due {
+        return Err(ContractError::InsufficientNativeTax {
+            required: due,
+            paid,
+            denom: payment.denom.clone(),
+        });
+    }
+
+    let mut messages = vec![];
+    if !due.is_zero() {
+        messages.push(BankMsg::Send {
+            to_address: deduction.proceeds.to_string(),
+            amount: coins(due.u128(), &payment.denom),
+        });
+    }
+    if paid > due {
+        messages.push(BankMsg::Send {
+            to_address: sender.to_string(),
+            amount: coins((paid - due).u128(), &payment.denom),
+        });
+    }
+    deduction.net = amount;
+    deduction.gross = amount;
+    deduction.tax = Uint128::zero();
+    Ok(Some(NativeTaxPayment {
+        tax: coin(due.u128(), &payment.denom),
+        messages,
+    }))
+}
+
+/// Taxes sells into a Terraswap/Terraport pair of this token by the price
+/// impact they cause. The rate of the last step whose `impact` is reached
+/// applies, smaller impacts are not taxed. Transfers to anything but a pair
+/// of this token are not taxed either.
+#[cw_serde]
+pub struct TaxPriceImpactCondition {
+    pub curve: Vec<TaxImpactStep>,
+}
+
+#[cw_serde]
+pub struct TaxImpactStep {
+    pub impact: Decimal,
+    pub tax_rate: Decimal,
+}
+
+/// Pool query of Terraswap/Terraport pairs
+#[cw_serde]
+pub enum PairQueryMsg {
+    Pool {},
+}
+
+// pair responses are not matched strictly, as pair versions add fields
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PoolResponse {
+    pub assets: Vec<Asset>,
+    pub total_share: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Asset {
+    pub info: AssetInfo,
+    pub amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AssetInfo {
+    Token { contract_addr: String },
+    NativeToken { denom: String },
+}
+
+impl TaxPriceImpactCondition {
+    pub fn validate(&self) -> bool {
+        let ascending = self.curve.windows(2).all(|x| x[0].impact < x[1].impact);
+        ascending
+            && self
+                .curve
+                .iter()
+                .all(|x| x.impact.le(&Decimal::one()) && x.tax_rate.le(&Decimal::one()))
+    }
+
+    /// Reserve of this token in the pool of `pair`, fails if `pair` is not a
+    /// pair of this token
+    pub fn token_reserve(&self, ctx: &TaxContext, pair: &Addr) -> StdResult<Uint128> {
+        let pool: PoolResponse = ctx
+            .deps
+            .querier
+            .query_wasm_smart(pair, &PairQueryMsg::Pool {})?;
+        pool.assets
+            .into_iter()
+            .find(|x| match &x.info {
+                AssetInfo::Token { contract_addr } => *contract_addr == ctx.env.contract.address,
+                AssetInfo::NativeToken { .. } => false,
+            })
+            .map(|x| x.amount)
+            .ok_or_else(|| StdError::generic_err("Not a pair of this token"))
+    }
+
+    /// Share the sold amount makes up of the token reserve after the swap,
+    /// which is the price impact of a constant product pool
+    pub fn price_impact(&self, ctx: &TaxContext, pair: &Addr) -> StdResult<Decimal> {
+        let reserve = self.token_reserve(ctx, pair)?;
+        let total = reserve.checked_add(ctx.amount)?;
+        if total.is_zero() {
+            return Ok(Decimal::zero());
+        }
+        Ok(Decimal::from_ratio(ctx.amount, total))
+    }
+}
+
+/// Exempts holders of at least one NFT of the cw721 `collection`, everybody
+/// else pays `tax_rate`
+#[cw_serde]
+pub struct TaxNftHolderCondition {
+    pub collection: Addr,
+    pub tax_rate: Decimal,
+}
+
+/// Tokens query of cw721 contracts
+#[cw_serde]
+pub enum Cw721QueryMsg {
+    Tokens {
+        owner: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+}
+
+#[cw_serde]
+pub struct TokensResponse {
+    pub tokens: Vec<String>,
+}
+
+impl TaxNftHolderCondition {
+    pub fn validate(&self) -> bool {
+        self.tax_rate.le(&Decimal::one())
+    }
+
+    /// Whether `addr` holds an NFT of the collection, collections that cannot
+    /// be queried exempt nobody
+    pub fn is_holder(&self, ctx: &TaxContext, addr: &Addr) -> bool {
+        let key = (self.collection.clone(), addr.clone());
+        if let Some(is_holder) = ctx.nft_holders.borrow().get(&key) {
+            return *is_holder;
+        }
+        let msg = Cw721QueryMsg::Tokens {
+            owner: addr.to_string(),
+            start_after: None,
+            limit: Some(1),
+        };
+        let is_holder = ctx
+            .deps
+            .querier
+            .query_wasm_smart::<TokensResponse>(&self.collection, &msg)
+            .map(|res| !res.tokens.is_empty())
+            .unwrap_or(false);
+        ctx.nft_holders.borrow_mut().insert(key, is_holder);
+        is_holder
+    }
+}
+
+/// Tells swaps from liquidity operations by decoding the hook message of
+/// sends. Swaps pay `swap_rate`, liquidity operations are not taxed and hook
+/// messages of unknown schema (or transfers without one) pay `default_rate`.
+#[cw_serde]
+pub struct TaxSendHookCondition {
+    pub swap_rate: Decimal,
+    pub default_rate: Decimal,
+}
+
+/// Kinds of hook messages of known DEX schemas
+#[cw_serde]
+pub enum SendHook {
+    Swap,
+    ProvideLiquidity,
+    WithdrawLiquidity,
+    Unknown,
+}
+
+// only the variant names of the known hook messages matter
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum KnownHookMsg {
+    Swap(IgnoredAny),
+    ExecuteSwapOperations(IgnoredAny),
+    ProvideLiquidity(IgnoredAny),
+    WithdrawLiquidity(IgnoredAny),
+}
+
+impl SendHook {
+    pub fn decode(msg: Option<&Binary>) -> Self {
+        match msg.map(from_json::<KnownHookMsg>) {
+            Some(Ok(KnownHookMsg::Swap(_))) | Some(Ok(KnownHookMsg::ExecuteSwapOperations(_))) => {
+                SendHook::Swap
+            }
+            Some(Ok(KnownHookMsg::ProvideLiquidity(_))) => SendHook::ProvideLiquidity,
+            Some(Ok(KnownHookMsg::WithdrawLiquidity(_))) => SendHook::WithdrawLiquidity,
+            _ => SendHook::Unknown,
+        }
+    }
+}
+
+impl TaxSendHookCondition {
+    pub fn validate(&self) -> bool {
+        self.swap_rate.le(&Decimal::one()) && self.default_rate.le(&Decimal::one())
+    }
+}
+
+/// Width of the buckets outgoing volume is tracked in
+pub const VOLUME_BUCKET_SECONDS: u64 = 60 * 60;
+/// Longest window a volume allowance may span
+pub const MAX_VOLUME_WINDOW: u64 = 7 * 24 * 60 * 60;
+
+/// Lets every address move up to `allowance` tokens within a rolling `window`
+/// (in seconds) tax free. Only the portion exceeding the allowance is taxed.
+#[cw_serde]
+pub struct TaxVolumeCondition {
+    pub allowance: Uint128,
+    pub window: u64,
+    pub tax_rate: Decimal,
+}
+
+impl TaxVolumeCondition {
+    pub fn validate(&self) -> bool {
+        self.tax_rate.le(&Decimal::one()) && self.window > 0 && self.window <= MAX_VOLUME_WINDOW
+    }
+
+    /// Tax free volume `addr` has left in the running window
+    pub fn headroom(
+        &self,
+        storage: &dyn Storage,
+        block: &BlockInfo,
+        addr: &Addr,
+    ) -> StdResult<Uint128> {
+        let used = outgoing_volume(storage, block, addr, self.window)?;
+        Ok(self.allowance.saturating_sub(used))
+    }
+}
+
+/// Outgoing volume of an address within one bucket starting at `start`
+#[cw_serde]
+pub struct VolumeBucket {
+    pub start: u64,
+    pub amount: Uint128,
+}
+
+/// Outgoing volume of `addr` in all buckets overlapping the last `window` seconds
+pub fn outgoing_volume(
+    storage: &dyn Storage,
+    block: &BlockInfo,
+    addr: &Addr,
+    window: u64,
+) -> StdResult<Uint128> {
+    let since = block.time.seconds().saturating_sub(window);
+    let buckets = OUTGOING_VOLUME.may_load(storage, addr)?.unwrap_or_default();
+    Ok(buckets
+        .iter()
+        .filter(|x| x.start + VOLUME_BUCKET_SECONDS > since)
+        .map(|x| x.amount)
+        .sum())
+}
+
+/// Adds `amount` to the outgoing volume of `addr` if any condition of the tax
+/// map tracks volume. Buckets that left the longest window are dropped.
+pub fn record_outgoing_volume(
+    storage: &mut dyn Storage,
+    block: &BlockInfo,
+    tax_map: &TaxMap,
+    addr: &Addr,
+    amount: Uint128,
+) -> StdResult<()> {
+    let window = match tax_map.volume_window() {
+        Some(x) => x,
+        None => return Ok(()),
+    };
+    let now = block.time.seconds();
+    let start = now - now % VOLUME_BUCKET_SECONDS;
+    let since = now.saturating_sub(window);
+
+    let mut buckets = OUTGOING_VOLUME.may_load(storage, addr)?.unwrap_or_default();
+    buckets.retain(|x| x.start + VOLUME_BUCKET_SECONDS > since);
+    match buckets.last_mut() {
+        Some(x) if x.start == start => x.amount += amount,
+        _ => buckets.push(VolumeBucket { start, amount }),
+    }
+    OUTGOING_VOLUME.save(storage, addr, &buckets)
+}
+
+/// A tax map as it was accepted, along with the block it became active in
+#[cw_serde]
+pub struct TaxMapVersion {
+    pub version: u64,
+    pub height: u64,
+    pub time: Timestamp,
+    pub tax_map: TaxMap,
+}
+
+/// Stores the tax map as the active one and appends it to the tax map history.
+/// Returns the version of the stored map.
+pub fn save_tax_map(
+    storage: &mut dyn Storage,
+    block: &BlockInfo,
+    tax_map: &TaxMap,
+) -> StdResult<u64> {
+    let version = TAX_MAP_VERSION.may_load(storage)?.unwrap_or_default() + 1;
+    TAX_INFO.save(storage, tax_map)?;
+    TAX_MAP_VERSION.save(storage, &version)?;
+    TAX_MAP_HISTORY.save(
+        storage,
+        version,
+        &TaxMapVersion {
+            version,
+            height: block.height,
+            time: block.time,
+            tax_map: tax_map.clone(),
+        },
+    )?;
+    TAX_MAP_HEIGHTS.save(storage, block.height, &version)?;
+    Ok(version)
+}
+
+#[cw_serde]
+pub enum TaxEpoch {
+    Blocks(u64),
+    Seconds(u64),
+}
+
+/// Limits how far any tax rate may move within one epoch
+#[cw_serde]
+pub struct TaxRateLimit {
+    pub max_delta: Decimal,
+    pub epoch: TaxEpoch,
+}
+
+/// The tax map that was in place when the running epoch started
+#[cw_serde]
+pub struct TaxRateWindow {
+    pub height: u64,
+    pub time: Timestamp,
+    pub previous: TaxMap,
+}
+
+impl TaxRateLimit {
+    pub fn validate(&self) -> StdResult<()> {
+        if self.max_delta > Decimal::one() {
+            return Err(StdError::generic_err(
+                "Maximum tax rate delta must not exceed 1",
+            ));
+        }
+        match self.epoch {
+            TaxEpoch::Blocks(0) | TaxEpoch::Seconds(0) => {
+                Err(StdError::generic_err("Tax rate epoch must not be empty"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn is_expired(&self, window: &TaxRateWindow, block: &BlockInfo) -> bool {
+        match self.epoch {
+            TaxEpoch::Blocks(blocks) => block.height >= window.height.saturating_add(blocks),
+            TaxEpoch::Seconds(seconds) => block.time >= window.time.plus_seconds(seconds),
+        }
+    }
+
+    /// Compares both maps condition by condition and fails on the first rate
+    /// that moved by more than `max_delta`
+    pub fn check(&self, old: &TaxMap, new: &TaxMap) -> Result<(), ContractError> {
+        for ((action, old_info), (_, new_info)) in old.tax_infos().iter().zip(new.tax_infos()) {
+            for (side, previous, requested) in rate_changes(old_info, new_info) {
+                let delta = previous.abs_diff(requested);
+                if delta > self.max_delta {
+                    return Err(ContractError::TaxRateChangeExceedsLimit {
+                        action: action.to_string(),
+                        side,
+                        previous,
+                        requested,
+                        max_delta: self.max_delta,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Every rate of a tax info along with the rate it changes to. Besides the
+/// highest rate per side, the conditions of the rules are compared rule by
+/// rule and the discount tiers by the highest rate they result in. Rates that
+/// only exist on one side count as a change from zero.
+fn rate_changes(old: &TaxInfo, new: &TaxInfo) -> Vec<(String, Decimal, Decimal)> {
+    let mut changes = vec![
+        (
+            "src".to_string(),
+            old.max_src_tax_rate(),
+            new.max_src_tax_rate(),
+        ),
+        (
+            "dst".to_string(),
+            old.max_dst_tax_rate(),
+            new.max_dst_tax_rate(),
+        ),
+    ];
+
+    let legacy = old.rules.is_none() && new.rules.is_none();
+    let (old_rules, new_rules) = (old.effective_rules(), new.effective_rules());
+    let never = TaxCondition::never();
+    for i in 0..old_rules.len().max(new_rules.len()) {
+        let old_rule = old_rules.get(i);
+        let new_rule = new_rules.get(i);
+        let sides = [
+            (
+                "src",
+                old_rule.map_or(&never, |x| &x.src_cond),
+                new_rule.map_or(&never, |x| &x.src_cond),
+            ),
+            (
+                "dst",
+                old_rule.map_or(&never, |x| &x.dst_cond),
+                new_rule.map_or(&never, |x| &x.dst_cond),
+            ),
+        ];
+        for (side, old_cond, new_cond) in sides {
+            let side = match legacy {
+                true => side.to_string(),
+                false => format!("rule {} {}", i, side),
+            };
+            for (previous, requested) in rate_pairs(old_cond, new_cond) {
+                changes.push((side.clone(), previous, requested));
+            }
+        }
+    }
+
+    let (old_max, new_max) = (old.max_tax_rate(), new.max_tax_rate());
+    let discounted = |max: Decimal, tier: Option<&TaxDiscountTier>| {
+        max * Decimal::one().saturating_sub(tier.map(|x| x.discount).unwrap_or_default())
+    };
+    for i in 0..old.discounts.len().max(new.discounts.len()) {
+        changes.push((
+            format!("discount {}", i),
+            discounted(old_max, old.discounts.get(i)),
+            discounted(new_max, new.discounts.get(i)),
+        ));
+    }
+    changes
+}
+
+/// Pairs the rates of a condition before and after a change. Rates of the same
+/// kind of condition are paired by position, otherwise every previous rate is
+/// paired with every requested one.
+fn rate_pairs(old: &TaxCondition, new: &TaxCondition) -> Vec<(Decimal, Decimal)> {
+    let (old_rates, new_rates) = (old.tax_rates(), new.tax_rates());
+    if std::mem::discriminant(old) == std::mem::discriminant(new) {
+        let rate = |rates: &[Decimal], i: usize| rates.get(i).copied().unwrap_or_default();
+        return (0..old_rates.len().max(new_rates.len()))
+            .map(|i| (rate(&old_rates, i), rate(&new_rates, i)))
+            .collect();
+    }
+    old_rates
+        .iter()
+        .flat_map(|previous| {
+            new_rates
+                .iter()
+                .map(move |requested| (*previous, *requested))
+        })
+        .collect()
+}
+
+/// Enforces the configured rate limit (if any) on a tax map change and keeps
+/// track of the epoch the change falls into
+pub fn assert_tax_rate_change(
+    storage: &mut dyn Storage,
+    block: &BlockInfo,
+    curr: &TaxMap,
+    new: &TaxMap,
+) -> Result<(), ContractError> {
+    if let Some(window) = check_tax_rate_change(storage, block, curr, new)? {
+        TAX_RATE_WINDOW.save(storage, &window)?;
+    }
+    Ok(())
+}
+
+/// Checks a tax map change against the rate limit without recording it and
+/// returns the epoch window the change falls into, if rates are limited
+pub fn check_tax_rate_change(
+    storage: &dyn Storage,
+    block: &BlockInfo,
+    curr: &TaxMap,
+    new: &TaxMap,
+) -> Result<Option<TaxRateWindow>, ContractError> {
+    let limit = match TAX_RATE_LIMIT.may_load(storage)? {
+        Some(x) => x,
+        None => return Ok(None),
+    };
+    let window = match TAX_RATE_WINDOW.may_load(storage)? {
+        Some(x) if !limit.is_expired(&x, block) => x,
+        _ => TaxRateWindow {
+            height: block.height,
+            time: block.time,
+            previous: curr.clone(),
+        },
+    };
+    limit.check(&window.previous, new)?;
+    Ok(Some(window))
+}
+
 impl TaxInfo {
     pub fn deduct_tax(
         &self,
-        q: &QuerierWrapper,
+        ctx: &TaxContext,
         src: Addr,
         dst: Addr,
         amount: Uint128,
     ) -> Result<(Uint128, Uint128), ContractError> {
-        let is_taxed = self.src_cond.is_taxed(q, src.clone())
-            && self.dst_cond.is_taxed(q, dst.clone())
-            && self.proceeds != dst;
-        match is_taxed {
-            true => self.src_cond.tax_deduction(q, src, amount),
-            false => Ok((amount, Uint128::zero())),
+        self.deduct(ctx, src, dst, amount)
+            .map(|deduction| (deduction.net, deduction.tax))
+    }
+
+    /// Taxes the transfer according to the first matching rule
+    pub fn deduct(
+        &self,
+        ctx: &TaxContext,
+        src: Addr,
+        dst: Addr,
+        amount: Uint128,
+    ) -> Result<TaxDeduction, ContractError> {
+        let ctx = &ctx.with_transfer(&dst, amount);
+        let rules = self.effective_rules();
+        let (index, rule) = match rules
+            .iter()
+            .enumerate()
+            .find(|(_, rule)| rule.is_taxed(ctx, &src, &dst))
+        {
+            Some(x) => x,
+            None => {
+                return Ok(TaxDeduction {
+                    net: amount,
+                    gross: amount,
+                    tax: Uint128::zero(),
+                    proceeds: self.proceeds.clone(),
+                    rule: None,
+                })
+            }
+        };
+        let (_, mut tax) = rule.src_cond.tax_deduction(ctx, src.clone(), amount)?;
+
+        // discounts apply on the balance of the payer before the transfer
+        if !self.discounts.is_empty() && !tax.is_zero() {
+            let payer = ctx.payer.as_ref().unwrap_or(&src);
+            let balance = BALANCES
+                .may_load(ctx.deps.storage, payer)?
+                .unwrap_or_default();
+            let discount = tax.mul_floor(self.discount(balance));
+            tax = tax.checked_sub(discount).map_err(StdError::overflow)?;
         }
+
+        let (net, gross) = match self.mode {
+            TaxMode::Deduct => (amount.checked_sub(tax).map_err(StdError::overflow)?, amount),
+            TaxMode::OnTop => (amount, amount.checked_add(tax).map_err(StdError::overflow)?),
+        };
+        Ok(TaxDeduction {
+            net,
+            gross,
+            tax,
+            proceeds: rule.proceeds.clone(),
+            rule: self.rules.as_ref().map(|_| index),
+        })
     }
 }
 
+/// Takes the gross amount of a transfer of `amount` from the payer. With tax
+/// on top, a short balance fails naming the gross amount that is required.
+pub fn debit_payer(
+    storage: &mut dyn Storage,
+    payer: &Addr,
+    amount: Uint128,
+    gross: Uint128,
+) -> Result<(), ContractError> {
+    let balance = BALANCES.may_load(storage, payer)?.unwrap_or_default();
+    if gross > amount && balance < gross {
+        return Err(ContractError::InsufficientBalance {
+            required: gross,
+            balance,
+        });
+    }
+    let balance = balance.checked_sub(gross).map_err(StdError::overflow)?;
+    BALANCES.save(storage, payer, &balance)?;
+    Ok(())
+}
+
 impl TaxDeductible for TaxNeverCondition {
-    fn is_taxed(&self, _: &QuerierWrapper, _addr: Addr) -> bool {
+    fn is_taxed(&self, _: &TaxContext, _addr: Addr) -> bool {
         false
     }
 
-    fn tax_rate(&self, _: &QuerierWrapper, _addr: Addr) -> Decimal {
+    fn tax_rate(&self, _: &TaxContext, _addr: Addr) -> Decimal {
         Decimal::zero()
     }
 }
 
 impl TaxDeductible for TaxAlwaysCondition {
-    fn is_taxed(&self, _: &QuerierWrapper, _addr: Addr) -> bool {
+    fn is_taxed(&self, _: &TaxContext, _addr: Addr) -> bool {
         true
     }
 
-    fn tax_rate(&self, _: &QuerierWrapper, _addr: Addr) -> Decimal {
+    fn tax_rate(&self, _: &TaxContext, _addr: Addr) -> Decimal {
         self.tax_rate
     }
 }
 
+impl TaxDeductible for TaxAddressCondition {
+    fn is_taxed(&self, _: &TaxContext, addr: Addr) -> bool {
+        self.addresses.contains(&addr)
+    }
+
+    fn tax_rate(&self, ctx: &TaxContext, addr: Addr) -> Decimal {
+        if self.is_taxed(ctx, addr) {
+            self.tax_rate
+        } else {
+            Decimal::zero()
+        }
+    }
+}
+
 impl TaxDeductible for TaxContractCodeCondition {
-    fn is_taxed(&self, q: &QuerierWrapper, addr: Addr) -> bool {
-        let info = q.query_wasm_contract_info(addr);
+    fn is_taxed(&self, ctx: &TaxContext, addr: Addr) -> bool {
+        let info = ctx.deps.querier.query_wasm_contract_info(addr);
         match info {
             Ok(info) => self.code_ids.contains(&info.code_id),
             Err(_) => false,
         }
     }
 
-    fn tax_rate(&self, qw: &QuerierWrapper, addr: Addr) -> Decimal {
-        if self.is_taxed(qw, addr.clone()) {
-            self.tax_rate
-        } else {
-            Decimal::zero()
-        }
+    fn tax_rate(&self, ctx: &TaxContext, addr: Addr) -> Decimal {
+        if self.is_taxed(ctx, addr.clone()) {
+            self.tax_rate
+        } else {
+            Decimal::zero()
+        }
+    }
+}
+
+impl TaxDeductible for TaxNftHolderCondition {
+    fn is_taxed(&self, ctx: &TaxContext, addr: Addr) -> bool {
+        !self.is_holder(ctx, &addr)
+    }
+
+    fn tax_rate(&self, ctx: &TaxContext, addr: Addr) -> Decimal {
+        if self.is_holder(ctx, &addr) {
+            Decimal::zero()
+        } else {
+            self.tax_rate
+        }
+    }
+}
+
+impl TaxDeductible for TaxSendHookCondition {
+    fn is_taxed(&self, ctx: &TaxContext, addr: Addr) -> bool {
+        !self.tax_rate(ctx, addr).is_zero()
+    }
+
+    fn tax_rate(&self, ctx: &TaxContext, _addr: Addr) -> Decimal {
+        match SendHook::decode(ctx.msg.as_ref()) {
+            SendHook::Swap => self.swap_rate,
+            SendHook::ProvideLiquidity | SendHook::WithdrawLiquidity => Decimal::zero(),
+            SendHook::Unknown => self.default_rate,
+        }
+    }
+}
+
+impl TaxDeductible for TaxPriceImpactCondition {
+    fn is_taxed(&self, ctx: &TaxContext, _addr: Addr) -> bool {
+        match &ctx.dst {
+            Some(pair) => self.token_reserve(ctx, pair).is_ok(),
+            None => false,
+        }
+    }
+
+    fn tax_rate(&self, ctx: &TaxContext, _addr: Addr) -> Decimal {
+        let impact = match &ctx.dst {
+            Some(pair) => self.price_impact(ctx, pair).unwrap_or_default(),
+            None => Decimal::zero(),
+        };
+        self.curve
+            .iter()
+            .rev()
+            .find(|step| impact >= step.impact)
+            .map(|step| step.tax_rate)
+            .unwrap_or_default()
+    }
+}
+
+impl TaxDeductible for TaxOracleCondition {
+    fn is_taxed(&self, _: &TaxContext, _addr: Addr) -> bool {
+        true
+    }
+
+    fn tax_rate(&self, ctx: &TaxContext, _addr: Addr) -> Decimal {
+        match self.price(ctx) {
+            Ok(price) => self
+                .bands
+                .iter()
+                .find(|band| price < band.below)
+                .map(|band| band.tax_rate)
+                .unwrap_or(self.tax_rate),
+            Err(_) => self.fallback_rate,
+        }
+    }
+}
+
+impl TaxDeductible for TaxVolumeCondition {
+    fn is_taxed(&self, _: &TaxContext, _addr: Addr) -> bool {
+        true
+    }
+
+    fn tax_rate(&self, _: &TaxContext, _addr: Addr) -> Decimal {
+        self.tax_rate
+    }
+}
+
+impl TaxDeductible for TaxHoldingPeriodCondition {
+    fn is_taxed(&self, _: &TaxContext, _addr: Addr) -> bool {
+        true
+    }
+
+    fn tax_rate(&self, ctx: &TaxContext, addr: Addr) -> Decimal {
+        let acquired_at = ACQUIRED_AT
+            .may_load(ctx.deps.storage, &addr)
+            .unwrap_or_default()
+            .unwrap_or_default();
+        let held = ctx
+            .env
+            .block
+            .time
+            .seconds()
+            .saturating_sub(acquired_at.seconds());
+        self.tiers
+            .iter()
+            .find(|tier| held < tier.seconds)
+            .map(|tier| tier.tax_rate)
+            .unwrap_or(self.tax_rate)
     }
 }
 
+fn into_result(errors: Vec<ContractError>) -> Result<(), ContractError> {
+    if errors.is_empty() {
+        return Ok(());
+    }
+    Err(ContractError::InvalidTaxMap { errors })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use cosmwasm_std::testing::mock_env;
     use cosmwasm_std::{
-        to_json_binary, Addr, ContractInfoResponse, ContractResult, Decimal, QuerierResult,
-        StdResult, Uint128, WasmQuery,
+        to_json_binary, Addr, ContractInfoResponse, ContractResult, Decimal, DepsMut,
+        QuerierResult, StdResult, Uint128, WasmQuery,
     };
 
     fn get_info(addr: &Addr) -> StdResult<ContractInfoResponse> {
@@ -276,7 +1775,8 @@ mod tests {
     fn test_tax_condition_is_taxed() {
         let mut deps = cosmwasm_std::testing::mock_dependencies();
         deps.querier.update_wasm(|r| wasm_query_handler(r));
-        let qw = QuerierWrapper::new(&deps.querier);
+        let env = mock_env();
+        let ctx = TaxContext::new(deps.as_ref(), &env);
 
         let addr0 = Addr::unchecked("0");
         let addr1 = Addr::unchecked("1");
@@ -285,7 +1785,7 @@ mod tests {
 
         // tax condition not fulfilled for any address
         let none_condition = TaxCondition::Never(TaxNeverCondition {});
-        assert_eq!(none_condition.is_taxed(&qw, addr0.clone()), false);
+        assert_eq!(none_condition.is_taxed(&ctx, addr0.clone()), false);
 
         // tax condition only fulfilled for listed contract addresses
         let contract_code_condition = TaxCondition::ContractCode(TaxContractCodeCondition {
@@ -294,30 +1794,31 @@ mod tests {
         });
 
         // is a contract and is listed
-        assert_eq!(contract_code_condition.is_taxed(&qw, addr0.clone()), true);
+        assert_eq!(contract_code_condition.is_taxed(&ctx, addr0.clone()), true);
         // is a contract and is listed
-        assert_eq!(contract_code_condition.is_taxed(&qw, addr1.clone()), true);
+        assert_eq!(contract_code_condition.is_taxed(&ctx, addr1.clone()), true);
         // is a contract but not listed
-        assert_eq!(contract_code_condition.is_taxed(&qw, addr2.clone()), false);
+        assert_eq!(contract_code_condition.is_taxed(&ctx, addr2.clone()), false);
         // is not a contract
-        assert_eq!(contract_code_condition.is_taxed(&qw, addr3.clone()), false);
+        assert_eq!(contract_code_condition.is_taxed(&ctx, addr3.clone()), false);
 
         // tax condition fulfilled for all addresses
         let contract_code_condition = TaxCondition::Always(TaxAlwaysCondition {
             tax_rate: Decimal::percent(10),
         });
 
-        assert_eq!(contract_code_condition.is_taxed(&qw, addr0.clone()), true);
-        assert_eq!(contract_code_condition.is_taxed(&qw, addr1.clone()), true);
-        assert_eq!(contract_code_condition.is_taxed(&qw, addr2.clone()), true);
-        assert_eq!(contract_code_condition.is_taxed(&qw, addr3.clone()), true);
+        assert_eq!(contract_code_condition.is_taxed(&ctx, addr0.clone()), true);
+        assert_eq!(contract_code_condition.is_taxed(&ctx, addr1.clone()), true);
+        assert_eq!(contract_code_condition.is_taxed(&ctx, addr2.clone()), true);
+        assert_eq!(contract_code_condition.is_taxed(&ctx, addr3.clone()), true);
     }
 
     #[test]
     fn test_tax_condition_tax_rate() {
         let mut deps = cosmwasm_std::testing::mock_dependencies();
         deps.querier.update_wasm(|r| wasm_query_handler(r));
-        let qw = QuerierWrapper::new(&deps.querier);
+        let env = mock_env();
+        let ctx = TaxContext::new(deps.as_ref(), &env);
 
         let addr0 = Addr::unchecked("0");
         let addr1 = Addr::unchecked("1");
@@ -326,7 +1827,10 @@ mod tests {
 
         // tax rate is zero for any address
         let none_condition = TaxCondition::Never(TaxNeverCondition {});
-        assert_eq!(none_condition.tax_rate(&qw, addr0.clone()), Decimal::zero());
+        assert_eq!(
+            none_condition.tax_rate(&ctx, addr0.clone()),
+            Decimal::zero()
+        );
 
         // tax condition only fulfilled for listed contract addresses
         let contract_code_condition = TaxCondition::ContractCode(TaxContractCodeCondition {
@@ -336,22 +1840,22 @@ mod tests {
 
         // is a contract and is listed
         assert_eq!(
-            contract_code_condition.tax_rate(&qw, addr0.clone()),
+            contract_code_condition.tax_rate(&ctx, addr0.clone()),
             Decimal::percent(10)
         );
         // is a contract and is listed
         assert_eq!(
-            contract_code_condition.tax_rate(&qw, addr1.clone()),
+            contract_code_condition.tax_rate(&ctx, addr1.clone()),
             Decimal::percent(10)
         );
         // is a contract but not listed
         assert_eq!(
-            contract_code_condition.tax_rate(&qw, addr2.clone()),
+            contract_code_condition.tax_rate(&ctx, addr2.clone()),
             Decimal::zero()
         );
         // is not a contract
         assert_eq!(
-            contract_code_condition.tax_rate(&qw, addr3.clone()),
+            contract_code_condition.tax_rate(&ctx, addr3.clone()),
             Decimal::zero()
         );
 
@@ -360,19 +1864,19 @@ mod tests {
             tax_rate: Decimal::percent(10),
         });
         assert_eq!(
-            contract_code_condition.tax_rate(&qw, addr0.clone()),
+            contract_code_condition.tax_rate(&ctx, addr0.clone()),
             Decimal::percent(10)
         );
         assert_eq!(
-            contract_code_condition.tax_rate(&qw, addr1.clone()),
+            contract_code_condition.tax_rate(&ctx, addr1.clone()),
             Decimal::percent(10)
         );
         assert_eq!(
-            contract_code_condition.tax_rate(&qw, addr2.clone()),
+            contract_code_condition.tax_rate(&ctx, addr2.clone()),
             Decimal::percent(10)
         );
         assert_eq!(
-            contract_code_condition.tax_rate(&qw, addr3.clone()),
+            contract_code_condition.tax_rate(&ctx, addr3.clone()),
             Decimal::percent(10)
         );
     }
@@ -381,7 +1885,8 @@ mod tests {
     fn test_tax_info_deduct_tax() {
         let mut deps = cosmwasm_std::testing::mock_dependencies();
         deps.querier.update_wasm(|r| wasm_query_handler(r));
-        let qw = QuerierWrapper::new(&deps.querier);
+        let env = mock_env();
+        let ctx = TaxContext::new(deps.as_ref(), &env);
 
         let addr0 = Addr::unchecked("0");
         let addr1 = Addr::unchecked("1");
@@ -393,9 +1898,15 @@ mod tests {
             src_cond: TaxCondition::Never(TaxNeverCondition {}),
             dst_cond: TaxCondition::Never(TaxNeverCondition {}),
             proceeds: addr0.clone(),
+            admin: None,
+            discounts: vec![],
+            referral_share: Decimal::zero(),
+            mode: TaxMode::Deduct,
+            native_payment: None,
+            rules: None,
         };
         assert_eq!(
-            tax_info.deduct_tax(&qw, addr0.clone(), addr1.clone(), Uint128::new(100)),
+            tax_info.deduct_tax(&ctx, addr0.clone(), addr1.clone(), Uint128::new(100)),
             Ok((Uint128::new(100), Uint128::zero()))
         );
 
@@ -409,26 +1920,32 @@ mod tests {
                 tax_rate: Decimal::percent(10),
             }),
             proceeds: addr0.clone(),
+            admin: None,
+            discounts: vec![],
+            referral_share: Decimal::zero(),
+            mode: TaxMode::Deduct,
+            native_payment: None,
+            rules: None,
         };
 
         // is listed contract but dst is proceeds wallet -> no tax
         assert_eq!(
-            tax_info_with_tax.deduct_tax(&qw, addr1.clone(), addr0.clone(), Uint128::new(100)),
+            tax_info_with_tax.deduct_tax(&ctx, addr1.clone(), addr0.clone(), Uint128::new(100)),
             Ok((Uint128::new(100), Uint128::new(0)))
         );
         // src is a contract and is listed -> tax
         assert_eq!(
-            tax_info_with_tax.deduct_tax(&qw, addr0.clone(), addr1.clone(), Uint128::new(100)),
+            tax_info_with_tax.deduct_tax(&ctx, addr0.clone(), addr1.clone(), Uint128::new(100)),
             Ok((Uint128::new(90), Uint128::new(10)))
         );
         // src is a contract but not listed -> no tax
         assert_eq!(
-            tax_info_with_tax.deduct_tax(&qw, addr2.clone(), addr2.clone(), Uint128::new(100)),
+            tax_info_with_tax.deduct_tax(&ctx, addr2.clone(), addr2.clone(), Uint128::new(100)),
             Ok((Uint128::new(100), Uint128::new(0)))
         );
         // is not a contract -> no tax
         assert_eq!(
-            tax_info_with_tax.deduct_tax(&qw, addr3.clone(), addr2.clone(), Uint128::new(100)),
+            tax_info_with_tax.deduct_tax(&ctx, addr3.clone(), addr2.clone(), Uint128::new(100)),
             Ok((Uint128::new(100), Uint128::new(0)))
         );
 
@@ -441,24 +1958,30 @@ mod tests {
                 tax_rate: Decimal::percent(10),
             }),
             proceeds: addr0.clone(),
+            admin: None,
+            discounts: vec![],
+            referral_share: Decimal::zero(),
+            mode: TaxMode::Deduct,
+            native_payment: None,
+            rules: None,
         };
 
         // is proceeds wallet -> no tax
         assert_eq!(
-            tax_info_with_tax.deduct_tax(&qw, addr1.clone(), addr0.clone(), Uint128::new(100)),
+            tax_info_with_tax.deduct_tax(&ctx, addr1.clone(), addr0.clone(), Uint128::new(100)),
             Ok((Uint128::new(100), Uint128::new(0)))
         );
         // is normal wallet -> tax
         assert_eq!(
-            tax_info_with_tax.deduct_tax(&qw, addr1.clone(), addr2.clone(), Uint128::new(100)),
+            tax_info_with_tax.deduct_tax(&ctx, addr1.clone(), addr2.clone(), Uint128::new(100)),
             Ok((Uint128::new(90), Uint128::new(10)))
         );
         assert_eq!(
-            tax_info_with_tax.deduct_tax(&qw, addr2.clone(), addr1.clone(), Uint128::new(100)),
+            tax_info_with_tax.deduct_tax(&ctx, addr2.clone(), addr1.clone(), Uint128::new(100)),
             Ok((Uint128::new(90), Uint128::new(10)))
         );
         assert_eq!(
-            tax_info_with_tax.deduct_tax(&qw, addr3.clone(), addr2.clone(), Uint128::new(100)),
+            tax_info_with_tax.deduct_tax(&ctx, addr3.clone(), addr2.clone(), Uint128::new(100)),
             Ok((Uint128::new(90), Uint128::new(10)))
         );
     }
@@ -492,6 +2015,12 @@ mod tests {
                 tax_rate: Decimal::percent(10),
             }),
             proceeds: Addr::unchecked("blubb"),
+            admin: None,
+            discounts: vec![],
+            referral_share: Decimal::zero(),
+            mode: TaxMode::Deduct,
+            native_payment: None,
+            rules: None,
         };
         let invalid_tax_info2 = TaxInfo {
             src_cond: TaxCondition::Always(TaxAlwaysCondition {
@@ -501,6 +2030,12 @@ mod tests {
                 tax_rate: Decimal::percent(110),
             }),
             proceeds: Addr::unchecked("blubb"),
+            admin: None,
+            discounts: vec![],
+            referral_share: Decimal::zero(),
+            mode: TaxMode::Deduct,
+            native_payment: None,
+            rules: None,
         };
         let invalid_tax_info3 = TaxInfo {
             src_cond: TaxCondition::Always(TaxAlwaysCondition {
@@ -510,6 +2045,12 @@ mod tests {
                 tax_rate: Decimal::percent(110),
             }),
             proceeds: Addr::unchecked("blubb"),
+            admin: None,
+            discounts: vec![],
+            referral_share: Decimal::zero(),
+            mode: TaxMode::Deduct,
+            native_payment: None,
+            rules: None,
         };
         let valid_tax_info = TaxInfo {
             src_cond: TaxCondition::Always(TaxAlwaysCondition {
@@ -519,6 +2060,12 @@ mod tests {
                 tax_rate: Decimal::percent(10),
             }),
             proceeds: Addr::unchecked("blubb"),
+            admin: None,
+            discounts: vec![],
+            referral_share: Decimal::zero(),
+            mode: TaxMode::Deduct,
+            native_payment: None,
+            rules: None,
         };
         assert_eq!(invalid_tax_info1.validate(), false);
         assert_eq!(invalid_tax_info2.validate(), false);
@@ -536,6 +2083,12 @@ mod tests {
                 tax_rate: Decimal::percent(10),
             }),
             proceeds: Addr::unchecked("blubb"),
+            admin: None,
+            discounts: vec![],
+            referral_share: Decimal::zero(),
+            mode: TaxMode::Deduct,
+            native_payment: None,
+            rules: None,
         };
         let valid_tax_info = TaxInfo {
             src_cond: TaxCondition::Always(TaxAlwaysCondition {
@@ -545,6 +2098,12 @@ mod tests {
                 tax_rate: Decimal::percent(10),
             }),
             proceeds: Addr::unchecked("blubb"),
+            admin: None,
+            discounts: vec![],
+            referral_share: Decimal::zero(),
+            mode: TaxMode::Deduct,
+            native_payment: None,
+            rules: None,
         };
         let valid_tax_map = TaxMap {
             on_transfer: valid_tax_info.clone(),
@@ -560,7 +2119,1160 @@ mod tests {
             on_transfer_from: valid_tax_info.clone(),
             admin: Addr::unchecked(""),
         };
-        assert_eq!(valid_tax_map.validate().is_ok(), true);
-        assert_eq!(invalid_tax_map.validate().is_err(), true);
+        assert_eq!(valid_tax_map.validate(None).is_ok(), true);
+        assert_eq!(invalid_tax_map.validate(None).is_err(), true);
+    }
+
+    #[test]
+    fn test_tax_rate_limit_check() {
+        let limit = TaxRateLimit {
+            max_delta: Decimal::percent(2),
+            epoch: TaxEpoch::Blocks(100),
+        };
+        let old = TaxMap {
+            on_send: TaxInfo {
+                src_cond: TaxCondition::Always(TaxAlwaysCondition {
+                    tax_rate: Decimal::percent(5),
+                }),
+                dst_cond: TaxCondition::Never(TaxNeverCondition {}),
+                proceeds: Addr::unchecked("proceeds"),
+                admin: None,
+                discounts: vec![],
+                referral_share: Decimal::zero(),
+                mode: TaxMode::Deduct,
+                native_payment: None,
+                rules: None,
+            },
+            ..TaxMap::default()
+        };
+
+        let mut new = old.clone();
+        new.on_send.src_cond = TaxCondition::Always(TaxAlwaysCondition {
+            tax_rate: Decimal::percent(3),
+        });
+        assert!(limit.check(&old, &new).is_ok());
+
+        new.on_send.src_cond = TaxCondition::Always(TaxAlwaysCondition {
+            tax_rate: Decimal::percent(8),
+        });
+        assert_eq!(
+            limit.check(&old, &new),
+            Err(ContractError::TaxRateChangeExceedsLimit {
+                action: "on_send".to_string(),
+                side: "src".to_string(),
+                previous: Decimal::percent(5),
+                requested: Decimal::percent(8),
+                max_delta: Decimal::percent(2),
+            })
+        );
+
+        // enabling a tax counts as a change from zero
+        let mut new = old.clone();
+        new.on_transfer.dst_cond = TaxCondition::Always(TaxAlwaysCondition {
+            tax_rate: Decimal::percent(10),
+        });
+        assert!(limit.check(&old, &new).is_err());
+
+        // rules are compared one by one, not only by their highest rate
+        let always = |rate: u64| {
+            TaxCondition::Always(TaxAlwaysCondition {
+                tax_rate: Decimal::percent(rate),
+            })
+        };
+        let rule = |rate: u64| TaxRule {
+            name: None,
+            src_cond: always(rate),
+            dst_cond: TaxCondition::never(),
+            proceeds: Addr::unchecked("proceeds"),
+        };
+        let mut old = old.clone();
+        old.on_send.rules = Some(vec![rule(5), rule(1)]);
+        let mut new = old.clone();
+        new.on_send.rules = Some(vec![rule(5), rule(2)]);
+        assert!(limit.check(&old, &new).is_ok());
+        new.on_send.rules = Some(vec![rule(5), rule(4)]);
+        assert_eq!(
+            limit.check(&old, &new),
+            Err(ContractError::TaxRateChangeExceedsLimit {
+                action: "on_send".to_string(),
+                side: "rule 1 src".to_string(),
+                previous: Decimal::percent(1),
+                requested: Decimal::percent(4),
+                max_delta: Decimal::percent(2),
+            })
+        );
+
+        // so are the tiers of a condition
+        let holding = |short: u64| {
+            TaxCondition::HoldingPeriod(TaxHoldingPeriodCondition {
+                tiers: vec![
+                    TaxHoldingTier {
+                        seconds: 60,
+                        tax_rate: Decimal::percent(10),
+                    },
+                    TaxHoldingTier {
+                        seconds: 3600,
+                        tax_rate: Decimal::percent(short),
+                    },
+                ],
+                tax_rate: Decimal::percent(1),
+            })
+        };
+        let mut old = TaxMap::default();
+        old.on_transfer.src_cond = holding(2);
+        let mut new = old.clone();
+        new.on_transfer.src_cond = holding(6);
+        assert_eq!(
+            limit.check(&old, &new),
+            Err(ContractError::TaxRateChangeExceedsLimit {
+                action: "on_transfer".to_string(),
+                side: "src".to_string(),
+                previous: Decimal::percent(2),
+                requested: Decimal::percent(6),
+                max_delta: Decimal::percent(2),
+            })
+        );
+
+        // and the rates discount tiers result in
+        let mut old = TaxMap::default();
+        old.on_transfer.src_cond = always(10);
+        old.on_transfer.discounts = vec![TaxDiscountTier {
+            min_balance: Uint128::new(1000),
+            discount: Decimal::percent(50),
+        }];
+        let mut new = old.clone();
+        new.on_transfer.discounts[0].discount = Decimal::percent(90);
+        assert_eq!(
+            limit.check(&old, &new),
+            Err(ContractError::TaxRateChangeExceedsLimit {
+                action: "on_transfer".to_string(),
+                side: "discount 0".to_string(),
+                previous: Decimal::percent(5),
+                requested: Decimal::percent(1),
+                max_delta: Decimal::percent(2),
+            })
+        );
+    }
+
+    #[test]
+    fn test_tax_map_validate_max_tax_rate() {
+        let tax_info = TaxInfo {
+            src_cond: TaxCondition::ContractCode(TaxContractCodeCondition {
+                code_ids: vec![1],
+                tax_rate: Decimal::percent(5),
+            }),
+            dst_cond: TaxCondition::Always(TaxAlwaysCondition {
+                tax_rate: Decimal::percent(12),
+            }),
+            proceeds: Addr::unchecked("proceeds"),
+            admin: None,
+            discounts: vec![],
+            referral_share: Decimal::zero(),
+            mode: TaxMode::Deduct,
+            native_payment: None,
+            rules: None,
+        };
+        let tax_map = TaxMap {
+            on_transfer: TaxInfo::default(),
+            on_send: tax_info,
+            on_send_from: TaxInfo::default(),
+            on_transfer_from: TaxInfo::default(),
+            admin: Addr::unchecked(""),
+        };
+
+        // every rate in every condition counts, not only the charged one
+        assert_eq!(tax_map.max_tax_rate(), Decimal::percent(12));
+        assert!(tax_map.validate(Some(Decimal::percent(12))).is_ok());
+        assert_eq!(
+            tax_map.validate(Some(Decimal::percent(10))),
+            Err(ContractError::InvalidTaxMap {
+                errors: vec![ContractError::TaxRateExceedsMax {
+                    action: "on_send".to_string(),
+                    side: "dst".to_string(),
+                    rate: Decimal::percent(12),
+                    max: Decimal::percent(10),
+                }],
+            })
+        );
+        assert!(TaxMap::default().validate(Some(Decimal::zero())).is_ok());
+    }
+
+    #[test]
+    fn test_tax_map_validate_reports_all_errors() {
+        let mut tax_map = TaxMap::default();
+        tax_map.on_transfer.src_cond = TaxCondition::Always(TaxAlwaysCondition {
+            tax_rate: Decimal::percent(150),
+        });
+        tax_map.on_transfer.proceeds = Addr::unchecked("proceeds");
+        tax_map.on_transfer.referral_share = Decimal::percent(110);
+        tax_map.on_send.rules = Some(vec![TaxRule {
+            name: None,
+            src_cond: TaxCondition::Volume(TaxVolumeCondition {
+                allowance: Uint128::new(100),
+                window: 0,
+                tax_rate: Decimal::percent(5),
+            }),
+            dst_cond: TaxCondition::Never(TaxNeverCondition {}),
+            proceeds: Addr::unchecked(""),
+        }]);
+
+        let err = tax_map.validate(None).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InvalidTaxMap {
+                errors: vec![
+                    ContractError::InvalidTaxRate {
+                        action: "on_transfer".to_string(),
+                        side: "src".to_string(),
+                        rate: Decimal::percent(150),
+                    },
+                    ContractError::InvalidTaxInfo {
+                        action: "on_transfer".to_string(),
+                        reason: "referral share must not exceed 1".to_string(),
+                    },
+                    ContractError::MissingProceeds {
+                        action: "on_send".to_string(),
+                    },
+                    ContractError::InvalidTaxCondition {
+                        action: "on_send".to_string(),
+                        side: "rule 0 src".to_string(),
+                    },
+                ],
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "Invalid tax map: Tax rate 1.5 of on_transfer (src) must not exceed 1; \
+             Invalid tax info of on_transfer: referral share must not exceed 1; \
+             Proceeds wallet of on_send cannot be empty; \
+             Invalid tax condition of on_send (rule 0 src)"
+        );
+    }
+
+    #[test]
+    fn test_holding_period_condition() {
+        let mut deps = cosmwasm_std::testing::mock_dependencies();
+        let mut env = mock_env();
+        let addr = Addr::unchecked("holder");
+        let tier = |seconds, percent| TaxHoldingTier {
+            seconds,
+            tax_rate: Decimal::percent(percent),
+        };
+        let mut cond = TaxHoldingPeriodCondition {
+            tiers: vec![tier(100, 30), tier(1000, 10)],
+            tax_rate: Decimal::percent(1),
+        };
+        assert!(cond.validate());
+        assert_eq!(
+            TaxCondition::HoldingPeriod(cond.clone()).max_tax_rate(),
+            Decimal::percent(30)
+        );
+
+        // 1000 tokens acquired now, another 1000 after 200 seconds
+        let start = env.block.time;
+        BALANCES
+            .save(&mut deps.storage, &addr, &Uint128::new(1000))
+            .unwrap();
+        record_acquisition(&mut deps.storage, &env.block, &addr, Uint128::new(1000)).unwrap();
+        env.block.time = start.plus_seconds(200);
+        BALANCES
+            .save(&mut deps.storage, &addr, &Uint128::new(2000))
+            .unwrap();
+        record_acquisition(&mut deps.storage, &env.block, &addr, Uint128::new(1000)).unwrap();
+        assert_eq!(
+            ACQUIRED_AT.load(&deps.storage, &addr).unwrap(),
+            start.plus_seconds(100)
+        );
+
+        let rate_at = |seconds| {
+            let mut env = env.clone();
+            env.block.time = start.plus_seconds(seconds);
+            let ctx = TaxContext::new(deps.as_ref(), &env);
+            cond.tax_rate(&ctx, addr.clone())
+        };
+        assert_eq!(rate_at(199), Decimal::percent(30));
+        assert_eq!(rate_at(200), Decimal::percent(10));
+        assert_eq!(rate_at(1100), Decimal::percent(1));
+
+        // tiers must be ordered and rates must not exceed 1
+        cond.tiers.reverse();
+        assert!(!cond.validate());
+        cond.tiers = vec![tier(100, 101)];
+        assert!(!cond.validate());
+    }
+
+    #[test]
+    fn test_send_hook_condition() {
+        let deps = cosmwasm_std::testing::mock_dependencies();
+        let env = mock_env();
+        let addr = Addr::unchecked("pair");
+        let cond = TaxSendHookCondition {
+            swap_rate: Decimal::percent(5),
+            default_rate: Decimal::percent(1),
+        };
+        assert!(cond.validate());
+
+        let rate_for = |msg: Option<&str>| {
+            let mut ctx = TaxContext::new(deps.as_ref(), &env);
+            if let Some(msg) = msg {
+                ctx = ctx.with_hook(&Binary::from(msg.as_bytes()));
+            }
+            cond.tax_rate(&ctx, addr.clone())
+        };
+        assert_eq!(
+            rate_for(Some(r#"{"swap":{"belief_price":"1.5","max_spread":null}}"#)),
+            Decimal::percent(5)
+        );
+        assert_eq!(
+            rate_for(Some(r#"{"execute_swap_operations":{"operations":[]}}"#)),
+            Decimal::percent(5)
+        );
+        assert_eq!(
+            rate_for(Some(r#"{"provide_liquidity":{}}"#)),
+            Decimal::zero()
+        );
+        assert_eq!(
+            rate_for(Some(r#"{"withdraw_liquidity":{}}"#)),
+            Decimal::zero()
+        );
+        assert_eq!(rate_for(Some(r#"{"stake":{}}"#)), Decimal::percent(1));
+        assert_eq!(rate_for(Some("not json")), Decimal::percent(1));
+        assert_eq!(rate_for(None), Decimal::percent(1));
+
+        let ctx = TaxContext::new(deps.as_ref(), &env)
+            .with_hook(&Binary::from(br#"{"withdraw_liquidity":{}}"#));
+        assert!(!cond.is_taxed(&ctx, addr.clone()));
+        assert!(!TaxSendHookCondition {
+            swap_rate: Decimal::percent(101),
+            default_rate: Decimal::zero(),
+        }
+        .validate());
+    }
+
+    #[test]
+    fn test_nft_holder_condition_caches_queries() {
+        let mut deps = cosmwasm_std::testing::mock_dependencies();
+        let env = mock_env();
+        let queries = Rc::new(std::cell::Cell::new(0));
+        let counter = queries.clone();
+        deps.querier.update_wasm(move |r| {
+            counter.set(counter.get() + 1);
+            let owner = match r {
+                WasmQuery::Smart { msg, .. } => match from_json(msg).unwrap() {
+                    Cw721QueryMsg::Tokens { owner, .. } => owner,
+                },
+                _ => panic!("unexpected query"),
+            };
+            let tokens = match owner.as_str() {
+                "holder" => vec![String::from("1")],
+                _ => vec![],
+            };
+            let res = TokensResponse { tokens };
+            QuerierResult::Ok(ContractResult::Ok(to_json_binary(&res).unwrap()))
+        });
+        let cond = TaxCondition::NftHolder(TaxNftHolderCondition {
+            collection: Addr::unchecked("collection"),
+            tax_rate: Decimal::percent(5),
+        });
+        assert!(cond.validate());
+        assert_eq!(cond.max_tax_rate(), Decimal::percent(5));
+
+        let ctx = TaxContext::new(deps.as_ref(), &env);
+        let holder = Addr::unchecked("holder");
+        let other = Addr::unchecked("other");
+        assert!(!cond.is_taxed(&ctx, holder.clone()));
+        assert_eq!(cond.tax_rate(&ctx, holder.clone()), Decimal::zero());
+        assert!(cond.is_taxed(&ctx, other.clone()));
+        assert_eq!(cond.tax_rate(&ctx, other.clone()), Decimal::percent(5));
+
+        // the cache is shared with the contexts of single transfers
+        let transfer_ctx = ctx.with_transfer(&other, Uint128::new(100));
+        assert!(!cond.is_taxed(&transfer_ctx, holder.clone()));
+        assert_eq!(queries.get(), 2);
+
+        // another execution queries again
+        let ctx = TaxContext::new(deps.as_ref(), &env);
+        assert!(!cond.is_taxed(&ctx, holder));
+        assert_eq!(queries.get(), 3);
+    }
+
+    #[test]
+    fn test_discount_tiers() {
+        let mut deps = cosmwasm_std::testing::mock_dependencies();
+        let env = mock_env();
+        let tier = |min_balance, percent| TaxDiscountTier {
+            min_balance: Uint128::new(min_balance),
+            discount: Decimal::percent(percent),
+        };
+        let mut info = TaxInfo {
+            src_cond: TaxCondition::Always(TaxAlwaysCondition {
+                tax_rate: Decimal::percent(10),
+            }),
+            dst_cond: TaxCondition::Always(TaxAlwaysCondition {
+                tax_rate: Decimal::zero(),
+            }),
+            proceeds: Addr::unchecked("proceeds"),
+            admin: None,
+            discounts: vec![tier(1000, 25), tier(5000, 100)],
+            referral_share: Decimal::zero(),
+            mode: TaxMode::Deduct,
+            native_payment: None,
+            rules: None,
+        };
+        assert!(info.validate());
+        assert_eq!(info.discount(Uint128::new(999)), Decimal::zero());
+        assert_eq!(info.discount(Uint128::new(1000)), Decimal::percent(25));
+        assert_eq!(info.discount(Uint128::new(7000)), Decimal::percent(100));
+
+        let deduct = |deps: Deps, info: &TaxInfo, amount| {
+            let ctx = TaxContext::new(deps, &env);
+            info.deduct_tax(
+                &ctx,
+                Addr::unchecked("holder"),
+                Addr::unchecked("pair"),
+                Uint128::new(amount),
+            )
+            .unwrap()
+        };
+        fn set_balance(deps: DepsMut, balance: u128) {
+            BALANCES
+                .save(
+                    deps.storage,
+                    &Addr::unchecked("holder"),
+                    &Uint128::new(balance),
+                )
+                .unwrap()
+        }
+
+        // no discount below the first tier
+        set_balance(deps.as_mut(), 500);
+        assert_eq!(
+            deduct(deps.as_ref(), &info, 101),
+            (Uint128::new(91), Uint128::new(10))
+        );
+
+        // the discounted part of the tax is rounded down
+        set_balance(deps.as_mut(), 1000);
+        assert_eq!(
+            deduct(deps.as_ref(), &info, 101),
+            (Uint128::new(93), Uint128::new(8))
+        );
+
+        // a full discount never yields a negative tax
+        set_balance(deps.as_mut(), 5000);
+        for amount in [1, 7, 101, 5000] {
+            assert_eq!(
+                deduct(deps.as_ref(), &info, amount),
+                (Uint128::new(amount), Uint128::zero())
+            );
+        }
+
+        // discounts above 1 and unordered tiers are rejected
+        info.discounts = vec![tier(1000, 101)];
+        assert!(!info.validate());
+        info.discounts = vec![tier(5000, 50), tier(1000, 25)];
+        assert!(!info.validate());
+        info.discounts = vec![tier(1000, 50), tier(1000, 25)];
+        assert!(!info.validate());
+    }
+
+    #[test]
+    fn test_tax_rules() {
+        let deps = cosmwasm_std::testing::mock_dependencies();
+        let env = mock_env();
+        let ctx = TaxContext::new(deps.as_ref(), &env);
+
+        // the rule form does not need the legacy fields
+        let info: TaxInfo = from_json(
+            br#"{
+                "rules": [
+                    {
+                        "name": "buy",
+                        "src_cond": { "Addresses": { "addresses": ["pair_a"], "tax_rate": "0.02" } },
+                        "dst_cond": { "Always": { "tax_rate": "0" } },
+                        "proceeds": "treasury"
+                    },
+                    {
+                        "src_cond": { "Always": { "tax_rate": "0.05" } },
+                        "dst_cond": { "Addresses": { "addresses": ["pair_b"], "tax_rate": "0" } },
+                        "proceeds": "marketing"
+                    }
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert!(info.validate());
+        assert_eq!(info.max_src_tax_rate(), Decimal::percent(5));
+        assert_eq!(info.max_dst_tax_rate(), Decimal::zero());
+
+        let deduct = |src: &str, dst: &str| {
+            info.deduct(
+                &ctx,
+                Addr::unchecked(src),
+                Addr::unchecked(dst),
+                Uint128::new(1000),
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            deduct("pair_a", "user"),
+            TaxDeduction {
+                net: Uint128::new(980),
+                gross: Uint128::new(1000),
+                tax: Uint128::new(20),
+                proceeds: Addr::unchecked("treasury"),
+                rule: Some(0),
+            }
+        );
+        assert_eq!(
+            deduct("user", "pair_b"),
+            TaxDeduction {
+                net: Uint128::new(950),
+                gross: Uint128::new(1000),
+                tax: Uint128::new(50),
+                proceeds: Addr::unchecked("marketing"),
+                rule: Some(1),
+            }
+        );
+        // the first matching rule wins
+        assert_eq!(deduct("pair_a", "pair_b").rule, Some(0));
+        assert_eq!(deduct("user", "pair_a").tax, Uint128::zero());
+        assert_eq!(deduct("user", "pair_a").rule, None);
+
+        // the legacy form is still accepted and reports no rule
+        let legacy: TaxInfo = from_json(
+            br#"{
+                "src_cond": { "Always": { "tax_rate": "0.05" } },
+                "dst_cond": { "Always": { "tax_rate": "0" } },
+                "proceeds": "proceeds"
+            }"#,
+        )
+        .unwrap();
+        assert!(legacy.validate());
+        let deduction = legacy
+            .deduct(
+                &ctx,
+                Addr::unchecked("user"),
+                Addr::unchecked("pair"),
+                Uint128::new(1000),
+            )
+            .unwrap();
+        assert_eq!(deduction.tax, Uint128::new(50));
+        assert_eq!(deduction.proceeds, Addr::unchecked("proceeds"));
+        assert_eq!(deduction.rule, None);
+
+        // rules must not be empty and need proceeds
+        let mut invalid = info.clone();
+        invalid.rules = Some(vec![]);
+        assert!(!invalid.validate());
+        let mut invalid = info.clone();
+        invalid.rules.as_mut().unwrap()[1].proceeds = Addr::unchecked("");
+        assert!(!invalid.validate());
+    }
+
+    mod oracle {
+        use super::*;
+
+        use cosmwasm_std::{
+            to_json_binary, Binary, Deps, DepsMut, Empty, Env, MessageInfo, Response,
+        };
+        use cw20::BalanceResponse;
+        use cw_multi_test::{App, Contract, ContractWrapper, Executor};
+        use cw_storage_plus::Item;
+
+        use crate::msg::{Cw20TaxedExecuteMsg as ExecuteMsg, QueryMsg};
+        use crate::testing::{instantiate_cw20, instantiate_msg, transfer_tax_map};
+
+        const PRICE: Item<OraclePriceResponse> = Item::new("price");
+
+        fn oracle_instantiate(
+            deps: DepsMut,
+            _env: Env,
+            _info: MessageInfo,
+            msg: OraclePriceResponse,
+        ) -> StdResult<Response> {
+            PRICE.save(deps.storage, &msg)?;
+            Ok(Response::new())
+        }
+
+        fn oracle_query(deps: Deps, _env: Env, _msg: OracleQueryMsg) -> StdResult<Binary> {
+            to_json_binary(&PRICE.load(deps.storage)?)
+        }
+
+        fn oracle_contract() -> Box<dyn Contract<Empty>> {
+            // executing sets a new price
+            Box::new(ContractWrapper::new(
+                oracle_instantiate,
+                oracle_instantiate,
+                oracle_query,
+            ))
+        }
+
+        fn setup(oracle: Option<&Addr>) -> (App, Addr, Addr) {
+            let mut app = App::default();
+            let oracle_id = app.store_code(oracle_contract());
+
+            let price = OraclePriceResponse {
+                price: Decimal::percent(150),
+                updated_at: Some(app.block_info().time),
+            };
+            let oracle_addr = app
+                .instantiate_contract(
+                    oracle_id,
+                    Addr::unchecked("owner"),
+                    &price,
+                    &[],
+                    "ORACLE",
+                    None,
+                )
+                .unwrap();
+
+            let sell_tax = TaxCondition::Oracle(TaxOracleCondition {
+                oracle: oracle.unwrap_or(&oracle_addr).clone(),
+                bands: vec![
+                    TaxPriceBand {
+                        below: Decimal::percent(50),
+                        tax_rate: Decimal::percent(20),
+                    },
+                    TaxPriceBand {
+                        below: Decimal::one(),
+                        tax_rate: Decimal::percent(10),
+                    },
+                ],
+                tax_rate: Decimal::percent(1),
+                max_age: Some(60),
+                fallback_rate: Decimal::percent(5),
+            });
+            let msg = instantiate_msg()
+                .with_balance("seller", 10000)
+                .with_tax_map(transfer_tax_map(sell_tax));
+            let cw20_addr = instantiate_cw20(&mut app, &msg);
+            (app, cw20_addr, oracle_addr)
+        }
+
+        // sells 100 tokens and returns the tax the proceeds wallet received
+        fn sell(app: &mut App, cw20_addr: &Addr) -> Uint128 {
+            let balance = |app: &App| {
+                let res: BalanceResponse = app
+                    .wrap()
+                    .query_wasm_smart(
+                        cw20_addr,
+                        &QueryMsg::Balance {
+                            address: "proceeds".to_string(),
+                        },
+                    )
+                    .unwrap();
+                res.balance
+            };
+            let before = balance(app);
+            let msg = ExecuteMsg::Transfer {
+                recipient: "pair".to_string(),
+                amount: Uint128::new(100),
+            };
+            app.execute_contract(Addr::unchecked("seller"), cw20_addr.clone(), &msg, &[])
+                .unwrap();
+            balance(app) - before
+        }
+
+        fn set_price(app: &mut App, oracle_addr: &Addr, price: Decimal) {
+            let msg = OraclePriceResponse {
+                price,
+                updated_at: Some(app.block_info().time),
+            };
+            app.execute_contract(Addr::unchecked("owner"), oracle_addr.clone(), &msg, &[])
+                .unwrap();
+        }
+
+        #[test]
+        fn test_oracle_price_bands() {
+            let (mut app, cw20_addr, oracle_addr) = setup(None);
+            assert_eq!(sell(&mut app, &cw20_addr), Uint128::new(1));
+
+            set_price(&mut app, &oracle_addr, Decimal::percent(80));
+            assert_eq!(sell(&mut app, &cw20_addr), Uint128::new(10));
+
+            set_price(&mut app, &oracle_addr, Decimal::percent(30));
+            assert_eq!(sell(&mut app, &cw20_addr), Uint128::new(20));
+
+            // the price is exactly at the band limit
+            set_price(&mut app, &oracle_addr, Decimal::percent(50));
+            assert_eq!(sell(&mut app, &cw20_addr), Uint128::new(10));
+        }
+
+        #[test]
+        fn test_oracle_fallback_rate() {
+            // stale price
+            let (mut app, cw20_addr, oracle_addr) = setup(None);
+            app.update_block(|block| block.time = block.time.plus_seconds(61));
+            assert_eq!(sell(&mut app, &cw20_addr), Uint128::new(5));
+            set_price(&mut app, &oracle_addr, Decimal::percent(30));
+            assert_eq!(sell(&mut app, &cw20_addr), Uint128::new(20));
+
+            // oracle that cannot be queried
+            let (mut app, cw20_addr, _) = setup(Some(&Addr::unchecked("no_oracle")));
+            assert_eq!(sell(&mut app, &cw20_addr), Uint128::new(5));
+        }
+
+        #[test]
+        fn test_oracle_condition_validate() {
+            let mut cond = TaxOracleCondition {
+                oracle: Addr::unchecked("oracle"),
+                bands: vec![
+                    TaxPriceBand {
+                        below: Decimal::percent(50),
+                        tax_rate: Decimal::percent(20),
+                    },
+                    TaxPriceBand {
+                        below: Decimal::one(),
+                        tax_rate: Decimal::percent(10),
+                    },
+                ],
+                tax_rate: Decimal::percent(1),
+                max_age: None,
+                fallback_rate: Decimal::percent(30),
+            };
+            assert!(cond.validate());
+            assert_eq!(
+                TaxCondition::Oracle(cond.clone()).max_tax_rate(),
+                Decimal::percent(30)
+            );
+            cond.bands.reverse();
+            assert!(!cond.validate());
+        }
+    }
+
+    mod price_impact {
+        use super::*;
+
+        use cosmwasm_std::{
+            to_json_binary, Binary, Deps, DepsMut, Empty, Env, MessageInfo, Response,
+        };
+        use cw20::BalanceResponse;
+        use cw_multi_test::{App, Contract, ContractWrapper, Executor};
+        use cw_storage_plus::Item;
+
+        use crate::msg::{Cw20TaxedExecuteMsg as ExecuteMsg, QueryMsg};
+        use crate::testing::{instantiate_cw20, instantiate_msg, transfer_tax_map};
+
+        const POOL: Item<PoolResponse> = Item::new("pool");
+
+        fn pair_instantiate(
+            deps: DepsMut,
+            _env: Env,
+            _info: MessageInfo,
+            msg: PoolResponse,
+        ) -> StdResult<Response> {
+            POOL.save(deps.storage, &msg)?;
+            Ok(Response::new())
+        }
+
+        fn pair_query(deps: Deps, _env: Env, _msg: PairQueryMsg) -> StdResult<Binary> {
+            to_json_binary(&POOL.load(deps.storage)?)
+        }
+
+        fn pair_contract() -> Box<dyn Contract<Empty>> {
+            Box::new(ContractWrapper::new(
+                pair_instantiate,
+                pair_instantiate,
+                pair_query,
+            ))
+        }
+
+        fn impact_curve() -> TaxPriceImpactCondition {
+            let step = |impact, tax_rate| TaxImpactStep {
+                impact: Decimal::percent(impact),
+                tax_rate: Decimal::percent(tax_rate),
+            };
+            TaxPriceImpactCondition {
+                curve: vec![step(1, 2), step(5, 10), step(10, 25)],
+            }
+        }
+
+        // token with a pair holding 100k tokens, and a pair of another token
+        fn setup() -> (App, Addr, Addr, Addr) {
+            let mut app = App::default();
+            let pair_id = app.store_code(pair_contract());
+
+            let msg = instantiate_msg()
+                .with_balance("seller", 1_000_000)
+                .with_tax_map(transfer_tax_map(TaxCondition::PriceImpact(impact_curve())));
+            let cw20_addr = instantiate_cw20(&mut app, &msg);
+
+            let pool = |token: &str| PoolResponse {
+                assets: vec![
+                    Asset {
+                        info: AssetInfo::NativeToken {
+                            denom: "uluna".to_string(),
+                        },
+                        amount: Uint128::new(100_000),
+                    },
+                    Asset {
+                        info: AssetInfo::Token {
+                            contract_addr: token.to_string(),
+                        },
+                        amount: Uint128::new(100_000),
+                    },
+                ],
+                total_share: Uint128::new(100_000),
+            };
+            let pair_addr = app
+                .instantiate_contract(
+                    pair_id,
+                    Addr::unchecked("owner"),
+                    &pool(cw20_addr.as_str()),
+                    &[],
+                    "PAIR",
+                    None,
+                )
+                .unwrap();
+            let other_pair_addr = app
+                .instantiate_contract(
+                    pair_id,
+                    Addr::unchecked("owner"),
+                    &pool("other_token"),
+                    &[],
+                    "PAIR",
+                    None,
+                )
+                .unwrap();
+            (app, cw20_addr, pair_addr, other_pair_addr)
+        }
+
+        // transfers the amount and returns the tax the proceeds wallet received
+        fn sell(app: &mut App, cw20_addr: &Addr, recipient: &Addr, amount: u128) -> Uint128 {
+            let balance = |app: &App| {
+                let res: BalanceResponse = app
+                    .wrap()
+                    .query_wasm_smart(
+                        cw20_addr,
+                        &QueryMsg::Balance {
+                            address: "proceeds".to_string(),
+                        },
+                    )
+                    .unwrap();
+                res.balance
+            };
+            let before = balance(app);
+            let msg = ExecuteMsg::Transfer {
+                recipient: recipient.to_string(),
+                amount: Uint128::new(amount),
+            };
+            app.execute_contract(Addr::unchecked("seller"), cw20_addr.clone(), &msg, &[])
+                .unwrap();
+            balance(app) - before
+        }
+
+        #[test]
+        fn test_price_impact_curve() {
+            let (mut app, cw20_addr, pair_addr, other_pair_addr) = setup();
+
+            // ~0.1% impact
+            assert_eq!(sell(&mut app, &cw20_addr, &pair_addr, 100), Uint128::zero());
+            // ~1.96% impact
+            assert_eq!(
+                sell(&mut app, &cw20_addr, &pair_addr, 2_000),
+                Uint128::new(40)
+            );
+            // ~5.66% impact
+            assert_eq!(
+                sell(&mut app, &cw20_addr, &pair_addr, 6_000),
+                Uint128::new(600)
+            );
+            // ~16.7% impact
+            assert_eq!(
+                sell(&mut app, &cw20_addr, &pair_addr, 20_000),
+                Uint128::new(5_000)
+            );
+
+            // neither wallets nor pairs of other tokens are taxed
+            let wallet = Addr::unchecked("wallet");
+            assert_eq!(sell(&mut app, &cw20_addr, &wallet, 20_000), Uint128::zero());
+            assert_eq!(
+                sell(&mut app, &cw20_addr, &other_pair_addr, 20_000),
+                Uint128::zero()
+            );
+        }
+
+        #[test]
+        fn test_tax_map_validates_impact_curve() {
+            let mut tax_map = TaxMap::default();
+            tax_map.on_send.src_cond = TaxCondition::PriceImpact(impact_curve());
+            tax_map.on_send.proceeds = Addr::unchecked("proceeds");
+            assert!(tax_map.validate(None).is_ok());
+            assert_eq!(tax_map.max_tax_rate(), Decimal::percent(25));
+
+            let mut curve = impact_curve();
+            curve.curve.swap(0, 1);
+            tax_map.on_send.src_cond = TaxCondition::PriceImpact(curve);
+            assert!(tax_map.validate(None).is_err());
+
+            let mut curve = impact_curve();
+            curve.curve[2].impact = Decimal::percent(101);
+            tax_map.on_send.src_cond = TaxCondition::PriceImpact(curve);
+            assert!(tax_map.validate(None).is_err());
+        }
+    }
+
+    mod nft {
+        use super::*;
+
+        use cosmwasm_std::{
+            to_json_binary, Binary, Deps, DepsMut, Empty, Env, MessageInfo, Response,
+        };
+        use cw20::BalanceResponse;
+        use cw_multi_test::{App, Contract, ContractWrapper, Executor};
+        use cw_storage_plus::Item;
+
+        use crate::msg::{Cw20TaxedExecuteMsg as ExecuteMsg, QueryMsg};
+        use crate::testing::{instantiate_cw20, instantiate_msg, transfer_tax_map};
+
+        const OWNERS: Item<Vec<String>> = Item::new("owners");
+
+        #[cw_serde]
+        struct MintMsg {
+            owners: Vec<String>,
+        }
+
+        fn cw721_mint(
+            deps: DepsMut,
+            _env: Env,
+            _info: MessageInfo,
+            msg: MintMsg,
+        ) -> StdResult<Response> {
+            let mut owners = OWNERS.may_load(deps.storage)?.unwrap_or_default();
+            owners.extend(msg.owners);
+            OWNERS.save(deps.storage, &owners)?;
+            Ok(Response::new())
+        }
+
+        fn cw721_query(deps: Deps, _env: Env, msg: Cw721QueryMsg) -> StdResult<Binary> {
+            let Cw721QueryMsg::Tokens { owner, limit, .. } = msg;
+            let tokens = OWNERS
+                .load(deps.storage)?
+                .iter()
+                .enumerate()
+                .filter(|(_, x)| **x == owner)
+                .map(|(id, _)| id.to_string())
+                .take(limit.unwrap_or(10) as usize)
+                .collect();
+            to_json_binary(&TokensResponse { tokens })
+        }
+
+        fn cw721_contract() -> Box<dyn Contract<Empty>> {
+            // executing mints NFTs to the given owners
+            Box::new(ContractWrapper::new(cw721_mint, cw721_mint, cw721_query))
+        }
+
+        fn setup(collection: Option<&Addr>) -> (App, Addr, Addr) {
+            let mut app = App::default();
+            let cw721_id = app.store_code(cw721_contract());
+
+            let msg = MintMsg {
+                owners: vec!["holder".to_string(), "holder".to_string()],
+            };
+            let cw721_addr = app
+                .instantiate_contract(cw721_id, Addr::unchecked("owner"), &msg, &[], "NFT", None)
+                .unwrap();
+
+            let src_cond = TaxCondition::NftHolder(TaxNftHolderCondition {
+                collection: collection.unwrap_or(&cw721_addr).clone(),
+                tax_rate: Decimal::percent(5),
+            });
+            let msg = instantiate_msg()
+                .with_balance("holder", 10000)
+                .with_balance("trader", 10000)
+                .with_tax_map(transfer_tax_map(src_cond));
+            let cw20_addr = instantiate_cw20(&mut app, &msg);
+            (app, cw20_addr, cw721_addr)
+        }
+
+        // transfers 100 tokens and returns the tax the proceeds wallet received
+        fn transfer(app: &mut App, cw20_addr: &Addr, sender: &str) -> Uint128 {
+            let balance = |app: &App| {
+                let res: BalanceResponse = app
+                    .wrap()
+                    .query_wasm_smart(
+                        cw20_addr,
+                        &QueryMsg::Balance {
+                            address: "proceeds".to_string(),
+                        },
+                    )
+                    .unwrap();
+                res.balance
+            };
+            let before = balance(app);
+            let msg = ExecuteMsg::Transfer {
+                recipient: "pair".to_string(),
+                amount: Uint128::new(100),
+            };
+            app.execute_contract(Addr::unchecked(sender), cw20_addr.clone(), &msg, &[])
+                .unwrap();
+            balance(app) - before
+        }
+
+        #[test]
+        fn test_nft_holders_are_exempt() {
+            let (mut app, cw20_addr, cw721_addr) = setup(None);
+            assert_eq!(transfer(&mut app, &cw20_addr, "holder"), Uint128::zero());
+            assert_eq!(transfer(&mut app, &cw20_addr, "trader"), Uint128::new(5));
+
+            // the trader joins the community
+            let msg = MintMsg {
+                owners: vec!["trader".to_string()],
+            };
+            app.execute_contract(Addr::unchecked("owner"), cw721_addr, &msg, &[])
+                .unwrap();
+            assert_eq!(transfer(&mut app, &cw20_addr, "trader"), Uint128::zero());
+        }
+
+        #[test]
+        fn test_nft_collection_that_cannot_be_queried() {
+            let (mut app, cw20_addr, _) = setup(Some(&Addr::unchecked("no_collection")));
+            assert_eq!(transfer(&mut app, &cw20_addr, "holder"), Uint128::new(5));
+            assert_eq!(transfer(&mut app, &cw20_addr, "trader"), Uint128::new(5));
+        }
+    }
+
+    mod native_tax {
+        use super::*;
+
+        use cosmwasm_std::coins;
+        use cw20::BalanceResponse;
+        use cw_multi_test::{App, AppResponse, BankSudo, Executor, SudoMsg};
+
+        use crate::msg::{Cw20TaxedExecuteMsg as ExecuteMsg, QueryMsg};
+        use crate::testing::{instantiate_cw20, instantiate_msg, transfer_tax_map};
+
+        // token with a 10% transfer tax that can be paid with 0.5uluna per token
+        fn setup() -> (App, Addr) {
+            let mut app = App::new(|router, _, storage| {
+                router
+                    .bank
+                    .init_balance(storage, &Addr::unchecked("trader"), coins(1000, "uluna"))
+                    .unwrap();
+            });
+            let mut tax_map = transfer_tax_map(TaxCondition::Always(TaxAlwaysCondition {
+                tax_rate: Decimal::percent(10),
+            }));
+            tax_map.on_transfer.native_payment = Some(TaxNativePayment {
+                denom: "uluna".to_string(),
+                price: TaxNativePrice::Fixed(Decimal::percent(50)),
+            });
+            let msg = instantiate_msg()
+                .with_balance("trader", 10000)
+                .with_tax_map(tax_map);
+            let cw20_addr = instantiate_cw20(&mut app, &msg);
+            (app, cw20_addr)
+        }
+
+        fn transfer(
+            app: &mut App,
+            cw20_addr: &Addr,
+            funds: &[Coin],
+        ) -> Result<AppResponse, ContractError> {
+            let msg = ExecuteMsg::Transfer {
+                recipient: "pair".to_string(),
+                amount: Uint128::new(1000),
+            };
+            app.execute_contract(Addr::unchecked("trader"), cw20_addr.clone(), &msg, funds)
+                .map_err(|err| err.downcast().unwrap())
+        }
+
+        fn balance(app: &App, cw20_addr: &Addr, address: &str) -> Uint128 {
+            let msg = QueryMsg::Balance {
+                address: address.to_string(),
+            };
+            let res: BalanceResponse = app.wrap().query_wasm_smart(cw20_addr, &msg).unwrap();
+            res.balance
+        }
+
+        fn native_balance(app: &App, address: &str) -> Uint128 {
+            app.wrap().query_balance(address, "uluna").unwrap().amount
+        }
+
+        #[test]
+        fn test_tax_paid_in_native_coins() {
+            let (mut app, cw20_addr) = setup();
+
+            // 100 tokens tax cost 50uluna, the rest is refunded
+            transfer(&mut app, &cw20_addr, &coins(80, "uluna")).unwrap();
+            assert_eq!(balance(&app, &cw20_addr, "pair"), Uint128::new(1000));
+            assert_eq!(balance(&app, &cw20_addr, "trader"), Uint128::new(9000));
+            assert_eq!(balance(&app, &cw20_addr, "proceeds"), Uint128::zero());
+            assert_eq!(native_balance(&app, "proceeds"), Uint128::new(50));
+            assert_eq!(native_balance(&app, "trader"), Uint128::new(950));
+            assert_eq!(native_balance(&app, cw20_addr.as_str()), Uint128::zero());
+
+            // without coins the tax is paid in tokens
+            transfer(&mut app, &cw20_addr, &[]).unwrap();
+            assert_eq!(balance(&app, &cw20_addr, "pair"), Uint128::new(1900));
+            assert_eq!(balance(&app, &cw20_addr, "proceeds"), Uint128::new(100));
+        }
+
+        #[test]
+        fn test_invalid_native_tax_funds() {
+            let (mut app, cw20_addr) = setup();
+            app.sudo(SudoMsg::Bank(BankSudo::Mint {
+                to_address: "trader".to_string(),
+                amount: coins(100, "uatom"),
+            }))
+            .unwrap();
+
+            let err = transfer(&mut app, &cw20_addr, &coins(49, "uluna")).unwrap_err();
+            assert_eq!(
+                err,
+                ContractError::InsufficientNativeTax {
+                    required: Uint128::new(50),
+                    paid: Uint128::new(49),
+                    denom: "uluna".to_string(),
+                }
+            );
+
+            let err = transfer(&mut app, &cw20_addr, &coins(50, "uatom")).unwrap_err();
+            assert_eq!(
+                err,
+                ContractError::InvalidNativeTaxFunds {
+                    denom: "uluna".to_string(),
+                }
+            );
+            assert_eq!(native_balance(&app, "trader"), Uint128::new(1000));
+        }
+
+        #[test]
+        fn test_native_tax_funds_rejected_without_native_payment() {
+            let (mut app, cw20_addr) = setup();
+            app.sudo(SudoMsg::Bank(BankSudo::Mint {
+                to_address: "spender".to_string(),
+                amount: coins(100, "uluna"),
+            }))
+            .unwrap();
+            let msg = ExecuteMsg::IncreaseAllowance {
+                spender: "spender".to_string(),
+                amount: Uint128::new(1000),
+                expires: None,
+            };
+            app.execute_contract(Addr::unchecked("trader"), cw20_addr.clone(), &msg, &[])
+                .unwrap();
+
+            // transfer_from does not accept native tax, the coins must not be kept
+            let msg = ExecuteMsg::TransferFrom {
+                owner: "trader".to_string(),
+                recipient: "pair".to_string(),
+                amount: Uint128::new(1000),
+            };
+            let err: ContractError = app
+                .execute_contract(
+                    Addr::unchecked("spender"),
+                    cw20_addr.clone(),
+                    &msg,
+                    &coins(50, "uluna"),
+                )
+                .unwrap_err()
+                .downcast()
+                .unwrap();
+            assert_eq!(err, ContractError::NativeTaxNotAccepted {});
+            assert_eq!(native_balance(&app, "spender"), Uint128::new(100));
+            assert_eq!(native_balance(&app, cw20_addr.as_str()), Uint128::zero());
+        }
     }
 }
@@ -0,0 +1,78 @@
+//! Helpers shared by the multi-test suites of the extensions
+
+use cosmwasm_std::{Addr, Decimal, Empty, Uint128};
+use cw20::Cw20Coin;
+use cw_multi_test::{App, Contract, ContractWrapper, Executor};
+
+use crate::approval::ApprovalConfig;
+use crate::msg::InstantiateMsg;
+use crate::tax::{TaxAlwaysCondition, TaxCondition, TaxMap};
+use crate::whale::WhaleInfo;
+
+pub fn cw20_contract() -> Box<dyn Contract<Empty>> {
+    let contract = ContractWrapper::new(
+        crate::contract::execute,
+        crate::contract::instantiate,
+        crate::contract::query,
+    )
+    .with_reply(crate::contract::reply);
+    Box::new(contract)
+}
+
+/// Token without balances and extensions, configure it with the `with_*`
+/// methods below
+pub fn instantiate_msg() -> InstantiateMsg {
+    InstantiateMsg {
+        name: "Token".to_string(),
+        symbol: "TOKEN".to_string(),
+        decimals: 6,
+        ..InstantiateMsg::default()
+    }
+}
+
+impl InstantiateMsg {
+    pub fn with_balance(mut self, address: &str, amount: u128) -> Self {
+        self.initial_balances.push(Cw20Coin {
+            address: address.to_string(),
+            amount: Uint128::new(amount),
+        });
+        self
+    }
+
+    pub fn with_tax_map(mut self, tax_map: TaxMap) -> Self {
+        self.tax_map = Some(tax_map);
+        self
+    }
+
+    pub fn with_whale_info(mut self, whale_info: WhaleInfo) -> Self {
+        self.whale_info = Some(whale_info);
+        self
+    }
+
+    pub fn with_approval(mut self, approval: ApprovalConfig) -> Self {
+        self.approval = Some(approval);
+        self
+    }
+}
+
+/// Tax map taxing transfers from senders matching `src_cond`, paid to
+/// "proceeds" and administered by "admin"
+pub fn transfer_tax_map(src_cond: TaxCondition) -> TaxMap {
+    let mut tax_map = TaxMap {
+        admin: Addr::unchecked("admin"),
+        ..TaxMap::default()
+    };
+    tax_map.on_transfer.src_cond = src_cond;
+    tax_map.on_transfer.dst_cond = TaxCondition::Always(TaxAlwaysCondition {
+        tax_rate: Decimal::zero(),
+    });
+    tax_map.on_transfer.proceeds = Addr::unchecked("proceeds");
+    tax_map
+}
+
+/// Stores the token code and instantiates it, returning its address
+pub fn instantiate_cw20(app: &mut App, msg: &InstantiateMsg) -> Addr {
+    let cw20_id = app.store_code(cw20_contract());
+    app.instantiate_contract(cw20_id, Addr::unchecked("owner"), msg, &[], "TOKEN", None)
+        .unwrap()
+}
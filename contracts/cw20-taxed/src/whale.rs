@@ -1,11 +1,13 @@
 use std::char::REPLACEMENT_CHARACTER;
 
 use cosmwasm_std::{DepsMut, Env, MessageInfo, Response};
-use cosmwasm_std::{Addr, Decimal, StdError, StdResult, Storage, Uint128};
+use cosmwasm_std::{Addr, Api, Decimal, Storage, Uint128};
 use crate::ContractError;
+use crate::events::{AdminChangedEvent, WhaleInfoUpdatedEvent};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use crate::state::ANTI_WHALE_INFO;
+use crate::state::{PendingAdmin, ANTI_WHALE_INFO, PENDING_WHALE_ADMIN};
+use cw20::Expiration;
 
 use crate::state::TOKEN_INFO;
 
@@ -27,7 +29,7 @@ impl WhaleInfo {
         storage: &dyn Storage,
         addr: &Addr,
         amount: Uint128,
-    ) -> StdResult<()> {
+    ) -> Result<(), ContractError> {
         if self.is_allowed(addr) {
             return Ok(());
         }
@@ -38,10 +40,11 @@ impl WhaleInfo {
         // can used unchecked mul here, as threshold is between 0 and 1
         let max_allowed = total_supply * self.threshold;
         if amount.gt(&max_allowed) {
-            return Err(StdError::generic_err(format!(
-                "Address {} is holding too many tokens. Max allowed: {}. Tx results in: {}",
-                addr, max_allowed, amount
-            )));
+            return Err(ContractError::WhaleLimitExceeded {
+                address: addr.to_string(),
+                max: max_allowed,
+                resulting: amount,
+            });
         }
 
         Ok(())
@@ -51,9 +54,24 @@ impl WhaleInfo {
         self.whitelist.contains(addr)
     }
 
-    pub fn validate(&self) -> StdResult<()> {
+    pub fn validate(&self) -> Result<(), ContractError> {
         if self.threshold > Decimal::one() {
-            return Err(StdError::generic_err("Threshold must be between 0 and 1"));
+            return Err(ContractError::InvalidWhaleThreshold {
+                threshold: self.threshold,
+            });
+        }
+        Ok(())
+    }
+
+    // like validate, but also checks the admin and the whitelisted addresses
+    pub fn validate_with_api(&self, api: &dyn Api) -> Result<(), ContractError> {
+        self.validate()?;
+        // an empty admin is a renounced one
+        if !self.admin.as_str().is_empty() {
+            api.addr_validate(self.admin.as_str())?;
+        }
+        for addr in self.whitelist.iter() {
+            api.addr_validate(addr.as_str())?;
         }
         Ok(())
     }
@@ -64,28 +82,101 @@ pub fn execute_set_whale_info(
     env: Env, info: MessageInfo,
     whale_info: WhaleInfo
 ) -> Result<Response, ContractError> {
-    let mut old_whale_info = ANTI_WHALE_INFO.load(deps.storage)?;
-    whale_info.validate()?;
+    let old_whale_info = ANTI_WHALE_INFO.load(deps.storage)?;
+    whale_info.validate_with_api(deps.api)?;
     if info.sender != old_whale_info.admin {
         return Err(ContractError::Unauthorized {});
     }
+    // the admin is only handed over by proposing and accepting
+    if whale_info.admin != old_whale_info.admin {
+        return Err(ContractError::AdminChangeNotAllowed {});
+    }
     ANTI_WHALE_INFO.save(deps.storage, &whale_info)?;
-    Ok(Response::new())
+    Ok(Response::new()
+        .add_attribute("action", "set_whale_info")
+        .add_event(WhaleInfoUpdatedEvent { whale_info: &whale_info }.into()))
 }
 
+// proposes a new whale admin, the role is handed over once the new admin accepts
 pub fn execute_set_whale_admin(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    admin: Addr
+    admin: Addr,
+    expires: Option<Expiration>,
 ) -> Result<Response, ContractError> {
+    let old_info = ANTI_WHALE_INFO.load(deps.storage)?;
+    if info.sender != old_info.admin {
+        return Err(ContractError::Unauthorized{});
+    }
+    let pending = PendingAdmin::new(admin, expires, &env.block)?;
+    PENDING_WHALE_ADMIN.save(deps.storage, &pending)?;
+    Ok(Response::new()
+        .add_attribute("action", "propose_whale_admin")
+        .add_attribute("pending_admin", pending.admin)
+        .add_attribute("expires", pending.expires.to_string()))
+}
+
+pub fn execute_accept_whale_admin(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let pending = PENDING_WHALE_ADMIN
+        .may_load(deps.storage)?
+        .ok_or(ContractError::NoPendingAdmin {})?;
+    pending.assert_can_accept(&info.sender, &env.block)?;
+
     let mut old_info = ANTI_WHALE_INFO.load(deps.storage)?;
+    let previous = std::mem::replace(&mut old_info.admin, pending.admin);
+    ANTI_WHALE_INFO.save(deps.storage, &old_info)?;
+    PENDING_WHALE_ADMIN.remove(deps.storage);
+    Ok(Response::new()
+        .add_event(AdminChangedEvent {
+            role: "whale",
+            previous: &previous,
+            new: &old_info.admin,
+        }.into())
+        .add_attribute("action", "accept_whale_admin")
+        .add_attribute("admin", old_info.admin))
+}
+
+pub fn execute_cancel_whale_admin(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let old_info = ANTI_WHALE_INFO.load(deps.storage)?;
     if info.sender != old_info.admin {
         return Err(ContractError::Unauthorized{});
     }
-    old_info.admin = admin;
+    if PENDING_WHALE_ADMIN.may_load(deps.storage)?.is_none() {
+        return Err(ContractError::NoPendingAdmin {});
+    }
+    PENDING_WHALE_ADMIN.remove(deps.storage);
+    Ok(Response::new().add_attribute("action", "cancel_whale_admin"))
+}
+
+// gives up the whale admin role for good
+pub fn execute_renounce_whale_admin(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let mut old_info = ANTI_WHALE_INFO.load(deps.storage)?;
+    if info.sender != old_info.admin {
+        return Err(ContractError::Unauthorized{});
+    }
+    let previous = std::mem::replace(&mut old_info.admin, Addr::unchecked(""));
     ANTI_WHALE_INFO.save(deps.storage, &old_info)?;
-    Ok(Response::new())
+    PENDING_WHALE_ADMIN.remove(deps.storage);
+    Ok(Response::new()
+        .add_event(AdminChangedEvent {
+            role: "whale",
+            previous: &previous,
+            new: &old_info.admin,
+        }.into())
+        .add_attribute("action", "renounce_whale_admin"))
 }
 
 #[cfg(test)]
@@ -169,7 +260,14 @@ mod test {
         assert!(info.assert_no_whale(storage, &addr2, fish_amount).is_ok());
 
         // not allowed to have more than 10% of total supply
-        assert!(info.assert_no_whale(storage, &addr3, whale_amount).is_err());
+        assert_eq!(
+            info.assert_no_whale(storage, &addr3, whale_amount),
+            Err(ContractError::WhaleLimitExceeded {
+                address: addr3.to_string(),
+                max: Uint128::new(100_000_000_000),
+                resulting: whale_amount,
+            })
+        );
         assert!(info.assert_no_whale(storage, &addr3, fish_amount).is_ok());
     }
 
@@ -239,12 +337,41 @@ mod test {
         // mock info being set by instantiation
         super::ANTI_WHALE_INFO.save(deps.as_mut().storage, &old_whale_info).unwrap();
 
-        super::execute_set_whale_admin(deps.as_mut(), mock_env(), info, Addr::unchecked("admin2")).unwrap();
+        super::execute_set_whale_admin(deps.as_mut(), mock_env(), info, Addr::unchecked("admin2"), None).unwrap();
+
+        // admin2 did not accept yet
+        let new_info = super::ANTI_WHALE_INFO.load(deps.as_mut().storage).unwrap();
+        assert_eq!(new_info, old_whale_info);
+
+        let info = mock_info("admin2", &[]);
+        super::execute_accept_whale_admin(deps.as_mut(), mock_env(), info).unwrap();
 
         let new_info = super::ANTI_WHALE_INFO.load(deps.as_mut().storage).unwrap();
         assert_eq!(new_info, expected_whale_info)
     }
 
+    #[test]
+    fn test_renounce_whale_admin() {
+        let mut deps = mock_dependencies();
+        let old_whale_info = super::WhaleInfo {
+            threshold: Decimal::percent(10),
+            whitelist: vec![],
+            admin: Addr::unchecked("admin"),
+        };
+        super::ANTI_WHALE_INFO.save(deps.as_mut().storage, &old_whale_info).unwrap();
+
+        let info = mock_info("admin", &[]);
+        super::execute_set_whale_admin(deps.as_mut(), mock_env(), info.clone(), Addr::unchecked("admin2"), None).unwrap();
+        super::execute_renounce_whale_admin(deps.as_mut(), mock_env(), info).unwrap();
+
+        // renouncing drops pending proposals as well
+        let info = mock_info("admin2", &[]);
+        let res = super::execute_accept_whale_admin(deps.as_mut(), mock_env(), info);
+        assert_eq!(res, Err(ContractError::NoPendingAdmin {}));
+        let new_info = super::ANTI_WHALE_INFO.load(deps.as_mut().storage).unwrap();
+        assert_eq!(new_info.admin, Addr::unchecked(""));
+    }
+
     #[test]
     fn test_set_whale_admin_unauthorized() {
         let mut deps = mock_dependencies();
@@ -258,7 +385,7 @@ mod test {
         // mock info being set by instantiation
         super::ANTI_WHALE_INFO.save(deps.as_mut().storage, &old_whale_info).unwrap();
 
-        let res = super::execute_set_whale_admin(deps.as_mut(), mock_env(), info, Addr::unchecked("admin2"));
+        let res = super::execute_set_whale_admin(deps.as_mut(), mock_env(), info, Addr::unchecked("admin2"), None);
         match res {
             Ok(_) => {panic!("unexpected success of setting admin!")},
             Err(e) => {assert_eq!(e, ContractError::Unauthorized {  })}